@@ -0,0 +1,26 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+/// Writes the C header for the `ffi` module to `$OUT_DIR/pyth_hermes.h`
+///
+/// Only `src/ffi.rs` is parsed, so generating the header doesn't run `cargo metadata` and works offline. The copy
+/// checked in as `include/pyth_hermes.h` is kept up to date by `ffi::test::test_header_up_to_date`.
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    let crate_dir =
+        std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{crate_dir}/src/ffi.rs"))
+        .generate()
+        .expect("failed to generate C header")
+        .write_to_file(format!("{out_dir}/pyth_hermes.h"));
+}
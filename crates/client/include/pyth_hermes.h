@@ -0,0 +1,124 @@
+#ifndef PYTH_HERMES_H
+#define PYTH_HERMES_H
+
+/* Generated from src/ffi.rs by build.rs when the ffi feature is enabled, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Outcome of a call
+ */
+typedef enum HermesStatus {
+  HermesStatus_Ok = 0,
+  /**
+   * A pointer was null or an argument was malformed
+   */
+  HermesStatus_InvalidArgument = -1,
+  /**
+   * The request to hermes failed
+   */
+  HermesStatus_RequestFailed = -2,
+} HermesStatus;
+
+/**
+ * Client handle, created with [`hermes_client_new`]
+ */
+typedef struct HermesClient HermesClient;
+
+/**
+ * Subscription handle, created with [`hermes_subscribe`]
+ */
+typedef struct HermesSubscription HermesSubscription;
+
+/**
+ * Price of a feed, where the value is `price * 10^expo`
+ */
+typedef struct HermesPrice {
+  uint8_t id[32];
+  int64_t price;
+  uint64_t conf;
+  int32_t expo;
+  /**
+   * Unix timestamp in seconds
+   */
+  int64_t publish_time;
+} HermesPrice;
+
+/**
+ * Invoked with each price update and the `user_data` passed to [`hermes_subscribe`]
+ */
+typedef void (*HermesPriceCallback)(const struct HermesPrice *price, void *user_data);
+
+/**
+ * Message describing the last failure on the calling thread, or null if there was none
+ *
+ * The message is valid until the next call on the same thread.
+ */
+const char *hermes_last_error(void);
+
+/**
+ * Creates a client for the hermes deployment at `base_url`, returning null on failure
+ *
+ * # Safety
+ *
+ * `base_url` must be null or a valid NUL terminated string.
+ */
+struct HermesClient *hermes_client_new(const char *base_url);
+
+/**
+ * Frees a client, stopping the subscriptions created with it
+ *
+ * # Safety
+ *
+ * `client` must be null or returned by [`hermes_client_new`], and not used after this call.
+ */
+void hermes_client_free(struct HermesClient *client);
+
+/**
+ * Fetches the latest prices of `count` feeds, whose 32 byte ids are stored contiguously in `ids`
+ *
+ * Up to `count` prices are written to `out`, and the number written to `written`. Feeds hermes doesn't return a
+ * price for are left out.
+ *
+ * # Safety
+ *
+ * `client` must be a live client, `ids` must point to `count * 32` bytes, `out` to space for `count` prices and
+ * `written` to a `size_t`.
+ */
+enum HermesStatus hermes_latest_prices(const struct HermesClient *client,
+                                       const uint8_t *ids,
+                                       size_t count,
+                                       struct HermesPrice *out,
+                                       size_t *written);
+
+/**
+ * Streams price updates for `count` feeds, invoking `callback` with each from a background thread
+ *
+ * Returns null on failure. The stream runs until it is freed with [`hermes_subscription_free`] or its client is
+ * freed.
+ *
+ * # Safety
+ *
+ * `client` must be a live client and `ids` must point to `count * 32` bytes. `callback` must be safe to call with
+ * `user_data` from another thread until the subscription is freed.
+ */
+struct HermesSubscription *hermes_subscribe(const struct HermesClient *client,
+                                            const uint8_t *ids,
+                                            size_t count,
+                                            HermesPriceCallback callback,
+                                            void *user_data);
+
+/**
+ * Stops and frees a subscription
+ *
+ * # Safety
+ *
+ * `subscription` must be null or returned by [`hermes_subscribe`], and not used after this call.
+ */
+void hermes_subscription_free(struct HermesSubscription *subscription);
+
+#endif  /* PYTH_HERMES_H */
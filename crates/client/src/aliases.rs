@@ -0,0 +1,173 @@
+//! Application defined aliases for feeds, such as `WETH` or internal instrument codes, usable wherever symbols are
+//!
+//! Aliases are shared by every clone of a [`HermesClient`] and ignore case. They are resolved by
+//! [`HermesClient::resolve_feed_symbols`] ahead of pyth's own symbols, and by [`HermesClient::resolve_feeds`] ahead
+//! of hex feed ids. An alias which is also one of pyth's symbols must name the same feed, see
+//! [`HermesClient::define_feed_alias`].
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::{feed_ids, HermesClient};
+//!
+//! let client = HermesClient::new("https://hermes.pyth.network");
+//! client.define_feed_alias("WETH", feed_ids::ETH_USD).await?;
+//! let feeds = client.get_latest_price_feeds(&client.resolve_feed_symbols(&["weth"]).await?).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{error::HermesError, types::FeedId, HermesClient},
+    std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    },
+};
+
+/// Registry of feed aliases, see the [module documentation](self)
+#[derive(Debug, Clone, Default)]
+pub struct FeedAliases(Arc<RwLock<HashMap<String, FeedId>>>);
+
+impl FeedAliases {
+    /// Defines or replaces `alias`, without checking it against pyth's symbols
+    pub fn define(&self, alias: impl AsRef<str>, id: FeedId) {
+        self.write().insert(alias.as_ref().to_lowercase(), id);
+    }
+
+    /// Removes `alias`, returning its feed
+    pub fn remove(&self, alias: &str) -> Option<FeedId> {
+        self.write().remove(&alias.to_lowercase())
+    }
+
+    pub fn get(&self, alias: &str) -> Option<FeedId> {
+        self.read().get(&alias.to_lowercase()).copied()
+    }
+
+    /// Every alias, lowercased and sorted
+    pub fn aliases(&self) -> Vec<(String, FeedId)> {
+        let mut aliases: Vec<_> = self.read().iter().map(|(a, id)| (a.clone(), *id)).collect();
+        aliases.sort();
+        aliases
+    }
+
+    pub(crate) fn lookup(&self, aliases: &[impl AsRef<str>]) -> Vec<Option<FeedId>> {
+        let map = self.read();
+        aliases
+            .iter()
+            .map(|alias| map.get(&alias.as_ref().to_lowercase()).copied())
+            .collect()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, FeedId>> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, FeedId>> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HermesClient {
+    /// Feed aliases shared by the client and its clones
+    pub fn feed_aliases(&self) -> &FeedAliases {
+        &self.feed_aliases
+    }
+
+    /// Defines `alias` for `id`, failing with [`HermesError::AliasConflict`] if it's the symbol of another feed
+    ///
+    /// The symbols are taken from the cached metadata, which is fetched if it hasn't been yet.
+    pub async fn define_feed_alias(&self, alias: &str, id: FeedId) -> Result<(), HermesError> {
+        check_conflicts(
+            &[(alias.to_string(), id)],
+            self.pyth_symbol_ids(&[alias]).await?,
+        )?;
+        self.feed_aliases.define(alias, id);
+        Ok(())
+    }
+
+    /// Checks every alias against pyth's symbols, such as those defined with
+    /// [`HermesClientBuilder::feed_alias`](crate::builder::HermesClientBuilder::feed_alias)
+    pub async fn check_feed_aliases(&self) -> Result<(), HermesError> {
+        let aliases = self.feed_aliases.aliases();
+        let names: Vec<&str> = aliases.iter().map(|(alias, _)| alias.as_str()).collect();
+        check_conflicts(&aliases, self.pyth_symbol_ids(&names).await?)
+    }
+}
+
+/// Fails with the first alias which is the symbol of a different feed
+fn check_conflicts(
+    aliases: &[(String, FeedId)],
+    symbol_ids: Vec<Option<FeedId>>,
+) -> Result<(), HermesError> {
+    for ((alias, id), symbol_id) in aliases.iter().zip(symbol_ids) {
+        if let Some(symbol_id) = symbol_id.filter(|symbol_id| symbol_id != id) {
+            return Err(HermesError::AliasConflict {
+                alias: alias.clone(),
+                id: *id,
+                symbol_id,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD},
+        crate::mock::{MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_feed_aliases() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/price_feeds",
+            serde_json::json!([
+                {"id": ETH_USD, "attributes": {"symbol": "Crypto.ETH/USD"}},
+                {"id": BTC_USD, "attributes": {"symbol": "Crypto.BTC/USD"}},
+            ]),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .feed_alias("XBT", BTC_USD)
+            .build()
+            .unwrap();
+        client.check_feed_aliases().await.unwrap();
+        client
+            .clone()
+            .define_feed_alias("WETH", ETH_USD)
+            .await
+            .unwrap();
+        // aliasing a symbol to its own feed is harmless
+        client
+            .define_feed_alias("Crypto.ETH/USD", ETH_USD)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client
+                .resolve_feed_symbols(&["weth", "Crypto.BTC/USD", "xbt"])
+                .await
+                .unwrap(),
+            [ETH_USD, BTC_USD, BTC_USD]
+        );
+        assert_eq!(client.resolve_feeds(&["WETH"]).unwrap(), [ETH_USD]);
+
+        let err = client
+            .define_feed_alias("crypto.btc/usd", ETH_USD)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            HermesError::AliasConflict { symbol_id, .. } if symbol_id == BTC_USD
+        ));
+        assert!(client.feed_aliases().get("crypto.btc/usd").is_none());
+
+        client.feed_aliases().define("Crypto.BTC/USD", ETH_USD);
+        assert!(client.check_feed_aliases().await.is_err());
+        // the metadata is only fetched once
+        assert_eq!(mock.requests().await.len(), 1);
+    }
+}
@@ -0,0 +1,203 @@
+//! Authentication for hermes deployments fronted by an auth layer
+
+use {
+    crate::error::{BoxError, HermesError},
+    futures_util::future::BoxFuture,
+    reqwest::{
+        header::{HeaderName, HeaderValue},
+        RequestBuilder,
+    },
+};
+
+/// Provides bearer tokens attached to every REST request and SSE connection
+///
+/// The token is requested before each request, so implementations should cache tokens until they expire.
+pub trait AuthProvider: Send + Sync {
+    /// Returns the token to authenticate requests with
+    fn get_token(&self) -> BoxFuture<'_, Result<String, BoxError>>;
+
+    /// Called after hermes rejects a token with a 401, returns a freshly issued token
+    ///
+    /// Defaults to [`AuthProvider::get_token`]
+    fn refresh_token(&self) -> BoxFuture<'_, Result<String, BoxError>> {
+        self.get_token()
+    }
+}
+
+/// A fixed bearer token, see
+/// [`HermesClientBuilder::bearer_token`](crate::builder::HermesClientBuilder::bearer_token)
+pub struct StaticToken(pub String);
+
+impl AuthProvider for StaticToken {
+    fn get_token(&self) -> BoxFuture<'_, Result<String, BoxError>> {
+        Box::pin(async move { Ok(self.0.clone()) })
+    }
+}
+
+/// Credential attached to every request by providers which authenticate with an API key
+#[derive(Clone)]
+pub(crate) enum ApiKey {
+    Header { name: String, key: String },
+    Query { name: String, key: String },
+}
+
+impl ApiKey {
+    /// Checks a header key can be sent, so requests don't fail later
+    pub(crate) fn validate(&self) -> Result<(), HermesError> {
+        if let Self::Header { name, key } = self {
+            HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                HermesError::InvalidHeader(format!("invalid api key header {name:?}"))
+            })?;
+            HeaderValue::from_str(key).map_err(|_| {
+                HermesError::InvalidHeader("api key is not a valid header value".to_string())
+            })?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Header { name, key } => {
+                let mut value = HeaderValue::from_str(key).expect("validated when built");
+                value.set_sensitive(true);
+                req.header(name.as_str(), value)
+            }
+            Self::Query { name, key } => req.query(&[(name, key)]),
+        }
+    }
+}
+
+/// The key is omitted
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Header { name, .. } => write!(f, "ApiKey::Header({name}: ***)"),
+            Self::Query { name, .. } => write!(f, "ApiKey::Query({name}=***)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+            HermesClient,
+        },
+        std::sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Issues `token-N` where N increments on every refresh
+    #[derive(Default)]
+    struct RotatingToken {
+        refreshes: AtomicUsize,
+    }
+
+    impl AuthProvider for RotatingToken {
+        fn get_token(&self) -> BoxFuture<'_, Result<String, BoxError>> {
+            Box::pin(async move { Ok(format!("token-{}", self.refreshes.load(Ordering::SeqCst))) })
+        }
+
+        fn refresh_token(&self) -> BoxFuture<'_, Result<String, BoxError>> {
+            self.refreshes.fetch_add(1, Ordering::SeqCst);
+            self.get_token()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_refresh_on_unauthorized() {
+        let path = "/v2/updates/price/latest";
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status(path, 401),
+            RecordedResponse::json(path, price_update_json(&[ETH_USD], 1, 1)),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .auth_provider(RotatingToken::default())
+            .build()
+            .unwrap();
+        let feeds = client.get_latest_price_feeds(&[ETH_USD]).await.unwrap();
+        assert_eq!(feeds[0].id, ETH_USD);
+        let requests = mock.requests().await;
+        assert_eq!(requests[0].headers["authorization"], "Bearer token-0");
+        assert_eq!(requests[1].headers["authorization"], "Bearer token-1");
+    }
+
+    #[tokio::test]
+    async fn test_auth_refresh_on_unauthorized_stream() {
+        let path = "/v2/updates/price/stream";
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status(path, 401),
+            RecordedResponse::events(path, vec![price_update_json(&[ETH_USD], 1, 1).to_string()]),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .auth_provider(RotatingToken::default())
+            .build()
+            .unwrap();
+        let (mut rx, handle) = client.subscribe(&[ETH_USD]).await.unwrap().into_parts();
+        let update = rx.recv().await.unwrap();
+        handle.abort();
+        assert_eq!(update.id, ETH_USD);
+        let requests = mock.requests().await;
+        assert_eq!(requests[0].headers["authorization"], "Bearer token-0");
+        assert_eq!(requests[1].headers["authorization"], "Bearer token-1");
+    }
+
+    #[tokio::test]
+    async fn test_api_keys() {
+        let path = "/v2/updates/price/latest";
+        let response = RecordedResponse::json(path, price_update_json(&[ETH_USD], 1, 1));
+        let mock = MockHermes::start(vec![response.clone(), response.clone(), response])
+            .await
+            .unwrap();
+        for builder in [
+            HermesClient::builder(mock.url()).bearer_token("secret"),
+            HermesClient::builder(mock.url()).api_key_header("X-Api-Key", "secret"),
+            HermesClient::builder(mock.url()).api_key_query("api_key", "secret"),
+        ] {
+            let client = builder.build().unwrap();
+            client.get_latest_price_feeds(&[ETH_USD]).await.unwrap();
+        }
+        let requests = mock.requests().await;
+        assert_eq!(requests[0].headers["authorization"], "Bearer secret");
+        assert_eq!(requests[1].headers["x-api-key"], "secret");
+        assert!(requests[2]
+            .query
+            .as_ref()
+            .unwrap()
+            .ends_with("&api_key=secret"));
+
+        let invalid = HermesClient::builder(mock.url())
+            .api_key_header("X-Api-Key", "line\nbreak")
+            .build();
+        assert!(matches!(invalid, Err(HermesError::InvalidHeader(_))));
+    }
+
+    #[tokio::test]
+    async fn test_api_key_stream_reconnect() {
+        let path = "/v2/updates/price/stream";
+        let events =
+            RecordedResponse::events(path, vec![price_update_json(&[ETH_USD], 1, 1).to_string()]);
+        let mock = MockHermes::start(vec![events.clone(), events])
+            .await
+            .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .api_key_query("api_key", "secret")
+            .build()
+            .unwrap();
+        let (mut rx, handle) = client.subscribe(&[ETH_USD]).await.unwrap().into_parts();
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+        handle.abort();
+        let requests = mock.requests().await;
+        assert!(requests.len() >= 2);
+        for request in &requests[..2] {
+            assert!(request.query.as_ref().unwrap().contains("api_key=secret"));
+        }
+    }
+}
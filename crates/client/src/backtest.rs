@@ -0,0 +1,352 @@
+//! Running strategies against replayed updates, with the same interface as live streams
+//!
+//! A [`Strategy`] receives each update along with a [`Market`] view of recent prices. The same strategy can be run
+//! by a [`Backtest`] over recorded or simulated updates, or against hermes with [`HermesClient::run_strategy`]. The
+//! market measures time with a [`Clock`], simulated during backtests, scores updates with a [`ZScoreTracker`] and
+//! calculates TWAPs like [`HermesClient::compare_twap`].
+//!
+//! ```
+//! use pyth_hermes_rs::{
+//!     backtest::{Backtest, Market, Strategy},
+//!     types::ParsedPriceUpdate,
+//! };
+//!
+//! struct CountUpdates(usize);
+//!
+//! impl Strategy for CountUpdates {
+//!     fn on_update(&mut self, _market: &Market, _update: &ParsedPriceUpdate) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! let mut strategy = CountUpdates(0);
+//! let report = Backtest::new(Vec::new()).run(&mut strategy);
+//! assert_eq!(report.updates, strategy.0);
+//! ```
+
+use {
+    crate::{
+        clock::{Clock, SystemClock},
+        error::HermesError,
+        stream::{StreamHandle, StreamOptions},
+        twap::time_weighted_average,
+        types::*,
+        zscore::{ScoredUpdate, ZScoreTracker},
+        HermesClient,
+    },
+    std::{
+        collections::{HashMap, VecDeque},
+        fmt,
+        sync::{
+            atomic::{AtomicI64, Ordering},
+            Arc,
+        },
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// How long price history is kept for TWAP calculations by default
+const DEFAULT_HISTORY: Duration = Duration::from_secs(60 * 60);
+
+/// Returns each update is scored against by default, see [`ZScoreTracker`]
+const DEFAULT_Z_SCORE_WINDOW: usize = 30;
+
+/// Logic driven by price updates, either live or in a backtest
+pub trait Strategy: Send {
+    fn on_update(&mut self, market: &Market, update: &ParsedPriceUpdate);
+}
+
+/// Recent prices observed by a strategy
+pub struct Market {
+    clock: Arc<dyn Clock>,
+    history: Duration,
+    /// Latest update of each feed, scored by `z_scores`
+    latest: HashMap<FeedId, ScoredUpdate>,
+    z_scores: ZScoreTracker,
+    /// `(publish_time, price)` pairs per feed, oldest first
+    prices: HashMap<FeedId, VecDeque<(i64, f64)>>,
+}
+
+impl Market {
+    /// Market keeping `history` worth of prices per feed, at the time of `clock`
+    pub fn new(clock: Arc<dyn Clock>, history: Duration) -> Self {
+        Self {
+            clock,
+            history,
+            latest: HashMap::new(),
+            z_scores: ZScoreTracker::new(DEFAULT_Z_SCORE_WINDOW),
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Scores updates against the `window` most recent returns of their feed, see [`ZScoreTracker::new`]
+    ///
+    /// # Panics
+    ///
+    /// If `window` is less than 2
+    pub fn z_score_window(mut self, window: usize) -> Self {
+        self.z_scores = ZScoreTracker::new(window);
+        self
+    }
+
+    /// Current time, simulated during backtests
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// Latest update received for `id`
+    pub fn latest(&self, id: &FeedId) -> Option<&ParsedPriceUpdate> {
+        Some(&self.latest.get(id)?.update)
+    }
+
+    /// Latest update received for `id` along with its return and z-score, see [`ZScoreTracker`]
+    pub fn scored(&self, id: &FeedId) -> Option<&ScoredUpdate> {
+        self.latest.get(id)
+    }
+
+    /// Time since the latest price for `id` was published, like [`HermesClient::staleness`]
+    pub fn staleness(&self, id: &FeedId) -> Option<Duration> {
+        Some(self.latest(id)?.price.age_at(self.now()))
+    }
+
+    /// Time weighted average price of `id` over the `window` ending now, from the prices observed so far
+    ///
+    /// `None` until time has passed since the feed's first price.
+    pub fn twap(&self, id: &FeedId, window: Duration) -> Option<f64> {
+        let prices = self.prices.get(id)?;
+        let now = unix_seconds(self.now());
+        let start = now - window.as_secs() as i64;
+        let (twap, _) = time_weighted_average(prices.iter().copied().collect(), start, now)?;
+        Some(twap)
+    }
+
+    /// Records an update, dropping prices which are older than the history kept
+    pub fn record(&mut self, update: &ParsedPriceUpdate) {
+        if let Some(price) = update.price.to_f64() {
+            // keep the newest price from before the cutoff, as it applies until the following one
+            let cutoff = unix_seconds(self.now()) - self.history.as_secs() as i64;
+            let prices = self.prices.entry(update.id).or_default();
+            prices.push_back((update.price.publish_time, price));
+            while prices.get(1).is_some_and(|(time, _)| *time <= cutoff) {
+                prices.pop_front();
+            }
+        }
+        self.latest
+            .insert(update.id, self.z_scores.update(update.clone()));
+    }
+}
+
+impl fmt::Debug for Market {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Market")
+            .field("now", &self.now())
+            .field("history", &self.history)
+            .field("latest", &self.latest)
+            .field("z_scores", &self.z_scores)
+            .field("prices", &self.prices)
+            .finish()
+    }
+}
+
+/// Market at the time of the system clock
+impl Default for Market {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock), DEFAULT_HISTORY)
+    }
+}
+
+/// Clock which only advances when set, shared between clones
+///
+/// Backtests advance it to the publish time of each update, clients built with it measure staleness in simulated
+/// time.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedClock(Arc<AtomicI64>);
+
+impl SimulatedClock {
+    pub fn set(&self, unix_seconds: i64) {
+        self.0.store(unix_seconds, Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.0.load(Ordering::Relaxed).max(0) as u64)
+    }
+}
+
+/// Replays updates through a [`Strategy`] in publish time order
+pub struct Backtest {
+    updates: Vec<ParsedPriceUpdate>,
+    clock: SimulatedClock,
+    history: Duration,
+    z_score_window: usize,
+}
+
+/// Summary of a completed [`Backtest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktestReport {
+    /// Number of updates delivered to the strategy
+    pub updates: usize,
+    /// Publish time of the first and last update
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl Backtest {
+    pub fn new(mut updates: Vec<ParsedPriceUpdate>) -> Self {
+        updates.sort_by_key(|update| update.price.publish_time);
+        Self {
+            updates,
+            clock: SimulatedClock::default(),
+            history: DEFAULT_HISTORY,
+            z_score_window: DEFAULT_Z_SCORE_WINDOW,
+        }
+    }
+
+    /// How much price history the [`Market`] keeps for TWAP calculations
+    pub fn history(mut self, history: Duration) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Returns the [`Market`] scores each update against, see [`Market::z_score_window`]
+    pub fn z_score_window(mut self, window: usize) -> Self {
+        self.z_score_window = window;
+        self
+    }
+
+    /// Clock advanced to each update's publish time as the backtest runs
+    pub fn clock(&self) -> SimulatedClock {
+        self.clock.clone()
+    }
+
+    pub fn run(&self, strategy: &mut impl Strategy) -> BacktestReport {
+        let mut market = Market::new(Arc::new(self.clock.clone()), self.history)
+            .z_score_window(self.z_score_window);
+        for update in &self.updates {
+            self.clock.set(update.price.publish_time);
+            market.record(update);
+            strategy.on_update(&market, update);
+        }
+        BacktestReport {
+            updates: self.updates.len(),
+            start: self.updates.first().map(|u| u.price.publish_time),
+            end: self.updates.last().map(|u| u.price.publish_time),
+        }
+    }
+}
+
+impl HermesClient {
+    /// Streams price updates through `strategy`, with the market's time taken from the client's [`Clock`]
+    pub async fn run_strategy<S>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        mut strategy: S,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        S: Strategy + 'static,
+    {
+        let mut market = Market::new(self.clock.clone(), DEFAULT_HISTORY);
+        self.stream_price_updates_with_options(ids, StreamOptions::default(), move |update| {
+            market.record(&update);
+            strategy.on_update(&market, &update);
+        })
+        .await
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::ETH_USD,
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    fn update(price: i64, publish_time: i64) -> ParsedPriceUpdate {
+        let json = price_update_json(&[ETH_USD], price, publish_time);
+        serde_json::from_value(json["parsed"][0].clone()).unwrap()
+    }
+
+    /// Records the 10 second twap, staleness and return at each update
+    #[derive(Default)]
+    struct Recorder(Vec<(Option<f64>, Option<Duration>, Option<f64>)>);
+
+    impl Strategy for Recorder {
+        fn on_update(&mut self, market: &Market, update: &ParsedPriceUpdate) {
+            self.0.push((
+                market.twap(&update.id, Duration::from_secs(10)),
+                market.staleness(&update.id),
+                market.scored(&update.id).and_then(|scored| scored.return_),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_backtest() {
+        let backtest = Backtest::new(vec![
+            update(300_000_000, 1020),
+            update(100_000_000, 1000),
+            update(200_000_000, 1015),
+        ]);
+        let mut recorder = Recorder::default();
+        let report = backtest.run(&mut recorder);
+        assert_eq!(report.updates, 3);
+        assert_eq!((report.start, report.end), (Some(1000), Some(1020)));
+        assert_eq!(
+            backtest.clock().now(),
+            UNIX_EPOCH + Duration::from_secs(1020)
+        );
+
+        let twaps: Vec<_> = recorder.0.iter().map(|(twap, _, _)| *twap).collect();
+        // a new price has no weight until time passes, the last window is 1.0 for 5s and 2.0 for 5s
+        assert_eq!(twaps, [None, Some(1.0), Some(1.5)]);
+        assert!(recorder
+            .0
+            .iter()
+            .all(|(_, staleness, _)| *staleness == Some(Duration::ZERO)));
+        let returns: Vec<_> = recorder.0.iter().map(|(_, _, r)| *r).collect();
+        assert_eq!(returns, [None, Some(1.0), Some(0.5)]);
+    }
+
+    #[test]
+    fn test_backtest_negative_prices() {
+        let backtest = Backtest::new(vec![update(-200_000_000, 1000), update(-100_000_000, 1010)]);
+        let mut recorder = Recorder::default();
+        backtest.run(&mut recorder);
+        assert_eq!(recorder.0[1].0, Some(-2.0));
+        assert_eq!(recorder.0[1].2, Some(-0.5));
+    }
+
+    #[tokio::test]
+    async fn test_run_strategy() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 100_000_000, 5).to_string()],
+        )])
+        .await
+        .unwrap();
+        let clock = SimulatedClock::default();
+        clock.set(8);
+        let client = HermesClient::builder(mock.url())
+            .clock(clock)
+            .build()
+            .unwrap();
+
+        struct Forward(tokio::sync::mpsc::UnboundedSender<Option<Duration>>);
+        impl Strategy for Forward {
+            fn on_update(&mut self, market: &Market, update: &ParsedPriceUpdate) {
+                let _ = self.0.send(market.staleness(&update.id));
+            }
+        }
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = client.run_strategy(&[ETH_USD], Forward(tx)).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), Some(Duration::from_secs(3)));
+        handle.abort();
+    }
+}
@@ -0,0 +1,278 @@
+//! Command line tools for deployments of the Pyth Hermes API
+//!
+//! Usage: `hermes [--url <base url>] <command>`
+//!
+//! Commands:
+//!
+//! * `schema-check` - Reports fields returned by hermes which are not modelled by the crate's types
+//! * `search <query>` - Lists feeds whose symbol, assets or description match the query
+//! * `feed-ids [symbol...]` - Prints the `feed_ids` module regenerated from the deployment's metadata, with the
+//!   given symbols added to the module's
+
+use {
+    pyth_hermes_rs::{
+        feed_ids,
+        search::FeedIndex,
+        types::{PriceFeedMetadata, PUBLIC_BASE_URL},
+        HermesClient,
+    },
+    std::{fmt::Write, process::ExitCode},
+};
+
+const USAGE: &str =
+    "usage: hermes [--url <base url>] (schema-check | search <query> | feed-ids [symbol...])";
+
+/// Source of the `feed_ids` module, whose constants and `ALL` table are replaced by `feed-ids`
+const FEED_IDS_SOURCE: &str = include_str!("../feed_ids.rs");
+/// Line preceding the generated part of the `feed_ids` module
+const FEED_IDS_START: &str = "use crate::types::FeedId;\n\n";
+/// End of the `ALL` table, the last generated item
+const FEED_IDS_END: &str = "\n];\n";
+
+/// Maximum number of feeds listed by `search`
+const SEARCH_LIMIT: usize = 20;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let mut base_url = std::env::var("HERMES_URL").unwrap_or_else(|_| PUBLIC_BASE_URL.to_string());
+    let mut command = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--url" => match args.next() {
+                Some(url) => base_url = url,
+                None => {
+                    eprintln!("{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ => command.push(arg),
+        }
+    }
+    let client = HermesClient::new(base_url);
+    match command.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["schema-check"] => schema_check(&client).await,
+        ["search", ref query @ ..] if !query.is_empty() => search(&client, &query.join(" ")).await,
+        ["feed-ids", ref symbols @ ..] => generate_feed_ids(&client, symbols).await,
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn schema_check(client: &HermesClient) -> ExitCode {
+    let drift = match client.schema_check().await {
+        Ok(drift) => drift,
+        Err(err) => {
+            eprintln!("schema check failed: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if drift.is_empty() {
+        println!("no schema drift detected");
+        return ExitCode::SUCCESS;
+    }
+    for endpoint in drift {
+        println!("{}", endpoint.endpoint);
+        if let Some(err) = endpoint.error {
+            println!("  failed to deserialize: {err}");
+        }
+        for field in endpoint.unknown_fields {
+            println!("  unknown field: {field}");
+        }
+    }
+    ExitCode::FAILURE
+}
+
+async fn search(client: &HermesClient, query: &str) -> ExitCode {
+    let metadata = match client.get_price_feeds_metadata(None, None).await {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            eprintln!("failed to fetch feed metadata: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let hits = FeedIndex::new(metadata).search(query, SEARCH_LIMIT);
+    if hits.is_empty() {
+        println!("no feeds match {query:?}");
+        return ExitCode::FAILURE;
+    }
+    for hit in hits {
+        let symbol = hit.feed.attributes.get("symbol").map_or("", String::as_str);
+        println!("{} {symbol}", hit.feed.id);
+    }
+    ExitCode::SUCCESS
+}
+
+async fn generate_feed_ids(client: &HermesClient, extra_symbols: &[&str]) -> ExitCode {
+    let metadata = match client.get_price_feeds_metadata(None, None).await {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            eprintln!("failed to fetch feed metadata: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut symbols: Vec<&str> = feed_ids::ALL.iter().map(|(symbol, _)| *symbol).collect();
+    for symbol in extra_symbols {
+        if !symbols.contains(symbol) {
+            symbols.push(symbol);
+        }
+    }
+    match render_feed_ids(&metadata, &symbols) {
+        Ok(source) => {
+            print!("{source}");
+            ExitCode::SUCCESS
+        }
+        Err(missing) => {
+            eprintln!(
+                "symbols not found in the feed metadata: {}",
+                missing.join(", ")
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Renders the `feed_ids` module with a constant for each of `symbols`, grouped by asset class
+///
+/// Returns the symbols missing from `metadata` on failure.
+fn render_feed_ids<'a>(
+    metadata: &[PriceFeedMetadata],
+    symbols: &[&'a str],
+) -> Result<String, Vec<&'a str>> {
+    let mut feeds = Vec::new();
+    let mut missing = Vec::new();
+    for &symbol in symbols {
+        match metadata
+            .iter()
+            .find(|feed| feed.attributes.get("symbol").map(String::as_str) == Some(symbol))
+        {
+            Some(feed) => feeds.push((symbol, feed)),
+            None => missing.push(symbol),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+    let asset_class = |feed: &PriceFeedMetadata, symbol: &str| {
+        feed.attributes
+            .get("asset_type")
+            .cloned()
+            .unwrap_or_else(|| symbol.split('.').next().unwrap_or_default().to_string())
+    };
+    let mut classes: Vec<String> = Vec::new();
+    for (symbol, feed) in &feeds {
+        let class = asset_class(feed, symbol);
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+    }
+
+    let start = FEED_IDS_SOURCE
+        .find(FEED_IDS_START)
+        .expect("feed_ids has a use line")
+        + FEED_IDS_START.len();
+    let end = FEED_IDS_SOURCE[start..]
+        .find(FEED_IDS_END)
+        .expect("feed_ids has an ALL table")
+        + start
+        + FEED_IDS_END.len();
+    let mut source = FEED_IDS_SOURCE[..start].to_string();
+    let mut table = String::new();
+    for (index, class) in classes.iter().enumerate() {
+        if index > 0 {
+            source.push('\n');
+        }
+        write!(source, "// {class}\n\n").unwrap();
+        for (symbol, feed) in feeds
+            .iter()
+            .filter(|(symbol, feed)| asset_class(feed, symbol) == *class)
+        {
+            let name = constant_name(symbol);
+            writeln!(source, "/// {symbol}").unwrap();
+            if let Some(description) = feed.attributes.get("description") {
+                writeln!(source, "///\n/// {description}").unwrap();
+            }
+            writeln!(
+                source,
+                "pub const {name}: FeedId =\n    FeedId::from_hex(\"{}\");",
+                feed.id.to_hex()
+            )
+            .unwrap();
+            writeln!(table, "    (\"{symbol}\", {name}),").unwrap();
+        }
+    }
+    write!(
+        source,
+        "\n/// Symbol of each constant in this module, as found in the feed's `symbol` attribute\n\
+         pub const ALL: [(&str, FeedId); {}] = [\n{table}];\n",
+        feeds.len()
+    )
+    .unwrap();
+    Ok(source + &FEED_IDS_SOURCE[end..])
+}
+
+/// Name of the constant for `symbol`, its part after the asset class such as `BTC_USD` for `Crypto.BTC/USD`
+fn constant_name(symbol: &str) -> String {
+    let name = symbol.split_once('.').map_or(symbol, |(_, name)| name);
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::collections::HashMap};
+
+    fn metadata() -> Vec<PriceFeedMetadata> {
+        feed_ids::ALL
+            .iter()
+            .map(|(symbol, id)| PriceFeedMetadata {
+                id: *id,
+                attributes: HashMap::from([
+                    ("symbol".to_string(), symbol.to_string()),
+                    (
+                        "asset_type".to_string(),
+                        symbol.split('.').next().unwrap().to_string(),
+                    ),
+                ]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_render_feed_ids() {
+        let symbols: Vec<_> = feed_ids::ALL.iter().map(|(symbol, _)| *symbol).collect();
+        // the checked in module is the generator's output
+        assert_eq!(
+            render_feed_ids(&metadata(), &symbols).unwrap(),
+            FEED_IDS_SOURCE
+        );
+
+        let mut metadata = metadata();
+        metadata[0]
+            .attributes
+            .insert("description".to_string(), "BITCOIN / US DOLLAR".to_string());
+        let source = render_feed_ids(&metadata, &["Crypto.BTC/USD"]).unwrap();
+        assert!(
+            source.contains("/// Crypto.BTC/USD\n///\n/// BITCOIN / US DOLLAR\npub const BTC_USD")
+        );
+        assert!(source.contains(
+            "pub const ALL: [(&str, FeedId); 1] = [\n    (\"Crypto.BTC/USD\", BTC_USD),\n];"
+        ));
+        assert!(!source.contains("ETH_USD: FeedId"));
+
+        assert_eq!(
+            render_feed_ids(&metadata, &["Crypto.BTC/USD", "Crypto.NOPE/USD"]),
+            Err(vec!["Crypto.NOPE/USD"])
+        );
+        assert_eq!(constant_name("Equity.US.AAPL/USD"), "US_AAPL_USD");
+    }
+}
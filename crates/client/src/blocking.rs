@@ -0,0 +1,261 @@
+//! Blocking client for use outside of an async runtime
+//!
+//! Each call drives the async [`crate::HermesClient`] to completion on a runtime owned by the client, so methods
+//! must not be called from within an async context.
+//!
+//! [`PollingSubscription`] is a facade over the streaming engine for applications with their own main loop, such as
+//! game engines and GUI frameworks, which drain received updates once per frame with
+//! [`PollingSubscription::poll_updates`].
+
+use {
+    crate::{
+        error::HermesError,
+        schema::SchemaDrift,
+        stream::{StreamHandle, StreamOptions, Subscription},
+        types::*,
+    },
+    std::sync::Arc,
+    tokio::runtime::Runtime,
+};
+
+pub struct HermesClient {
+    inner: crate::HermesClient,
+    runtime: Arc<Runtime>,
+}
+
+/// Price updates streamed in the background and queued until polled, see [`HermesClient::subscribe`]
+///
+/// Keeps the client's runtime alive, so the stream keeps running after the client is dropped. Dropping the
+/// subscription stops the stream.
+#[must_use = "the stream is stopped when the subscription is dropped"]
+pub struct PollingSubscription {
+    subscription: Subscription,
+    // dropped after the subscription, so the stream is stopped before its runtime shuts down
+    _runtime: Arc<Runtime>,
+}
+
+impl HermesClient {
+    /// # Panics
+    ///
+    /// If the runtime backing the client cannot be created
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::from_async(crate::HermesClient::new(base_url))
+    }
+
+    /// Wraps an async client, preserving its configuration
+    ///
+    /// # Panics
+    ///
+    /// If the runtime backing the client cannot be created
+    pub fn from_async(inner: crate::HermesClient) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("failed to create runtime");
+        Self {
+            inner,
+            runtime: Arc::new(runtime),
+        }
+    }
+
+    /// Get the latest price updates by price feed id.
+    pub fn get_latest_price_feeds(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<Vec<RpcPriceFeed>, HermesError> {
+        self.runtime
+            .block_on(self.inner.get_latest_price_feeds(ids))
+    }
+
+    /// Get the latest price updates by price feed id, including the binary update data
+    pub fn get_latest_price_updates(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<PriceUpdate, HermesError> {
+        self.runtime
+            .block_on(self.inner.get_latest_price_updates(ids))
+    }
+
+    /// See [`crate::HermesClient::get_price_feeds_metadata`]
+    pub fn get_price_feeds_metadata(
+        &self,
+        query: Option<&str>,
+        asset_type: Option<AssetType>,
+    ) -> Result<Vec<PriceFeedMetadata>, HermesError> {
+        self.runtime
+            .block_on(self.inner.get_price_feeds_metadata(query, asset_type))
+    }
+
+    /// See [`crate::HermesClient::get_price_feeds_metadata_cached`]
+    pub fn get_price_feeds_metadata_cached(
+        &self,
+        query: Option<&str>,
+        asset_type: Option<AssetType>,
+    ) -> Result<Arc<[PriceFeedMetadata]>, HermesError> {
+        self.runtime.block_on(
+            self.inner
+                .get_price_feeds_metadata_cached(query, asset_type),
+        )
+    }
+
+    /// See [`crate::HermesClient::get_feeds_with_prices`]
+    pub fn get_feeds_with_prices(
+        &self,
+        query: Option<&str>,
+        asset_type: Option<AssetType>,
+    ) -> Result<Vec<FeedWithPrice>, HermesError> {
+        self.runtime
+            .block_on(self.inner.get_feeds_with_prices(query, asset_type))
+    }
+
+    /// See [`crate::HermesClient::get_price_updates_by_time`]
+    pub fn get_price_updates_by_time(
+        &self,
+        publish_time: i64,
+        ids: &[impl AsRef<FeedId>],
+        options: &PriceUpdatesByTimeOptions,
+    ) -> Result<PriceUpdate, HermesError> {
+        self.runtime.block_on(
+            self.inner
+                .get_price_updates_by_time(publish_time, ids, options),
+        )
+    }
+
+    /// See [`crate::HermesClient::get_latest_twaps`]
+    pub fn get_latest_twaps(
+        &self,
+        window_seconds: u64,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<TwapsResponse, HermesError> {
+        self.runtime
+            .block_on(self.inner.get_latest_twaps(window_seconds, ids))
+    }
+
+    /// Gets the most recent publisher stake caps update data
+    pub fn get_latest_publisher_stake_caps(
+        &self,
+    ) -> Result<LatestPublisherStakeCapsUpdateDataResponse, HermesError> {
+        self.runtime
+            .block_on(self.inner.get_latest_publisher_stake_caps())
+    }
+
+    /// See [`crate::HermesClient::is_live`]
+    pub fn is_live(&self) -> Result<bool, HermesError> {
+        self.runtime.block_on(self.inner.is_live())
+    }
+
+    /// See [`crate::HermesClient::is_ready`]
+    pub fn is_ready(&self) -> Result<bool, HermesError> {
+        self.runtime.block_on(self.inner.is_ready())
+    }
+
+    /// See [`crate::HermesClient::schema_check`]
+    pub fn schema_check(&self) -> Result<Vec<SchemaDrift>, HermesError> {
+        self.runtime.block_on(self.inner.schema_check())
+    }
+
+    /// Streams price updates on the client's runtime, queueing them until they are polled
+    pub fn subscribe(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<PollingSubscription, HermesError> {
+        let subscription = self.runtime.block_on(self.inner.subscribe(ids))?;
+        Ok(PollingSubscription {
+            subscription,
+            _runtime: self.runtime.clone(),
+        })
+    }
+
+    /// Streams price updates on the client's runtime, invoking `on_event` from a background thread
+    ///
+    /// # Returns
+    ///
+    /// [`StreamHandle`] which can be used to monitor and abort the stream
+    pub fn stream_price_updates<F>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        on_event: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(ParsedPriceUpdate) + Send + 'static,
+    {
+        self.runtime
+            .block_on(self.inner.stream_price_updates_with_options(
+                ids,
+                StreamOptions::default(),
+                on_event,
+            ))
+    }
+}
+
+impl PollingSubscription {
+    /// Drains the updates received since the last poll, in the order they were received, without blocking
+    pub fn poll_updates(&mut self) -> Vec<ParsedPriceUpdate> {
+        std::iter::from_fn(|| self.subscription.try_next()).collect()
+    }
+
+    /// Handle to the underlying stream, for its id and counters
+    pub fn handle(&self) -> &StreamHandle {
+        self.subscription.handle()
+    }
+}
+
+impl From<crate::HermesClient> for HermesClient {
+    fn from(inner: crate::HermesClient) -> Self {
+        Self::from_async(inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::ETH_USD,
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[test]
+    fn test_blocking_client() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mock = runtime
+            .block_on(MockHermes::start(vec![RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 1, 1),
+            )]))
+            .unwrap();
+        let client = HermesClient::new(mock.url());
+        let feeds = client.get_latest_price_feeds(&[ETH_USD]).unwrap();
+        assert_eq!(feeds[0].id, ETH_USD);
+    }
+
+    #[test]
+    fn test_poll_updates() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let events: Vec<_> = (1..=3)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = runtime
+            .block_on(MockHermes::start(vec![RecordedResponse::events(
+                "/v2/updates/price/stream",
+                events,
+            )]))
+            .unwrap();
+        let mut subscription = HermesClient::new(mock.url()).subscribe(&[ETH_USD]).unwrap();
+
+        // simulates a main loop polling once per frame
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            received.extend(subscription.poll_updates());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(
+            received[..3]
+                .iter()
+                .map(|u| u.price.publish_time)
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert!(subscription.handle().stats().messages_received() >= 3);
+    }
+}
@@ -0,0 +1,236 @@
+//! Locally served feed metadata, refreshed in the background
+//!
+//! A [`FeedCatalog`] fetches the metadata of every feed once and answers lookups from memory, so hot paths never wait
+//! on the metadata endpoint. A background task refreshes the catalog each time its TTL expires, revalidating with the
+//! last ETag, and stops once every clone of the catalog is dropped.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use {pyth_hermes_rs::{catalog::FeedCatalog, HermesClient}, std::time::Duration};
+//!
+//! let client = HermesClient::new("https://hermes.pyth.network");
+//! let catalog = FeedCatalog::start(client, Duration::from_secs(3600)).await?;
+//! let eth = catalog.by_symbol("Crypto.ETH/USD").map(|feed| feed.id);
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{
+        error::HermesError,
+        rt,
+        search::{FeedIndex, SearchHit},
+        types::{FeedId, PriceFeedMetadata},
+        HermesClient,
+    },
+    std::{
+        collections::HashMap,
+        fmt,
+        sync::{Arc, RwLock, Weak},
+        time::Duration,
+    },
+};
+
+/// Metadata of every feed, see the [module documentation](self)
+///
+/// Clones share the same metadata and background refresh.
+#[derive(Clone)]
+pub struct FeedCatalog {
+    inner: Arc<Inner>,
+}
+
+pub(crate) struct Inner {
+    client: HermesClient,
+    ttl: Duration,
+    snapshot: RwLock<Snapshot>,
+}
+
+struct Snapshot {
+    feeds: Arc<[PriceFeedMetadata]>,
+    by_id: HashMap<FeedId, usize>,
+    by_symbol: HashMap<String, usize>,
+    index: FeedIndex,
+    fetched_at_millis: u64,
+}
+
+impl FeedCatalog {
+    /// Fetches the metadata of every feed and starts refreshing it every `ttl`
+    pub async fn start(client: HermesClient, ttl: Duration) -> Result<Self, HermesError> {
+        let feeds = client.get_price_feeds_metadata_cached(None, None).await?;
+        let catalog = Self {
+            inner: Arc::new(Inner {
+                client,
+                ttl,
+                snapshot: RwLock::new(Snapshot::new(feeds)),
+            }),
+        };
+        catalog.inner.client.catalogs.register(&catalog.inner);
+        drop(rt::spawn(refresh_loop(Arc::downgrade(&catalog.inner), ttl)));
+        Ok(catalog)
+    }
+
+    /// Metadata of the feed `id`
+    pub fn get(&self, id: &FeedId) -> Option<PriceFeedMetadata> {
+        let snapshot = self.read();
+        snapshot
+            .by_id
+            .get(id)
+            .map(|&index| snapshot.feeds[index].clone())
+    }
+
+    /// Metadata of the feed with the `symbol` attribute, such as `Crypto.ETH/USD`
+    pub fn by_symbol(&self, symbol: &str) -> Option<PriceFeedMetadata> {
+        let snapshot = self.read();
+        snapshot
+            .by_symbol
+            .get(symbol)
+            .map(|&index| snapshot.feeds[index].clone())
+    }
+
+    /// Up to `limit` feeds matching `query`, see [`FeedIndex::search`]
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        self.read().index.search(query, limit)
+    }
+
+    /// Metadata of every feed, in the order hermes returned them
+    pub fn feeds(&self) -> Arc<[PriceFeedMetadata]> {
+        self.read().feeds.clone()
+    }
+
+    /// Time since the metadata was last fetched or revalidated
+    pub fn age(&self) -> Duration {
+        self.inner.age()
+    }
+
+    /// Whether the metadata is older than the TTL, meaning recent refreshes failed
+    pub fn is_stale(&self) -> bool {
+        self.age() > self.inner.ttl
+    }
+
+    /// Fetches the metadata now instead of waiting for the next background refresh
+    pub async fn refresh(&self) -> Result<(), HermesError> {
+        self.inner.refresh().await
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Snapshot> {
+        self.inner.read()
+    }
+}
+
+impl fmt::Debug for FeedCatalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeedCatalog")
+            .field("feeds", &self.read().feeds.len())
+            .field("ttl", &self.inner.ttl)
+            .field("age", &self.age())
+            .finish()
+    }
+}
+
+impl Inner {
+    /// Number of feeds held by the catalog
+    pub(crate) fn len(&self) -> usize {
+        self.read().feeds.len()
+    }
+
+    fn age(&self) -> Duration {
+        let fetched_at = self.read().fetched_at_millis;
+        Duration::from_millis(rt::unix_timestamp_millis().saturating_sub(fetched_at))
+    }
+
+    async fn refresh(&self) -> Result<(), HermesError> {
+        let feeds = self
+            .client
+            .get_price_feeds_metadata_cached(None, None)
+            .await?;
+        let snapshot = Snapshot::new(feeds);
+        *self.snapshot.write().unwrap_or_else(|e| e.into_inner()) = snapshot;
+        Ok(())
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Snapshot> {
+        self.snapshot.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Snapshot {
+    fn new(feeds: Arc<[PriceFeedMetadata]>) -> Self {
+        let by_id = feeds
+            .iter()
+            .enumerate()
+            .map(|(index, feed)| (feed.id, index))
+            .collect();
+        let by_symbol = feeds
+            .iter()
+            .enumerate()
+            .filter_map(|(index, feed)| Some((feed.attributes.get("symbol")?.clone(), index)))
+            .collect();
+        Self {
+            index: FeedIndex::new(feeds.clone()),
+            feeds,
+            by_id,
+            by_symbol,
+            fetched_at_millis: rt::unix_timestamp_millis(),
+        }
+    }
+}
+
+/// Refreshes the catalog every `ttl` until it is dropped
+async fn refresh_loop(catalog: Weak<Inner>, ttl: Duration) {
+    loop {
+        rt::sleep(ttl).await;
+        let Some(catalog) = catalog.upgrade() else {
+            return;
+        };
+        if let Err(err) = catalog.refresh().await {
+            log::warn!(
+                "failed to refresh feed catalog, serving metadata fetched {:?} ago: {err}",
+                catalog.age()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD},
+        crate::mock::{MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_background_refresh() {
+        let eth = serde_json::json!({"id": ETH_USD, "attributes": {"symbol": "Crypto.ETH/USD"}});
+        let btc = serde_json::json!({"id": BTC_USD, "attributes": {"symbol": "Crypto.BTC/USD"}});
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json("/v2/price_feeds", serde_json::json!([eth])),
+            RecordedResponse::json("/v2/price_feeds", serde_json::json!([eth, btc])),
+        ])
+        .await
+        .unwrap();
+        let catalog = FeedCatalog::start(HermesClient::new(mock.url()), Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(catalog.by_symbol("Crypto.ETH/USD").unwrap().id, ETH_USD);
+        assert!(catalog.get(&BTC_USD).is_none());
+        assert!(catalog.search("btc", 10).is_empty());
+
+        while catalog.feeds().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            catalog.get(&BTC_USD).unwrap().attributes["symbol"],
+            "Crypto.BTC/USD"
+        );
+        assert!(!catalog.is_stale());
+        assert_eq!(catalog.search("btc", 10)[0].feed.id, BTC_USD);
+
+        // the refresh task stops once the catalog is dropped
+        drop(catalog);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let requests = mock.requests().await.len();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(mock.requests().await.len(), requests);
+    }
+}
@@ -0,0 +1,168 @@
+//! Circuit breaker which fails requests locally while hermes is continuously erroring
+
+use {
+    crate::{error::HermesError, retry::RetryPolicy, rt},
+    std::{sync::Mutex, time::Duration},
+};
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent as normal
+    Closed,
+    /// Requests fail immediately with [`HermesError::CircuitOpen`] until the cooldown elapses
+    Open,
+    /// The cooldown has elapsed and a single probe request is allowed through, its outcome closes or reopens
+    /// the circuit
+    HalfOpen,
+}
+
+/// Trips after a number of consecutive failures, see
+/// [`HermesClientBuilder::circuit_breaker`](crate::builder::HermesClientBuilder::circuit_breaker)
+///
+/// Connection errors, 5xx and 429 responses count as failures, other errors don't affect the circuit.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the circuit was opened, or when the half open probe was sent
+    changed_at_millis: u64,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                changed_at_millis: 0,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let inner = self.lock();
+        match inner.state {
+            CircuitState::Open if self.remaining(&inner).is_zero() => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    /// Returns an error if the request should fail fast, otherwise allows it through
+    pub(crate) fn check(&self) -> Result<(), HermesError> {
+        let mut inner = self.lock();
+        if inner.state == CircuitState::Closed {
+            return Ok(());
+        }
+        let remaining = self.remaining(&inner);
+        if !remaining.is_zero() {
+            return Err(HermesError::CircuitOpen {
+                retry_after: remaining,
+            });
+        }
+        // let a single probe through, further requests fail fast until it completes or another cooldown elapses
+        inner.state = CircuitState::HalfOpen;
+        inner.changed_at_millis = rt::unix_timestamp_millis();
+        Ok(())
+    }
+
+    /// Records the outcome of a request which was allowed through
+    pub(crate) fn record<T>(&self, result: &Result<T, HermesError>) {
+        let mut inner = self.lock();
+        match result {
+            Err(err) if RetryPolicy::is_transient(err) => {
+                inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+                if inner.state == CircuitState::HalfOpen
+                    || inner.consecutive_failures >= self.failure_threshold
+                {
+                    if inner.state == CircuitState::Closed {
+                        log::warn!(
+                            "circuit breaker opened after {} consecutive failures",
+                            inner.consecutive_failures
+                        );
+                    }
+                    inner.state = CircuitState::Open;
+                    inner.changed_at_millis = rt::unix_timestamp_millis();
+                }
+            }
+            _ => {
+                inner.consecutive_failures = 0;
+                inner.state = CircuitState::Closed;
+            }
+        }
+    }
+
+    fn remaining(&self, inner: &Inner) -> Duration {
+        let elapsed = rt::unix_timestamp_millis().saturating_sub(inner.changed_at_millis);
+        self.cooldown.saturating_sub(Duration::from_millis(elapsed))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            mock::{MockHermes, RecordedResponse},
+            HermesClient,
+        },
+        serde_json::json,
+    };
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status("/v2/price_feeds", 503),
+            RecordedResponse::status("/v2/price_feeds", 503),
+            RecordedResponse::status("/v2/price_feeds", 503),
+            RecordedResponse::json("/v2/price_feeds", json!([])),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .circuit_breaker(2, Duration::from_millis(200))
+            .build()
+            .unwrap();
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+
+        for _ in 0..2 {
+            let err = client
+                .get_price_feeds_metadata(None, None)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, HermesError::Http(_)));
+        }
+        assert_eq!(client.circuit_state(), Some(CircuitState::Open));
+        let err = client
+            .get_price_feeds_metadata(None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HermesError::CircuitOpen { .. }));
+        assert_eq!(mock.requests().await.len(), 2);
+
+        // the failed probe reopens the circuit
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(client.circuit_state(), Some(CircuitState::HalfOpen));
+        assert!(client.get_price_feeds_metadata(None, None).await.is_err());
+        assert_eq!(client.circuit_state(), Some(CircuitState::Open));
+
+        // the successful probe closes it
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(client.get_price_feeds_metadata(None, None).await.is_ok());
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+        assert_eq!(mock.requests().await.len(), 4);
+    }
+}
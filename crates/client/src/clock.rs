@@ -0,0 +1,71 @@
+//! Time sources used for staleness calculations
+
+use {
+    crate::{rt, types::RpcPrice, HermesClient},
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Source of the current time, see [`HermesClientBuilder::clock`](crate::builder::HermesClientBuilder::clock)
+///
+/// Hosts with an NTP disciplined or PTP clock can provide it here so latency and staleness are measured against
+/// it rather than the system clock. Closures returning a [`SystemTime`] implement the trait.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        // SystemTime::now panics on wasm32
+        UNIX_EPOCH + Duration::from_millis(rt::unix_timestamp_millis())
+    }
+}
+
+impl<F: Fn() -> SystemTime + Send + Sync> Clock for F {
+    fn now(&self) -> SystemTime {
+        self()
+    }
+}
+
+impl HermesClient {
+    /// Current time according to the client's [`Clock`]
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// Time elapsed since `price` was published, according to the client's [`Clock`]
+    pub fn staleness(&self, price: &RpcPrice) -> Duration {
+        price.age_at(self.now())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_staleness() {
+        let client = HermesClient::builder("http://localhost")
+            .clock(|| UNIX_EPOCH + Duration::from_secs(110))
+            .build()
+            .unwrap();
+        let price = RpcPrice {
+            price: "1".to_string(),
+            conf: "1".to_string(),
+            expo: 0,
+            publish_time: 100,
+        };
+        assert_eq!(client.staleness(&price), Duration::from_secs(10));
+        assert_eq!(
+            price.age_at(UNIX_EPOCH + Duration::from_secs(90)),
+            Duration::ZERO
+        );
+
+        let now = SystemTime::now();
+        let system = SystemClock.now();
+        assert!(system.duration_since(now).unwrap_or_default() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,167 @@
+//! Conversions of prices which fail instead of rounding, for accounting where silent precision loss isn't acceptable
+//!
+//! Prices are integers scaled by a power of ten, `price * 10^expo`. [`RpcPrice::to_f64`] rounds freely, which is
+//! fine for display. The conversions here check that the value survives the conversion unchanged.
+
+use {crate::types::RpcPrice, std::fmt};
+
+/// Most significant decimal digits an `f64` is guaranteed to round trip
+const F64_DIGITS: usize = 15;
+
+/// Why a checked conversion failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The value isn't an integer
+    Invalid(String),
+    /// The value doesn't fit in the target type
+    Overflow,
+    /// The target type can't represent the value exactly
+    PrecisionLoss,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(value) => write!(f, "{value:?} is not an integer"),
+            Self::Overflow => f.write_str("value out of range"),
+            Self::PrecisionLoss => f.write_str("value can't be represented exactly"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl RpcPrice {
+    /// Price as a float, failing unless the float converts back to the exact same decimal
+    pub fn checked_to_f64(&self) -> Result<f64, ConversionError> {
+        checked_to_f64(&self.price, self.expo)
+    }
+
+    /// Confidence interval as a float, see [`RpcPrice::checked_to_f64`]
+    pub fn checked_conf_to_f64(&self) -> Result<f64, ConversionError> {
+        checked_to_f64(&self.conf, self.expo)
+    }
+
+    /// Price as an integer, failing if it has a fractional part or doesn't fit
+    pub fn lossless_to_i128(&self) -> Result<i128, ConversionError> {
+        rescale(&self.price, self.expo, 0)
+    }
+
+    /// Price as an integer number of `10^expo` units, such as cents for `-2`, failing if precision would be lost
+    pub fn rescale(&self, expo: i32) -> Result<i128, ConversionError> {
+        rescale(&self.price, self.expo, expo)
+    }
+}
+
+fn parse(value: &str) -> Result<i128, ConversionError> {
+    value
+        .parse()
+        .map_err(|_| ConversionError::Invalid(value.to_string()))
+}
+
+fn checked_to_f64(value: &str, expo: i32) -> Result<f64, ConversionError> {
+    let mantissa = parse(value)?;
+    let digits = mantissa.unsigned_abs().to_string();
+    if digits.trim_end_matches('0').len() > F64_DIGITS {
+        return Err(ConversionError::PrecisionLoss);
+    }
+    // parsing the decimal rounds correctly, unlike dividing by a power of ten
+    let float: f64 = format!("{mantissa}e{expo}")
+        .parse()
+        .map_err(|_| ConversionError::Invalid(value.to_string()))?;
+    if float.is_infinite() {
+        return Err(ConversionError::Overflow);
+    }
+    if float == 0.0 && mantissa != 0 {
+        return Err(ConversionError::PrecisionLoss);
+    }
+    Ok(float)
+}
+
+fn rescale(value: &str, from: i32, to: i32) -> Result<i128, ConversionError> {
+    let mantissa = parse(value)?;
+    if mantissa == 0 {
+        return Ok(0);
+    }
+    let shift = from.checked_sub(to).ok_or(ConversionError::Overflow)?;
+    let factor = 10_i128
+        .checked_pow(shift.unsigned_abs())
+        .ok_or(if shift > 0 {
+            ConversionError::Overflow
+        } else {
+            ConversionError::PrecisionLoss
+        })?;
+    if shift >= 0 {
+        mantissa
+            .checked_mul(factor)
+            .ok_or(ConversionError::Overflow)
+    } else if mantissa % factor == 0 {
+        Ok(mantissa / factor)
+    } else {
+        Err(ConversionError::PrecisionLoss)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn price(price: &str, expo: i32) -> RpcPrice {
+        RpcPrice {
+            price: price.to_string(),
+            conf: "5".to_string(),
+            expo,
+            publish_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_checked_to_f64() {
+        assert_eq!(
+            price("160644665033", -8).checked_to_f64(),
+            Ok(1606.44665033)
+        );
+        assert_eq!(price("-25", -1).checked_to_f64(), Ok(-2.5));
+        assert_eq!(price("160644665033", -8).checked_conf_to_f64(), Ok(5e-8));
+        // 17 significant digits don't survive the conversion
+        assert_eq!(
+            price("12345678901234567", -8).checked_to_f64(),
+            Err(ConversionError::PrecisionLoss)
+        );
+        assert_eq!(
+            price("1", 400).checked_to_f64(),
+            Err(ConversionError::Overflow)
+        );
+        assert_eq!(
+            price("1", -400).checked_to_f64(),
+            Err(ConversionError::PrecisionLoss)
+        );
+        assert!(matches!(
+            price("1.5", 0).checked_to_f64(),
+            Err(ConversionError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_lossless_to_i128() {
+        assert_eq!(price("1200", -2).lossless_to_i128(), Ok(12));
+        assert_eq!(price("-12", 3).lossless_to_i128(), Ok(-12000));
+        assert_eq!(
+            price("1234", -2).lossless_to_i128(),
+            Err(ConversionError::PrecisionLoss)
+        );
+        assert_eq!(
+            price("1", 40).lossless_to_i128(),
+            Err(ConversionError::Overflow)
+        );
+
+        // to cents
+        assert_eq!(
+            price("160644665033", -8).rescale(-2),
+            Err(ConversionError::PrecisionLoss)
+        );
+        assert_eq!(price("160644000000", -8).rescale(-2), Ok(160644));
+        assert_eq!(price("160644665033", -8).rescale(-10), Ok(16064466503300));
+        assert_eq!(price("0", -8).rescale(-100), Ok(0));
+    }
+}
@@ -0,0 +1,136 @@
+//! Correlation ids sent with requests, so failures in client logs can be matched to hermes side logs
+//!
+//! Once enabled with [`HermesClientBuilder::correlation_ids`](crate::builder::HermesClientBuilder::correlation_ids),
+//! each REST call is sent with a generated id, which is reused by its retries. Errors from the call are returned as
+//! [`HermesError::Correlated`], use [`HermesError::correlation_id`] to log the id. Each SSE connection is also sent
+//! with its own id.
+
+use {
+    crate::{error::HermesError, HermesClient},
+    std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Header correlation ids are sent in unless another is configured
+pub const DEFAULT_CORRELATION_HEADER: &str = "x-request-id";
+
+/// Distinguishes ids generated within the same millisecond
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Generates a random 32 character hex id
+pub(crate) fn generate_id() -> String {
+    let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let mut id = String::with_capacity(32);
+    for half in 0..2_u64 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(crate::rt::unix_timestamp_millis());
+        hasher.write_u64(sequence);
+        hasher.write_u64(half);
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id
+}
+
+impl HermesClient {
+    /// Returns a client sharing this client's connections and configuration, which sends `id` as the correlation
+    /// id of every request instead of generating one
+    ///
+    /// Correlation ids are sent in the configured header, or [`DEFAULT_CORRELATION_HEADER`] if they aren't enabled.
+    pub fn with_correlation_id(&self, id: impl Into<String>) -> Self {
+        Self {
+            correlation_header: Some(
+                self.correlation_header
+                    .clone()
+                    .unwrap_or_else(|| Arc::from(DEFAULT_CORRELATION_HEADER)),
+            ),
+            correlation_id: Some(Arc::from(id.into())),
+            ..self.clone()
+        }
+    }
+
+    /// Header and id to send with the next call, if correlation ids are enabled
+    pub(crate) fn next_correlation_id(&self) -> Option<(Arc<str>, String)> {
+        let header = self.correlation_header.clone()?;
+        let id = match &self.correlation_id {
+            Some(id) => id.to_string(),
+            None => generate_id(),
+        };
+        Some((header, id))
+    }
+}
+
+impl HermesError {
+    /// Correlation id of the request which failed, if correlation ids are enabled
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            Self::Correlated { correlation_id, .. } => Some(correlation_id),
+            _ => None,
+        }
+    }
+
+    /// The underlying error, without its correlation id
+    pub fn uncorrelated(&self) -> &HermesError {
+        match self {
+            Self::Correlated { source, .. } => source,
+            err => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+    };
+
+    #[test]
+    fn test_generate_id() {
+        let (a, b) = (generate_id(), generate_id());
+        assert_eq!(a.len(), 32);
+        assert!(a.bytes().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_correlation_ids() {
+        let path = "/v2/updates/price/latest";
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status(path, 503),
+            RecordedResponse::status(path, 503),
+            RecordedResponse::json(path, price_update_json(&[ETH_USD], 1, 1)),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .correlation_ids("x-correlation-id")
+            .retry_policy(crate::retry::RetryPolicy::new(2).base_delay(std::time::Duration::ZERO))
+            .build()
+            .unwrap();
+
+        let err = client.get_latest_price_feeds(&[ETH_USD]).await.unwrap_err();
+        let id = err.correlation_id().unwrap().to_string();
+        assert!(matches!(err.uncorrelated(), HermesError::Http(_)));
+        assert!(err.to_string().contains(&id));
+
+        client
+            .with_correlation_id("job-42")
+            .get_latest_price_feeds(&[ETH_USD])
+            .await
+            .unwrap();
+        let requests = mock.requests().await;
+        // retries of a call share its id
+        assert_eq!(requests[0].headers["x-correlation-id"], id);
+        assert_eq!(requests[1].headers["x-correlation-id"], id);
+        assert_eq!(requests[2].headers["x-correlation-id"], "job-42");
+    }
+}
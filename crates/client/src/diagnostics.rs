@@ -0,0 +1,256 @@
+//! Snapshot of a client's streams and caches, for debugging memory growth and stalled streams in production
+//!
+//! ```no_run
+//! # fn example(client: pyth_hermes_rs::HermesClient) {
+//! for stream in client.diagnostics().streams {
+//!     println!("stream {} running={} buffered={:?}", stream.id, stream.running, stream.buffered);
+//! }
+//! # }
+//! ```
+
+use {
+    crate::{
+        stream::{StreamState, StreamStats},
+        HermesClient,
+    },
+    serde::Serialize,
+    std::{
+        sync::{Arc, Mutex, Weak},
+        time::{Duration, UNIX_EPOCH},
+    },
+};
+
+/// Streams started by a client and its clones, forgotten once their task and handle are both gone
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StreamRegistry(Arc<Mutex<Vec<RegisteredStream>>>);
+
+#[derive(Debug)]
+struct RegisteredStream {
+    id: u64,
+    stats: Weak<StreamStats>,
+}
+
+impl StreamRegistry {
+    pub(crate) fn register(&self, id: u64, stats: &Arc<StreamStats>) {
+        let mut streams = self.lock();
+        streams.retain(|stream| stream.stats.strong_count() > 0);
+        streams.push(RegisteredStream {
+            id,
+            stats: Arc::downgrade(stats),
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<RegisteredStream>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Values of a kind created by a client and its clones, such as shared streams, forgotten once dropped
+pub(crate) struct Registry<T>(Arc<Mutex<Vec<Weak<T>>>>);
+
+impl<T> Clone for Registry<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<T> Registry<T> {
+    pub(crate) fn register(&self, value: &Arc<T>) {
+        let mut values = self.lock();
+        values.retain(|value| value.strong_count() > 0);
+        values.push(Arc::downgrade(value));
+    }
+
+    /// Registered values which are still alive
+    fn live(&self) -> Vec<Arc<T>> {
+        self.lock().iter().filter_map(Weak::upgrade).collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Weak<T>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// State of a client, see [`HermesClient::diagnostics`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostics {
+    /// Seed of the client's randomness, see [`crate::rng`]
+    pub seed: u64,
+    pub streams: Vec<StreamDiagnostics>,
+    /// Number of metadata responses cached for revalidation with their ETag
+    pub metadata_cache_entries: usize,
+    /// Number of symbols cached for resolving to feed ids
+    pub symbol_cache_entries: usize,
+    /// Shared streams created with [`HermesClient::shared_stream`] which are still in use
+    pub shared_streams: Vec<SharedStreamDiagnostics>,
+    /// Number of feeds held by the [`FeedCatalog`](crate::catalog::FeedCatalog)s started with the client
+    pub catalog_entries: usize,
+}
+
+/// State of a [`SharedStream`](crate::shared::SharedStream)
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedStreamDiagnostics {
+    /// Id of the underlying stream, listed in [`Diagnostics::streams`], `None` while no subscription needs it
+    pub stream_id: Option<u64>,
+    pub subscribers: usize,
+    /// Number of distinct feeds needed by the subscriptions
+    pub feed_count: usize,
+}
+
+/// State of a stream, see [`StreamStats`]
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamDiagnostics {
+    pub id: u64,
+    pub feed_count: usize,
+    /// Whether the stream's task is still running
+    pub running: bool,
+    pub state: StreamState,
+    pub uptime: Duration,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    pub reconnects: u64,
+    /// Updates waiting to be taken from the stream's subscription, see [`StreamStats::buffered`]
+    pub buffered: Option<u64>,
+    /// Unix timestamp in milliseconds of the last message received
+    pub last_message_at_millis: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+impl HermesClient {
+    /// Snapshot of the streams started by this client and its clones, along with the sizes of its caches, shared
+    /// streams and feed catalogs
+    ///
+    /// Streams remain listed after stopping as long as their handle is alive.
+    pub fn diagnostics(&self) -> Diagnostics {
+        let streams = self
+            .streams
+            .lock()
+            .iter()
+            .filter_map(|stream| {
+                let stats = stream.stats.upgrade()?;
+                Some(StreamDiagnostics {
+                    id: stream.id,
+                    feed_count: stats.feed_count(),
+                    running: stats.is_running(),
+                    state: stats.state(),
+                    uptime: stats.uptime(),
+                    messages_received: stats.messages_received(),
+                    bytes_received: stats.bytes_received(),
+                    reconnects: stats.reconnects(),
+                    buffered: stats.buffered(),
+                    last_message_at_millis: stats
+                        .last_message_at()
+                        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                        .map(|at| at.as_millis() as u64),
+                    last_error: stats.last_error(),
+                })
+            })
+            .collect();
+        Diagnostics {
+            seed: self.rng.seed(),
+            streams,
+            metadata_cache_entries: self.metadata_cache.len(),
+            symbol_cache_entries: self.symbol_cache.len(),
+            shared_streams: self
+                .shared_streams
+                .live()
+                .iter()
+                .map(|stream| stream.diagnostics())
+                .collect(),
+            catalog_entries: self
+                .catalogs
+                .live()
+                .iter()
+                .map(|catalog| catalog.len())
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            catalog::FeedCatalog,
+            feed_ids::{BTC_USD, ETH_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_diagnostics() {
+        let events = (1..=2)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![
+            RecordedResponse::events("/v2/updates/price/stream", events),
+            RecordedResponse::json(
+                "/v2/price_feeds",
+                serde_json::json!([{"id": ETH_USD, "attributes": {}}, {"id": BTC_USD, "attributes": {}}]),
+            ),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        let mut subscription = client.subscribe(&[ETH_USD]).await.unwrap();
+        while client.diagnostics().streams[0].buffered < Some(2) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        subscription.next().await.unwrap();
+
+        let diagnostics = client.clone().diagnostics();
+        let stream = &diagnostics.streams[0];
+        assert_eq!(stream.id, subscription.handle().id());
+        assert_eq!(stream.feed_count, 1);
+        assert!(stream.running);
+        assert!(stream.buffered >= Some(1));
+        assert!(stream.messages_received >= 2);
+        // the mock ends the stream after its events
+        assert!(stream.last_error.is_some());
+        assert_eq!(diagnostics.symbol_cache_entries, 0);
+        assert!(diagnostics.shared_streams.is_empty());
+        assert_eq!(diagnostics.catalog_entries, 0);
+
+        let shared = client.shared_stream(Default::default());
+        let eth = shared.subscribe(&[ETH_USD]).await.unwrap();
+        let both = shared.subscribe(&[ETH_USD, BTC_USD]).await.unwrap();
+        let catalog = FeedCatalog::start(client.clone(), Duration::from_secs(3600))
+            .await
+            .unwrap();
+        let diagnostics = client.diagnostics();
+        let shared_diagnostics = &diagnostics.shared_streams[0];
+        assert_eq!(shared_diagnostics.subscribers, 2);
+        assert_eq!(shared_diagnostics.feed_count, 2);
+        assert!(diagnostics
+            .streams
+            .iter()
+            .any(|stream| Some(stream.id) == shared_diagnostics.stream_id));
+        assert_eq!(diagnostics.catalog_entries, 2);
+        drop((eth, both, shared, catalog));
+        let diagnostics = client.diagnostics();
+        assert!(diagnostics.shared_streams.is_empty());
+        assert_eq!(diagnostics.catalog_entries, 0);
+        assert_eq!(
+            HermesClient::builder(mock.url())
+                .seed(42)
+                .build()
+                .unwrap()
+                .diagnostics()
+                .seed,
+            42
+        );
+
+        subscription.handle().abort();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!client.diagnostics().streams[0].running);
+        drop(subscription);
+        assert!(client.diagnostics().streams.is_empty());
+    }
+}
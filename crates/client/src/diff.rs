@@ -0,0 +1,115 @@
+//! Compact price diffs derived from the price update stream, sized for forwarding to browsers
+
+use {
+    crate::{
+        error::HermesError,
+        stream::{StreamHandle, StreamOptions},
+        types::*,
+        HermesClient,
+    },
+    serde::Serialize,
+    std::collections::HashMap,
+};
+
+/// Change in a feed's price between two updates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PriceDiff {
+    pub id: FeedId,
+    /// `None` for the first update received for the feed
+    pub old_price: Option<f64>,
+    pub new_price: f64,
+    /// Percentage change from `old_price`, `5.0` being a 5% increase
+    pub pct_change: Option<f64>,
+    pub publish_time: i64,
+}
+
+/// Tracks the last price of each feed, producing a [`PriceDiff`] when it changes
+#[derive(Debug, Default)]
+pub struct DiffTracker {
+    last: HashMap<FeedId, f64>,
+}
+
+impl DiffTracker {
+    /// Returns the diff from the previous update for the feed, or `None` if the price is unchanged or invalid
+    pub fn update(&mut self, update: &ParsedPriceUpdate) -> Option<PriceDiff> {
+        let new_price = update.price.to_f64()?;
+        let old_price = self.last.insert(update.id, new_price);
+        if old_price == Some(new_price) {
+            return None;
+        }
+        Some(PriceDiff {
+            id: update.id,
+            old_price,
+            new_price,
+            pct_change: old_price
+                .filter(|old| *old != 0.0)
+                .map(|old| (new_price - old) / old * 100.0),
+            publish_time: update.price.publish_time,
+        })
+    }
+}
+
+impl HermesClient {
+    /// Streams price updates, invoking `on_diff` only when a feed's price changes
+    pub async fn stream_price_diffs<F>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        mut on_diff: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(PriceDiff) + Send + 'static,
+    {
+        let mut tracker = DiffTracker::default();
+        self.stream_price_updates_with_options(ids, StreamOptions::default(), move |update| {
+            if let Some(diff) = tracker.update(&update) {
+                on_diff(diff);
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::ETH_USD,
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_stream_price_diffs() {
+        let events = [100_000_000, 100_000_000, 110_000_000]
+            .into_iter()
+            .enumerate()
+            .map(|(i, price)| price_update_json(&[ETH_USD], price, i as i64).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = HermesClient::new(mock.url())
+            .stream_price_diffs(&[ETH_USD], move |diff| {
+                let _ = tx.send(diff);
+            })
+            .await
+            .unwrap();
+        let first = rx.recv().await.unwrap();
+        let second = rx.recv().await.unwrap();
+        handle.abort();
+
+        assert_eq!(first.old_price, None);
+        assert_eq!(first.new_price, 1.0);
+        assert_eq!(second.old_price, Some(1.0));
+        assert_eq!(second.new_price, 1.1);
+        assert!((second.pct_change.unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(second.publish_time, 2);
+        assert_eq!(
+            serde_json::to_value(&second).unwrap()["id"],
+            ETH_USD.to_string()
+        );
+    }
+}
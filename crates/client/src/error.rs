@@ -0,0 +1,72 @@
+//! Error types returned by the client
+
+use std::time::Duration;
+
+/// Boxed error returned by user provided extensions such as [`crate::auth::AuthProvider`]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HermesError {
+    #[error("http request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("failed to get auth token: {0}")]
+    Auth(#[source] BoxError),
+    #[error("failed to sign request: {0}")]
+    Signing(#[source] BoxError),
+    /// The client's [`crate::history::HistoryStore`] failed to load stored updates
+    #[error("failed to load from the history store: {0}")]
+    History(#[source] BoxError),
+    #[error("invalid tls configuration: {0}")]
+    Tls(String),
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+    /// A selector passed to [`crate::groups::FeedGroups::resolve`] is neither a group nor a feed id
+    #[error("no feed group or feed id named {0:?}")]
+    UnknownFeed(String),
+    /// The request includes feeds which the client's [`crate::policy::FeedPolicy`] doesn't allow
+    #[error("feeds not allowed by the client's feed policy: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    FeedNotAllowed(Vec<crate::types::FeedId>),
+    /// Ids which aren't 64 hex characters, optionally prefixed with `0x`, along with what's wrong with each
+    #[error("invalid feed ids: {}", .0.iter().map(|(id, err)| format!("{id:?} ({err})")).collect::<Vec<_>>().join(", "))]
+    InvalidFeedId(Vec<(String, crate::types::FeedIdError)>),
+    /// An alias is one of pyth's symbols, naming a different feed, see [`crate::aliases`]
+    #[error("alias {alias:?} for feed {id} is the symbol of feed {symbol_id}")]
+    AliasConflict {
+        alias: String,
+        id: crate::types::FeedId,
+        symbol_id: crate::types::FeedId,
+    },
+    /// Hermes didn't return some of the requested feeds, see [`crate::order`]
+    #[error("feeds missing from the response: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    MissingFeeds(Vec<crate::types::FeedId>),
+    /// The client was called incorrectly, see [`crate::misuse`]
+    #[error(transparent)]
+    Misuse(#[from] crate::misuse::Misuse),
+    #[error("unknown hermes profile {0:?}, expected public, beta, local or a url")]
+    UnknownProfile(String),
+    #[error("invalid header: {0}")]
+    InvalidHeader(String),
+    /// Hermes responded with `429 Too Many Requests`
+    #[error("rate limited by hermes{}", retry_after.map(|d| format!(", retry after {}s", d.as_secs())).unwrap_or_default())]
+    RateLimited {
+        /// Delay requested by the `Retry-After` header, if present
+        retry_after: Option<Duration>,
+    },
+    /// The client's circuit breaker is open and the request was not sent
+    #[error("circuit breaker open, retry after {}ms", retry_after.as_millis())]
+    CircuitOpen {
+        /// Time until the circuit breaker allows a request through
+        retry_after: Duration,
+    },
+    /// A stream failed to connect, was disconnected or received a message it couldn't parse, see
+    /// [`HermesClient::try_price_update_stream`](crate::HermesClient::try_price_update_stream)
+    #[error("{0}")]
+    Stream(crate::stream::StreamFailure),
+    /// An error from a request sent with a correlation id, see [`crate::correlation`]
+    #[error("{source} (correlation id {correlation_id})")]
+    Correlated {
+        correlation_id: String,
+        #[source]
+        source: Box<HermesError>,
+    },
+}
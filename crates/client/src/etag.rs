@@ -0,0 +1,140 @@
+//! Conditional requests for the feed metadata, which is large and rarely changes
+
+use {
+    crate::{
+        error::HermesError,
+        types::{AssetType, PriceFeedMetadata},
+        HermesClient,
+    },
+    reqwest::{
+        header::{HeaderValue, ETAG, IF_NONE_MATCH},
+        StatusCode,
+    },
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Filters a metadata request was made with
+type MetadataKey = (Option<String>, Option<AssetType>);
+
+/// Last metadata received for each set of filters along with its ETag, shared by clones of a client
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MetadataCache(Arc<Mutex<HashMap<MetadataKey, CachedMetadata>>>);
+
+#[derive(Debug, Clone)]
+struct CachedMetadata {
+    etag: HeaderValue,
+    metadata: Arc<[PriceFeedMetadata]>,
+}
+
+impl MetadataCache {
+    fn get(&self, key: &MetadataKey) -> Option<CachedMetadata> {
+        self.lock().get(key).cloned()
+    }
+
+    fn store(
+        &self,
+        key: MetadataKey,
+        etag: Option<HeaderValue>,
+        metadata: Arc<[PriceFeedMetadata]>,
+    ) {
+        let mut cache = self.lock();
+        match etag {
+            Some(etag) => cache.insert(key, CachedMetadata { etag, metadata }),
+            // without an etag the response can't be revalidated
+            None => cache.remove(&key),
+        };
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<MetadataKey, CachedMetadata>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HermesClient {
+    /// Fetches feed metadata like [`HermesClient::get_price_feeds_metadata`], revalidating the last response for the
+    /// same filters with its ETag
+    ///
+    /// When hermes responds `304 Not Modified` the cached metadata is returned without being downloaded again. The
+    /// cache is shared by clones of the client.
+    pub async fn get_price_feeds_metadata_cached(
+        &self,
+        query: Option<&str>,
+        asset_type: Option<AssetType>,
+    ) -> Result<Arc<[PriceFeedMetadata]>, HermesError> {
+        let key = (query.map(str::to_string), asset_type);
+        let cached = self.metadata_cache.get(&key);
+        let url = self.url("/v2/price_feeds")?;
+        let mut req = self
+            .http
+            .get(url)
+            .query(&[("query", query)])
+            .query(&[("asset_type", asset_type)]);
+        if let Some(cached) = &cached {
+            req = req.header(IF_NONE_MATCH, cached.etag.clone());
+        }
+        let resp = self.send(req).await?;
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (resp.status(), cached) {
+            return Ok(cached.metadata);
+        }
+        let etag = resp.headers().get(ETAG).cloned();
+        let metadata: Arc<[PriceFeedMetadata]> = resp.json::<Vec<_>>().await?.into();
+        self.metadata_cache.store(key, etag, metadata.clone());
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{MockHermes, RecordedResponse},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_metadata_revalidation() {
+        let mut first = RecordedResponse::json(
+            "/v2/price_feeds",
+            serde_json::json!([{"id": ETH_USD, "attributes": {"symbol": "Crypto.ETH/USD"}}]),
+        );
+        first.headers.insert("etag".into(), "\"v1\"".into());
+        let mut unchanged = RecordedResponse::status("/v2/price_feeds", 304);
+        unchanged.headers.insert("etag".into(), "\"v1\"".into());
+        let mock = MockHermes::start(vec![first, unchanged]).await.unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let fetched = client
+            .get_price_feeds_metadata_cached(None, Some(AssetType::Crypto))
+            .await
+            .unwrap();
+        let revalidated = client
+            .clone()
+            .get_price_feeds_metadata_cached(None, Some(AssetType::Crypto))
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&fetched, &revalidated));
+        assert_eq!(revalidated[0].id, ETH_USD);
+
+        let requests = mock.requests().await;
+        assert!(!requests[0].headers.contains_key("if-none-match"));
+        assert_eq!(requests[1].headers["if-none-match"], "\"v1\"");
+
+        // other filters are cached separately
+        assert!(client
+            .get_price_feeds_metadata_cached(Some("btc"), None)
+            .await
+            .is_err());
+        assert!(!mock.requests().await[2]
+            .headers
+            .contains_key("if-none-match"));
+    }
+}
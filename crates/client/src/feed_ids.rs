@@ -0,0 +1,123 @@
+//! Ids of well known mainnet feeds, generated from the metadata of the public hermes deployment
+//!
+//! Regenerate with `cargo run --features cli --bin hermes -- feed-ids > src/feed_ids.rs`, passing symbols to add
+//! them to the module.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::{feed_ids, HermesClient};
+//!
+//! let client = HermesClient::new("https://hermes.pyth.network");
+//! let feeds = client
+//!     .get_latest_price_feeds(&[feed_ids::BTC_USD, feed_ids::XAU_USD])
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::types::FeedId;
+
+// Crypto
+
+/// Crypto.BTC/USD
+pub const BTC_USD: FeedId =
+    FeedId::from_hex("e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43");
+/// Crypto.ETH/USD
+pub const ETH_USD: FeedId =
+    FeedId::from_hex("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace");
+/// Crypto.SOL/USD
+pub const SOL_USD: FeedId =
+    FeedId::from_hex("ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d");
+/// Crypto.BNB/USD
+pub const BNB_USD: FeedId =
+    FeedId::from_hex("2f95862b045670cd22bee3114c39763a4a08beeb663b145d283c31d7d1101c4f");
+/// Crypto.XRP/USD
+pub const XRP_USD: FeedId =
+    FeedId::from_hex("ec5d399846a9209f3fe5881d70aae9268c94339ff9817e8d18ff19fa05eea1c8");
+/// Crypto.DOGE/USD
+pub const DOGE_USD: FeedId =
+    FeedId::from_hex("dcef50dd0a4cd2dcc17e45df1676dcb336a11a61c69df7a0299b0150c672d25c");
+/// Crypto.ADA/USD
+pub const ADA_USD: FeedId =
+    FeedId::from_hex("2a01deaec9e51a579277b34b122399984d0bbf57e2458a7e42fecd2829867a0d");
+/// Crypto.AVAX/USD
+pub const AVAX_USD: FeedId =
+    FeedId::from_hex("93da3352f9f1d105fdfe4971cfa80e9dd777bfc5d0f683ebb6e1294b92137bb7");
+/// Crypto.LINK/USD
+pub const LINK_USD: FeedId =
+    FeedId::from_hex("8ac0c70fff57e9aefdf5edf44b51d62c2d433653cbb2cf5cc06bb115af04d221");
+/// Crypto.USDC/USD
+pub const USDC_USD: FeedId =
+    FeedId::from_hex("eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a");
+/// Crypto.USDT/USD
+pub const USDT_USD: FeedId =
+    FeedId::from_hex("2b89b9dc8fdf9f34709a5b106b472f0f39bb6ca9ce04b0fd7f2e971688e2e53b");
+
+// FX
+
+/// FX.EUR/USD
+pub const EUR_USD: FeedId =
+    FeedId::from_hex("a995d00bb36a63cef7fd2c287dc105fc8f3d93779f062f09551b0af3e81ec30b");
+/// FX.GBP/USD
+pub const GBP_USD: FeedId =
+    FeedId::from_hex("84c2dde9633d93d1bcad84e7dc41c9d56578b7ec52fabedc1f335d673df0a7c1");
+/// FX.USD/JPY
+pub const USD_JPY: FeedId =
+    FeedId::from_hex("ef2c98c804ba503c6a707e38be4dfbb16683775f195b091252bf24693042fd52");
+
+// Metal
+
+/// Metal.XAU/USD
+pub const XAU_USD: FeedId =
+    FeedId::from_hex("765d2ba906dbc32ca17cc11f5310a89e9ee1f6420508c63861f2f8ba4ee34bb2");
+/// Metal.XAG/USD
+pub const XAG_USD: FeedId =
+    FeedId::from_hex("f2fb02c32b055c805e7238d628e5e9dadef274376114eb1f012337cabe93871e");
+
+/// Symbol of each constant in this module, as found in the feed's `symbol` attribute
+pub const ALL: [(&str, FeedId); 16] = [
+    ("Crypto.BTC/USD", BTC_USD),
+    ("Crypto.ETH/USD", ETH_USD),
+    ("Crypto.SOL/USD", SOL_USD),
+    ("Crypto.BNB/USD", BNB_USD),
+    ("Crypto.XRP/USD", XRP_USD),
+    ("Crypto.DOGE/USD", DOGE_USD),
+    ("Crypto.ADA/USD", ADA_USD),
+    ("Crypto.AVAX/USD", AVAX_USD),
+    ("Crypto.LINK/USD", LINK_USD),
+    ("Crypto.USDC/USD", USDC_USD),
+    ("Crypto.USDT/USD", USDT_USD),
+    ("FX.EUR/USD", EUR_USD),
+    ("FX.GBP/USD", GBP_USD),
+    ("FX.USD/JPY", USD_JPY),
+    ("Metal.XAU/USD", XAU_USD),
+    ("Metal.XAG/USD", XAG_USD),
+];
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{types::PUBLIC_BASE_URL, HermesClient},
+        std::collections::HashSet,
+    };
+
+    #[test]
+    fn test_unique() {
+        assert_eq!(
+            ALL.iter().map(|(_, id)| id).collect::<HashSet<_>>().len(),
+            ALL.len()
+        );
+    }
+
+    /// The constants still match the symbols in the public deployment's metadata
+    #[tokio::test]
+    async fn test_feed_ids_live() {
+        let symbols: Vec<_> = ALL.iter().map(|(symbol, _)| *symbol).collect();
+        let ids = HermesClient::new(PUBLIC_BASE_URL)
+            .resolve_feed_symbols(&symbols)
+            .await
+            .unwrap();
+        assert_eq!(ids, ALL.map(|(_, id)| id));
+    }
+}
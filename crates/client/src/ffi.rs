@@ -0,0 +1,326 @@
+//! C ABI for embedding the client in trading systems written in other languages
+//!
+//! Built into the crate's cdylib when the `ffi` feature is enabled, and declared in the header checked in as
+//! `include/pyth_hermes.h`, which builds regenerate into `OUT_DIR`. Requests block the calling thread, and subscription callbacks are invoked from a
+//! background thread. Functions returning a [`HermesStatus`] other than `Ok`, or a null pointer, describe the failure
+//! in [`hermes_last_error`].
+
+use {
+    crate::{blocking, stream::StreamHandle, types::*},
+    std::{
+        cell::RefCell,
+        ffi::{c_char, c_void, CStr, CString},
+        ptr, slice,
+    },
+};
+
+/// Outcome of a call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HermesStatus {
+    Ok = 0,
+    /// A pointer was null or an argument was malformed
+    InvalidArgument = -1,
+    /// The request to hermes failed
+    RequestFailed = -2,
+}
+
+/// Price of a feed, where the value is `price * 10^expo`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HermesPrice {
+    pub id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    /// Unix timestamp in seconds
+    pub publish_time: i64,
+}
+
+/// Client handle, created with [`hermes_client_new`]
+pub struct HermesClient(blocking::HermesClient);
+
+/// Subscription handle, created with [`hermes_subscribe`]
+pub struct HermesSubscription(StreamHandle);
+
+/// Invoked with each price update and the `user_data` passed to [`hermes_subscribe`]
+pub type HermesPriceCallback =
+    Option<unsafe extern "C" fn(price: *const HermesPrice, user_data: *mut c_void)>;
+
+/// Pointer passed back to the subscriber's callback, which is responsible for its thread safety
+struct UserData(*mut c_void);
+
+// SAFETY: the caller of `hermes_subscribe` guarantees `user_data` can be used from the callback's thread
+unsafe impl Send for UserData {}
+
+impl UserData {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Message describing the last failure on the calling thread, or null if there was none
+///
+/// The message is valid until the next call on the same thread.
+#[no_mangle]
+pub extern "C" fn hermes_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Creates a client for the hermes deployment at `base_url`, returning null on failure
+///
+/// # Safety
+///
+/// `base_url` must be null or a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hermes_client_new(base_url: *const c_char) -> *mut HermesClient {
+    if base_url.is_null() {
+        fail(HermesStatus::InvalidArgument, "base_url is null");
+        return ptr::null_mut();
+    }
+    let Ok(base_url) = CStr::from_ptr(base_url).to_str() else {
+        fail(HermesStatus::InvalidArgument, "base_url is not valid utf-8");
+        return ptr::null_mut();
+    };
+    match crate::HermesClient::builder(base_url).build() {
+        Ok(client) => Box::into_raw(Box::new(HermesClient(client.into()))),
+        Err(err) => {
+            fail(HermesStatus::InvalidArgument, err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a client, stopping the subscriptions created with it
+///
+/// # Safety
+///
+/// `client` must be null or returned by [`hermes_client_new`], and not used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn hermes_client_free(client: *mut HermesClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Fetches the latest prices of `count` feeds, whose 32 byte ids are stored contiguously in `ids`
+///
+/// Up to `count` prices are written to `out`, and the number written to `written`. Feeds hermes doesn't return a
+/// price for are left out.
+///
+/// # Safety
+///
+/// `client` must be a live client, `ids` must point to `count * 32` bytes, `out` to space for `count` prices and
+/// `written` to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn hermes_latest_prices(
+    client: *const HermesClient,
+    ids: *const u8,
+    count: usize,
+    out: *mut HermesPrice,
+    written: *mut usize,
+) -> HermesStatus {
+    if client.is_null() || (count > 0 && (ids.is_null() || out.is_null())) || written.is_null() {
+        return fail(HermesStatus::InvalidArgument, "null argument");
+    }
+    *written = 0;
+    let ids = feed_ids(ids, count);
+    let feeds = match (*client).0.get_latest_price_feeds(&ids) {
+        Ok(feeds) => feeds,
+        Err(err) => return fail(HermesStatus::RequestFailed, err),
+    };
+    let out = slice::from_raw_parts_mut(out, count);
+    for (slot, price) in out
+        .iter_mut()
+        .zip(feeds.iter().filter_map(|feed| to_c(feed.id, &feed.price)))
+    {
+        *slot = price;
+        *written += 1;
+    }
+    HermesStatus::Ok
+}
+
+/// Streams price updates for `count` feeds, invoking `callback` with each from a background thread
+///
+/// Returns null on failure. The stream runs until it is freed with [`hermes_subscription_free`] or its client is
+/// freed.
+///
+/// # Safety
+///
+/// `client` must be a live client and `ids` must point to `count * 32` bytes. `callback` must be safe to call with
+/// `user_data` from another thread until the subscription is freed.
+#[no_mangle]
+pub unsafe extern "C" fn hermes_subscribe(
+    client: *const HermesClient,
+    ids: *const u8,
+    count: usize,
+    callback: HermesPriceCallback,
+    user_data: *mut c_void,
+) -> *mut HermesSubscription {
+    let Some(callback) = callback.filter(|_| !(client.is_null() || (count > 0 && ids.is_null())))
+    else {
+        fail(HermesStatus::InvalidArgument, "null argument");
+        return ptr::null_mut();
+    };
+    let ids = feed_ids(ids, count);
+    let user_data = UserData(user_data);
+    let on_event = move |update: ParsedPriceUpdate| {
+        if let Some(price) = to_c(update.id, &update.price) {
+            // SAFETY: guaranteed by the caller of `hermes_subscribe`
+            unsafe { callback(&price, user_data.get()) };
+        }
+    };
+    match (*client).0.stream_price_updates(&ids, on_event) {
+        Ok(handle) => Box::into_raw(Box::new(HermesSubscription(handle))),
+        Err(err) => {
+            fail(HermesStatus::RequestFailed, err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Stops and frees a subscription
+///
+/// # Safety
+///
+/// `subscription` must be null or returned by [`hermes_subscribe`], and not used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn hermes_subscription_free(subscription: *mut HermesSubscription) {
+    if !subscription.is_null() {
+        Box::from_raw(subscription).0.abort();
+    }
+}
+
+/// Records `message` as the calling thread's last error
+fn fail(status: HermesStatus, message: impl ToString) -> HermesStatus {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+    status
+}
+
+/// # Safety
+///
+/// `ids` must point to `count * 32` bytes.
+unsafe fn feed_ids(ids: *const u8, count: usize) -> Vec<FeedId> {
+    if count == 0 {
+        return Vec::new();
+    }
+    slice::from_raw_parts(ids, count * 32)
+        .chunks_exact(32)
+        .map(|id| FeedId::from_bytes(id.try_into().expect("chunks are 32 bytes")))
+        .collect()
+}
+
+fn to_c(id: FeedId, price: &RpcPrice) -> Option<HermesPrice> {
+    Some(HermesPrice {
+        id: id.to_bytes(),
+        price: price.price.parse().ok()?,
+        conf: price.conf.parse().ok()?,
+        expo: price.expo,
+        publish_time: price.publish_time,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+        std::sync::mpsc,
+    };
+
+    unsafe extern "C" fn on_price(price: *const HermesPrice, user_data: *mut c_void) {
+        let sender = &*(user_data as *const mpsc::Sender<HermesPrice>);
+        let _ = sender.send(*price);
+    }
+
+    #[test]
+    fn test_c_abi() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mock = runtime
+            .block_on(MockHermes::start(vec![
+                RecordedResponse::json(
+                    "/v2/updates/price/latest",
+                    price_update_json(&[ETH_USD], 42, 7),
+                ),
+                RecordedResponse::events(
+                    "/v2/updates/price/stream",
+                    vec![price_update_json(&[ETH_USD], 43, 8).to_string()],
+                ),
+            ]))
+            .unwrap();
+        let url = CString::new(mock.url()).unwrap();
+        let ids = ETH_USD.to_bytes();
+
+        unsafe {
+            assert!(hermes_client_new(ptr::null()).is_null());
+            assert!(!hermes_last_error().is_null());
+
+            let client = hermes_client_new(url.as_ptr());
+            assert!(!client.is_null());
+            let mut prices = [HermesPrice {
+                id: [0; 32],
+                price: 0,
+                conf: 0,
+                expo: 0,
+                publish_time: 0,
+            }];
+            let mut written = 0;
+            let status =
+                hermes_latest_prices(client, ids.as_ptr(), 1, prices.as_mut_ptr(), &mut written);
+            assert_eq!(status, HermesStatus::Ok);
+            assert_eq!(written, 1);
+            assert_eq!(prices[0].id, ids);
+            assert_eq!((prices[0].price, prices[0].expo), (42, -8));
+
+            let (sender, receiver) = mpsc::channel::<HermesPrice>();
+            let subscription = hermes_subscribe(
+                client,
+                ids.as_ptr(),
+                1,
+                Some(on_price),
+                &sender as *const _ as *mut c_void,
+            );
+            assert!(!subscription.is_null());
+            let price = receiver.recv().unwrap();
+            assert_eq!((price.price, price.publish_time), (43, 8));
+
+            hermes_subscription_free(subscription);
+
+            // ids are only read when there are any, like for requests
+            let empty = hermes_subscribe(
+                client,
+                ptr::null(),
+                0,
+                Some(on_price),
+                &sender as *const _ as *mut c_void,
+            );
+            assert!(!empty.is_null());
+            hermes_subscription_free(empty);
+            hermes_client_free(client);
+        }
+    }
+
+    #[test]
+    fn test_header_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/pyth_hermes.h"));
+        let checked_in = include_str!("../include/pyth_hermes.h");
+        assert!(
+            generated == checked_in,
+            "include/pyth_hermes.h is out of date, copy it from {}",
+            concat!(env!("OUT_DIR"), "/pyth_hermes.h")
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Formatting of prices for display, with exact decimal rounding
+//!
+//! ```
+//! use pyth_hermes_rs::{format::{Precision, PriceFormat}, types::RpcPrice};
+//!
+//! let price = RpcPrice {
+//!     price: "6743215000000".to_string(),
+//!     conf: "2150000000".to_string(),
+//!     expo: -8,
+//!     publish_time: 0,
+//! };
+//! assert_eq!(price.format(&PriceFormat::en_us().precision(Precision::Decimals(2))), "67,432.15");
+//! assert_eq!(price.format(&PriceFormat::de_de().precision(Precision::SignificantFigures(3))), "67.400");
+//! ```
+
+use crate::types::RpcPrice;
+
+/// How many digits of a price are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    /// Every digit published by the feed, as given by its exponent
+    #[default]
+    Exponent,
+    /// A fixed number of decimal places
+    Decimals(u32),
+    /// A number of significant figures, rounding to tens or hundreds for large values
+    SignificantFigures(u32),
+}
+
+/// Options for formatting prices, see [`RpcPrice::format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceFormat {
+    pub precision: Precision,
+    /// Inserted between groups of three integer digits
+    pub thousands_separator: Option<char>,
+    pub decimal_separator: char,
+}
+
+impl Default for PriceFormat {
+    /// All digits without grouping, like `1606.44665033`
+    fn default() -> Self {
+        Self {
+            precision: Precision::Exponent,
+            thousands_separator: None,
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl PriceFormat {
+    /// `1,606.45`
+    pub fn en_us() -> Self {
+        Self {
+            thousands_separator: Some(','),
+            ..Self::default()
+        }
+    }
+
+    /// `1.606,45`
+    pub fn de_de() -> Self {
+        Self {
+            thousands_separator: Some('.'),
+            decimal_separator: ',',
+            ..Self::default()
+        }
+    }
+
+    /// `1 606,45`, grouped with narrow no-break spaces
+    pub fn fr_fr() -> Self {
+        Self {
+            thousands_separator: Some('\u{202f}'),
+            decimal_separator: ',',
+            ..Self::default()
+        }
+    }
+
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Formats `value * 10^expo`, where `value` is an integer string as published by hermes
+    ///
+    /// Rounding is exact, with halves rounded away from zero. Returns `None` if `value` is not an integer.
+    pub fn format(&self, value: &str, expo: i32) -> Option<String> {
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut digits: Vec<u8> = digits.bytes().map(|b| b - b'0').collect();
+        let mut scale = -i64::from(expo);
+        if scale < 0 {
+            digits.extend(std::iter::repeat_n(0, scale.unsigned_abs() as usize));
+            scale = 0;
+        }
+        while digits.len() > 1 && digits[0] == 0 {
+            digits.remove(0);
+        }
+
+        let places = match self.precision {
+            Precision::Exponent => scale,
+            Precision::Decimals(places) => i64::from(places),
+            Precision::SignificantFigures(figures) => {
+                if digits == [0] {
+                    scale
+                } else {
+                    i64::from(figures.max(1)) - (digits.len() as i64 - scale)
+                }
+            }
+        };
+        let (mut digits, mut scale) = round(digits, scale, places);
+        if let Precision::SignificantFigures(figures) = self.precision {
+            // rounding up can carry into a new leading digit, such as 0.9996 becoming 1.000
+            let significant = digits.iter().skip_while(|d| **d == 0).count();
+            if significant > figures.max(1) as usize && scale > 0 {
+                digits.pop();
+                scale -= 1;
+            }
+        }
+        Some(self.render(negative && digits.iter().any(|d| *d != 0), &digits, scale))
+    }
+
+    fn render(&self, negative: bool, digits: &[u8], scale: usize) -> String {
+        let padded: Vec<u8> = std::iter::repeat_n(0, (scale + 1).saturating_sub(digits.len()))
+            .chain(digits.iter().copied())
+            .collect();
+        let (int, frac) = padded.split_at(padded.len() - scale);
+        let mut out = String::new();
+        if negative {
+            out.push('-');
+        }
+        for (i, digit) in int.iter().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                if let Some(separator) = self.thousands_separator {
+                    out.push(separator);
+                }
+            }
+            out.push(char::from(b'0' + digit));
+        }
+        if !frac.is_empty() {
+            out.push(self.decimal_separator);
+            out.extend(frac.iter().map(|digit| char::from(b'0' + digit)));
+        }
+        out
+    }
+}
+
+/// Rounds `digits * 10^-scale` to `places` decimal places, which may be negative to round to tens and above
+fn round(mut digits: Vec<u8>, scale: i64, places: i64) -> (Vec<u8>, usize) {
+    if places >= scale {
+        digits.extend(std::iter::repeat_n(0, (places - scale) as usize));
+        return (digits, places as usize);
+    }
+    let drop = ((scale - places) as usize).min(digits.len() + 1);
+    let round_up = digits.len() >= drop && digits[digits.len() - drop] >= 5;
+    digits.truncate(digits.len().saturating_sub(drop));
+    if round_up {
+        let mut i = digits.len();
+        loop {
+            if i == 0 {
+                digits.insert(0, 1);
+                break;
+            }
+            i -= 1;
+            if digits[i] == 9 {
+                digits[i] = 0;
+            } else {
+                digits[i] += 1;
+                break;
+            }
+        }
+    }
+    if digits.is_empty() {
+        digits.push(0);
+    }
+    if places < 0 {
+        // restore the magnitude of values rounded to tens and above
+        digits.extend(std::iter::repeat_n(0, places.unsigned_abs() as usize));
+        return (digits, 0);
+    }
+    (digits, places as usize)
+}
+
+impl RpcPrice {
+    /// Formats the price, see [`PriceFormat`]
+    ///
+    /// Prices which are not valid integers are returned unchanged.
+    pub fn format(&self, format: &PriceFormat) -> String {
+        format
+            .format(&self.price, self.expo)
+            .unwrap_or_else(|| self.price.clone())
+    }
+
+    /// Formats the confidence interval with the price's exponent, see [`PriceFormat`]
+    pub fn format_conf(&self, format: &PriceFormat) -> String {
+        format
+            .format(&self.conf, self.expo)
+            .unwrap_or_else(|| self.conf.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_price_format() {
+        let en = PriceFormat::en_us();
+        assert_eq!(en.format("160644665033", -8).unwrap(), "1,606.44665033");
+        assert_eq!(en.format("5", -8).unwrap(), "0.00000005");
+        assert_eq!(en.format("12", 3).unwrap(), "12,000");
+
+        let decimals = en.precision(Precision::Decimals(2));
+        assert_eq!(decimals.format("160644665033", -8).unwrap(), "1,606.45");
+        assert_eq!(decimals.format("99999999999", -8).unwrap(), "1,000.00");
+        assert_eq!(decimals.format("-400000", -8).unwrap(), "0.00");
+        assert_eq!(decimals.format("-500000", -8).unwrap(), "-0.01");
+        assert_eq!(decimals.format("1", 0).unwrap(), "1.00");
+
+        let sig = PriceFormat::default().precision(Precision::SignificantFigures(3));
+        assert_eq!(sig.format("160644665033", -8).unwrap(), "1610");
+        assert_eq!(sig.format("123456", -8).unwrap(), "0.00123");
+        assert_eq!(sig.format("99960", -5).unwrap(), "1.00");
+        assert_eq!(sig.format("0", -8).unwrap(), "0.00000000");
+
+        let de = PriceFormat::de_de().precision(Precision::Decimals(1));
+        assert_eq!(de.format("-123456789", -2).unwrap(), "-1.234.567,9");
+        assert_eq!(
+            PriceFormat::fr_fr().format("123456", -2).unwrap(),
+            "1\u{202f}234,56"
+        );
+        assert_eq!(en.format("1.5", -8), None);
+    }
+}
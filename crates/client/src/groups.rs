@@ -0,0 +1,161 @@
+//! Named groups of feeds, such as `majors` or `stables`, which can be used in place of feed ids
+//!
+//! Groups are shared by every clone of a [`HermesClient`] and resolved when they are used, so a group redefined at
+//! runtime applies to the next request or subscription made with it.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::{types::FeedId, HermesClient};
+//!
+//! let btc = FeedId::from_hex("e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43");
+//! let eth = FeedId::from_hex("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace");
+//! let client = HermesClient::builder("https://hermes.pyth.network")
+//!     .feed_group("majors", [btc, eth])
+//!     .build()?;
+//! let ids = client.resolve_feeds(&["majors"])?;
+//! let feeds = client.get_latest_price_feeds(&ids).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{error::HermesError, types::FeedId, HermesClient},
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, RwLock},
+    },
+};
+
+/// Registry of named feed groups, see the [module documentation](self)
+#[derive(Debug, Clone, Default)]
+pub struct FeedGroups(Arc<RwLock<HashMap<String, Vec<FeedId>>>>);
+
+impl FeedGroups {
+    /// Defines or replaces the group `name`
+    pub fn define(&self, name: impl Into<String>, ids: impl IntoIterator<Item = FeedId>) {
+        self.write().insert(name.into(), ids.into_iter().collect());
+    }
+
+    /// Removes the group `name`, returning its feeds
+    pub fn remove(&self, name: &str) -> Option<Vec<FeedId>> {
+        self.write().remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec<FeedId>> {
+        self.read().get(name).cloned()
+    }
+
+    /// Names of the defined groups, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Expands each selector, either a group name or a hex feed id, into feed ids
+    ///
+    /// Feeds are returned in the order they are first selected, without duplicates. Group names take precedence over
+    /// feed ids.
+    pub fn resolve(&self, selectors: &[impl AsRef<str>]) -> Result<Vec<FeedId>, HermesError> {
+        self.resolve_with(selectors, |_| None)
+    }
+
+    /// Resolves selectors like [`FeedGroups::resolve`], trying `lookup` before parsing those which aren't groups
+    fn resolve_with(
+        &self,
+        selectors: &[impl AsRef<str>],
+        lookup: impl Fn(&str) -> Option<FeedId>,
+    ) -> Result<Vec<FeedId>, HermesError> {
+        let groups = self.read();
+        let mut seen = HashSet::new();
+        let mut ids = Vec::new();
+        for selector in selectors {
+            let selector = selector.as_ref();
+            let selected = match groups.get(selector) {
+                Some(group) => group.clone(),
+                None => vec![match lookup(selector) {
+                    Some(id) => id,
+                    None => FeedId::parse(selector)
+                        .map_err(|_| HermesError::UnknownFeed(selector.to_string()))?,
+                }],
+            };
+            ids.extend(selected.into_iter().filter(|id| seen.insert(*id)));
+        }
+        Ok(ids)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Vec<FeedId>>> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Vec<FeedId>>> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HermesClient {
+    /// Feed groups shared by the client and its clones
+    pub fn feed_groups(&self) -> &FeedGroups {
+        &self.feed_groups
+    }
+
+    /// Expands group names, feed aliases and hex feed ids into feed ids, see [`FeedGroups::resolve`]
+    ///
+    /// Aliases, see [`crate::aliases`], take precedence over feed ids.
+    pub fn resolve_feeds(&self, selectors: &[impl AsRef<str>]) -> Result<Vec<FeedId>, HermesError> {
+        self.feed_groups
+            .resolve_with(selectors, |selector| self.feed_aliases.get(selector))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD},
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[test]
+    fn test_resolve() {
+        let groups = FeedGroups::default();
+        groups.define("majors", [BTC_USD, ETH_USD]);
+        groups.define("eth", [ETH_USD]);
+        assert_eq!(groups.names(), ["eth", "majors"]);
+
+        let btc = BTC_USD.to_hex();
+        assert_eq!(
+            groups.resolve(&["eth", "majors", btc.as_str()]).unwrap(),
+            [ETH_USD, BTC_USD]
+        );
+        assert!(matches!(
+            groups.resolve(&["stables"]),
+            Err(HermesError::UnknownFeed(name)) if name == "stables"
+        ));
+
+        groups.define("eth", []);
+        assert!(groups.resolve(&["eth"]).unwrap().is_empty());
+        assert_eq!(groups.remove("eth"), Some(Vec::new()));
+        assert!(groups.get("eth").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_client_feed_groups() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD], 100_000_000, 5),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .feed_group("majors", [ETH_USD])
+            .build()
+            .unwrap();
+        // groups defined on a clone are visible to the original client
+        client.clone().feed_groups().define("watchlist", [ETH_USD]);
+
+        let ids = client.resolve_feeds(&["majors", "watchlist"]).unwrap();
+        let feeds = client.get_latest_price_feeds(&ids).await.unwrap();
+        assert_eq!(feeds[0].id, ETH_USD);
+    }
+}
@@ -0,0 +1,58 @@
+//! Health probes for hermes deployments
+
+use crate::{error::HermesError, HermesClient};
+
+impl HermesClient {
+    /// Whether the deployment's `/live` endpoint reports the process is running
+    ///
+    /// Probes only the base url, bypassing retries, failover and the circuit breaker. Error statuses are reported
+    /// as `Ok(false)`, while failing to reach the deployment is an error.
+    pub async fn is_live(&self) -> Result<bool, HermesError> {
+        self.probe("/live").await
+    }
+
+    /// Whether the deployment's `/ready` endpoint reports it is ready to serve traffic, see
+    /// [`HermesClient::is_live`]
+    pub async fn is_ready(&self) -> Result<bool, HermesError> {
+        self.probe("/ready").await
+    }
+
+    async fn probe(&self, path: &str) -> Result<bool, HermesError> {
+        let url = self.url(path)?;
+        let req = self.request_options.apply(self.http.get(url))?;
+        match self.exchange(req).await {
+            Ok(_) => Ok(true),
+            Err(HermesError::Http(err)) if err.status().is_some() => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::mock::{MockHermes, RecordedResponse},
+        serde_json::json,
+    };
+
+    #[tokio::test]
+    async fn test_health_probes() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json("/live", json!("OK")),
+            RecordedResponse::status("/ready", 503),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        assert!(client.is_live().await.unwrap());
+        assert!(!client.is_ready().await.unwrap());
+
+        drop(mock);
+        let client = HermesClient::new(crate::request::test::unresponsive_server().await)
+            .with_options(crate::request::RequestOptions::timeout(
+                std::time::Duration::from_millis(50),
+            ));
+        assert!(client.is_live().await.is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Local store of historical price updates consulted before requesting hermes
+//!
+//! With a [`HistoryStore`] set by [`HermesClientBuilder::history_store`], [`HermesClient::get_price_updates_by_time`]
+//! answers the feeds the store holds at the requested publish time locally and only requests the missing ones from
+//! hermes. The updates fetched are saved to the store and merged with the stored ones, so backtests replaying the
+//! same range request each update from hermes once.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::{
+//!     builder::HermesClientBuilder, feed_ids, history::MemoryHistoryStore,
+//!     types::PriceUpdatesByTimeOptions,
+//! };
+//!
+//! let client = HermesClientBuilder::new("https://hermes.pyth.network")
+//!     .history_store(MemoryHistoryStore::default())
+//!     .build()?;
+//! let options = PriceUpdatesByTimeOptions::default();
+//! let first = client.get_price_updates_by_time(1717581600, &[feed_ids::BTC_USD], &options).await?;
+//! // answered from the store
+//! let again = client.get_price_updates_by_time(1717581600, &[feed_ids::BTC_USD], &options).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`HermesClientBuilder::history_store`]: crate::builder::HermesClientBuilder::history_store
+
+use {
+    crate::{
+        error::{BoxError, HermesError},
+        request::{merge_updates, unique_ids},
+        types::{FeedId, PriceUpdate, PriceUpdatesByTimeOptions},
+        HermesClient,
+    },
+    futures_util::future::{self, BoxFuture},
+    std::{
+        collections::{HashMap, HashSet},
+        sync::Mutex,
+    },
+};
+
+/// Store of the updates fetched by [`HermesClient::get_price_updates_by_time`], such as a database kept between
+/// backtests
+pub trait HistoryStore: Send + Sync {
+    /// Updates saved at `publish_time` for requests with `options` which hold any of `ids`
+    fn load<'a>(
+        &'a self,
+        publish_time: i64,
+        options: &'a PriceUpdatesByTimeOptions,
+        ids: &'a [FeedId],
+    ) -> BoxFuture<'a, Result<Vec<PriceUpdate>, BoxError>>;
+
+    /// Saves `update` fetched from hermes at `publish_time` with `options`
+    fn save<'a>(
+        &'a self,
+        publish_time: i64,
+        options: &'a PriceUpdatesByTimeOptions,
+        update: PriceUpdate,
+    ) -> BoxFuture<'a, Result<(), BoxError>>;
+}
+
+/// [`HistoryStore`] keeping updates in memory for the lifetime of the process
+#[derive(Default)]
+pub struct MemoryHistoryStore {
+    updates: Mutex<HashMap<(i64, PriceUpdatesByTimeOptions), Vec<PriceUpdate>>>,
+}
+
+impl HistoryStore for MemoryHistoryStore {
+    fn load<'a>(
+        &'a self,
+        publish_time: i64,
+        options: &'a PriceUpdatesByTimeOptions,
+        ids: &'a [FeedId],
+    ) -> BoxFuture<'a, Result<Vec<PriceUpdate>, BoxError>> {
+        let updates = self.lock();
+        let held = updates
+            .get(&(publish_time, options.clone()))
+            .into_iter()
+            .flatten()
+            .filter(|update| {
+                update
+                    .parsed
+                    .iter()
+                    .flatten()
+                    .any(|feed| ids.contains(&feed.id))
+            })
+            .cloned()
+            .collect();
+        Box::pin(future::ready(Ok(held)))
+    }
+
+    fn save<'a>(
+        &'a self,
+        publish_time: i64,
+        options: &'a PriceUpdatesByTimeOptions,
+        update: PriceUpdate,
+    ) -> BoxFuture<'a, Result<(), BoxError>> {
+        self.lock()
+            .entry((publish_time, options.clone()))
+            .or_default()
+            .push(update);
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+impl MemoryHistoryStore {
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<(i64, PriceUpdatesByTimeOptions), Vec<PriceUpdate>>>
+    {
+        self.updates.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HermesClient {
+    /// Updates of `ids` at `publish_time` answered from `store` where it holds them, fetching the others from
+    /// hermes and saving them to it
+    ///
+    /// The binary data of stored updates may include feeds other than `ids`, their parsed prices are left out.
+    pub(crate) async fn get_stored_price_updates_by_time(
+        &self,
+        store: &dyn HistoryStore,
+        publish_time: i64,
+        ids: &[impl AsRef<FeedId>],
+        options: &PriceUpdatesByTimeOptions,
+    ) -> Result<PriceUpdate, HermesError> {
+        self.misuse_policy.check_ids(ids)?;
+        let ids = unique_ids(ids);
+        self.feed_policy.check(&ids)?;
+        let mut updates = store
+            .load(publish_time, options, &ids)
+            .await
+            .map_err(HermesError::History)?;
+        let held: HashSet<FeedId> = updates
+            .iter()
+            .flat_map(|update| update.parsed.iter().flatten().map(|feed| feed.id))
+            .collect();
+        let missing: Vec<FeedId> = ids
+            .iter()
+            .filter(|id| !held.contains(id))
+            .copied()
+            .collect();
+        if updates.is_empty() || !missing.is_empty() {
+            let fetched = self
+                .fetch_price_updates_by_time(publish_time, &missing, options)
+                .await?;
+            if let Err(err) = store.save(publish_time, options, fetched.clone()).await {
+                log::warn!(
+                    "failed to save price updates at {publish_time} to the history store: {err}"
+                );
+            }
+            updates.push(fetched);
+        }
+        let mut merged = merge_updates(updates);
+        if let Some(parsed) = &mut merged.parsed {
+            let mut seen = HashSet::new();
+            parsed.retain(|feed| ids.contains(&feed.id) && seen.insert(feed.id));
+        }
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            builder::HermesClientBuilder,
+            feed_ids::{BTC_USD, ETH_USD, SOL_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_history_store() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/100",
+                price_update_json(&[ETH_USD, BTC_USD], 1, 100),
+            ),
+            RecordedResponse::json(
+                "/v2/updates/price/100",
+                price_update_json(&[SOL_USD], 1, 100),
+            ),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClientBuilder::new(mock.url())
+            .history_store(MemoryHistoryStore::default())
+            .build()
+            .unwrap();
+        let options = PriceUpdatesByTimeOptions::default();
+        let ids = |update: &PriceUpdate| -> Vec<FeedId> {
+            update.parsed.iter().flatten().map(|feed| feed.id).collect()
+        };
+
+        let update = client
+            .get_price_updates_by_time(100, &[ETH_USD, BTC_USD], &options)
+            .await
+            .unwrap();
+        assert_eq!(ids(&update), [ETH_USD, BTC_USD]);
+        // stored feeds are answered locally
+        let update = client
+            .get_price_updates_by_time(100, &[BTC_USD], &options)
+            .await
+            .unwrap();
+        assert_eq!(ids(&update), [BTC_USD]);
+        assert_eq!(mock.requests().await.len(), 1);
+
+        // only the missing feed is requested, and merged with the stored ones
+        let update = client
+            .get_price_updates_by_time(100, &[ETH_USD, SOL_USD], &options)
+            .await
+            .unwrap();
+        assert_eq!(ids(&update), [ETH_USD, SOL_USD]);
+        let requests = mock.requests().await;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].query.as_deref(),
+            Some(format!("ids%5B%5D={SOL_USD}").as_str())
+        );
+
+        // requests with other options aren't answered from the store
+        let benchmarks = PriceUpdatesByTimeOptions {
+            benchmarks_only: Some(true),
+            ..Default::default()
+        };
+        client
+            .get_price_updates_by_time(100, &[ETH_USD], &benchmarks)
+            .await
+            .unwrap();
+        assert_eq!(mock.requests().await.len(), 3);
+    }
+}
@@ -0,0 +1,79 @@
+//! Hooks observing and modifying the requests made by a client, for logging, metrics or custom headers
+
+use reqwest::{Request, Response};
+
+/// Called for every request a client makes, see
+/// [`HermesClientBuilder::interceptor`](crate::builder::HermesClientBuilder::interceptor)
+///
+/// Interceptors run in the order they were added, after auth headers are attached and before the request is signed,
+/// so headers they add are covered by the signature.
+pub trait Interceptor: Send + Sync {
+    /// Called before each REST request and SSE connection is sent, including retries and reconnects
+    fn on_request(&self, _request: &mut Request) {}
+
+    /// Called with each REST response before its status is checked
+    ///
+    /// SSE responses are consumed by the stream and aren't passed to interceptors.
+    fn on_response(&self, _response: &Response) {}
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+            HermesClient,
+        },
+        reqwest::header::HeaderValue,
+        std::sync::{Arc, Mutex},
+    };
+
+    /// Tags requests with a sequence number and records response statuses
+    #[derive(Default)]
+    struct Recorder {
+        requests: Mutex<u32>,
+        statuses: Mutex<Vec<u16>>,
+    }
+
+    impl Interceptor for Arc<Recorder> {
+        fn on_request(&self, request: &mut Request) {
+            let mut requests = self.requests.lock().unwrap();
+            *requests += 1;
+            request
+                .headers_mut()
+                .insert("x-request-seq", HeaderValue::from(*requests));
+        }
+
+        fn on_response(&self, response: &Response) {
+            self.statuses
+                .lock()
+                .unwrap()
+                .push(response.status().as_u16());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interceptor() {
+        let path = "/v2/updates/price/latest";
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status(path, 503),
+            RecordedResponse::json(path, price_update_json(&[ETH_USD], 1, 1)),
+        ])
+        .await
+        .unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let client = HermesClient::builder(mock.url())
+            .interceptor(recorder.clone())
+            .retry_policy(crate::retry::RetryPolicy::new(2).base_delay(std::time::Duration::ZERO))
+            .build()
+            .unwrap();
+        client.get_latest_price_feeds(&[ETH_USD]).await.unwrap();
+
+        assert_eq!(*recorder.statuses.lock().unwrap(), [503, 200]);
+        let requests = mock.requests().await;
+        assert_eq!(requests[0].headers["x-request-seq"], "1");
+        assert_eq!(requests[1].headers["x-request-seq"], "2");
+    }
+}
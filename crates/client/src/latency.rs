@@ -0,0 +1,118 @@
+//! Measuring round trip times to hermes endpoints
+
+use {
+    crate::{error::HermesError, rt, HermesClient},
+    std::time::Duration,
+};
+
+/// Round trip times measured against a single endpoint by [`HermesClient::probe_latency`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyReport {
+    pub endpoint: String,
+    /// Number of successful probes the percentiles are computed from
+    pub samples: usize,
+    pub failures: usize,
+    pub min: Option<Duration>,
+    pub p50: Option<Duration>,
+    pub p90: Option<Duration>,
+    pub p99: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+impl LatencyReport {
+    fn new(endpoint: &str, mut samples: Vec<Duration>, failures: usize) -> Self {
+        samples.sort();
+        // nearest rank percentile
+        let percentile = |p: usize| {
+            let rank = (p * samples.len()).div_ceil(100).max(1);
+            samples.get(rank - 1).copied()
+        };
+        Self {
+            endpoint: endpoint.to_string(),
+            samples: samples.len(),
+            failures,
+            min: samples.first().copied(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples.last().copied(),
+        }
+    }
+}
+
+impl HermesClient {
+    /// Sends `n` sequential requests to the `/live` endpoint of the base url and each fallback url, returning
+    /// round trip statistics per endpoint in the order the endpoints are configured
+    ///
+    /// Reports can be sorted by `p50` or `p99` to pick the best region.
+    pub async fn probe_latency(&self, n: usize) -> Result<Vec<LatencyReport>, HermesError> {
+        let mut reports = Vec::new();
+        for endpoint in self.endpoints() {
+            let url = crate::endpoint_url(endpoint, "/live")?;
+            let (mut samples, mut failures) = (Vec::with_capacity(n), 0);
+            for _ in 0..n {
+                let req = self.request_options.apply(self.http.get(url.clone()))?;
+                let stopwatch = rt::Stopwatch::start();
+                match self.exchange(req).await {
+                    Ok(resp) => {
+                        // include reading the body in the round trip
+                        let _ = resp.bytes().await;
+                        samples.push(stopwatch.elapsed());
+                    }
+                    Err(err) => {
+                        log::debug!("latency probe to {url} failed: {err}");
+                        failures += 1;
+                    }
+                }
+            }
+            reports.push(LatencyReport::new(endpoint, samples, failures));
+        }
+        Ok(reports)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::mock::{MockHermes, RecordedResponse},
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_latency_percentiles() {
+        let samples = (1..=100).rev().map(Duration::from_millis).collect();
+        let report = LatencyReport::new("http://localhost", samples, 2);
+        assert_eq!(report.samples, 100);
+        assert_eq!(report.failures, 2);
+        assert_eq!(report.min, Some(Duration::from_millis(1)));
+        assert_eq!(report.p50, Some(Duration::from_millis(50)));
+        assert_eq!(report.p90, Some(Duration::from_millis(90)));
+        assert_eq!(report.p99, Some(Duration::from_millis(99)));
+        assert_eq!(report.max, Some(Duration::from_millis(100)));
+
+        let empty = LatencyReport::new("http://localhost", Vec::new(), 0);
+        assert_eq!(empty.p50, None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_latency() {
+        let healthy = MockHermes::start(vec![RecordedResponse::json("/live", json!("OK"))])
+            .await
+            .unwrap();
+        let unhealthy = MockHermes::start(vec![RecordedResponse::status("/live", 503)])
+            .await
+            .unwrap();
+        let client = HermesClient::builder(healthy.url())
+            .fallback_url(unhealthy.url())
+            .build()
+            .unwrap();
+
+        let reports = client.probe_latency(3).await.unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].endpoint, healthy.url());
+        assert_eq!((reports[0].samples, reports[0].failures), (3, 0));
+        assert!(reports[0].p50.is_some());
+        assert_eq!((reports[1].samples, reports[1].failures), (0, 3));
+    }
+}
@@ -1,59 +1,252 @@
 //! Rust library for querying deployments of the Pyth Hermes API
+//!
+//! When targeting `wasm32` the `wasm` feature must be enabled, requests are then made with the browser's fetch
+//! api and streams run on the browser's event loop. On other targets TLS is provided by the `native-tls` feature,
+//! enabled by default, or the `rustls` feature.
 
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("the `wasm` feature must be enabled when targeting wasm32");
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    not(any(feature = "native-tls", feature = "rustls"))
+))]
+compile_error!("either the `native-tls` or `rustls` feature must be enabled");
+
+#[cfg(all(feature = "uniffi", not(target_arch = "wasm32")))]
+uniffi::setup_scaffolding!("pyth_hermes");
+
+pub mod aliases;
+pub mod auth;
+pub mod backtest;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod builder;
+pub mod catalog;
+pub mod circuit_breaker;
+pub mod clock;
+pub mod convert;
+pub mod correlation;
+pub mod diagnostics;
+pub mod diff;
+pub mod error;
+pub mod etag;
+pub mod feed_ids;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+pub mod format;
+pub mod groups;
+pub mod health;
+pub mod history;
+pub mod interceptor;
+pub mod latency;
+pub mod misuse;
+#[cfg(all(feature = "uniffi", not(target_arch = "wasm32")))]
+pub mod mobile;
+#[cfg(all(any(test, feature = "mock"), not(target_arch = "wasm32")))]
+pub mod mock;
+pub mod order;
+pub mod policy;
+pub mod poll;
+pub mod profile;
+#[cfg(all(feature = "python", not(target_arch = "wasm32")))]
+pub mod python;
+pub mod rank;
+pub mod rate_limit;
+pub mod request;
+pub mod retry;
+pub mod rng;
+pub mod rt;
+pub mod scenario;
+pub mod schema;
+pub mod search;
+pub mod shared;
+pub mod signing;
+pub mod snapshot;
+pub mod stream;
+pub mod symbols;
+pub mod timer;
+pub mod twap;
 pub mod types;
+#[cfg(all(feature = "web", not(target_arch = "wasm32")))]
+pub mod web;
+pub mod zscore;
 
 use {
-    futures_util::StreamExt,
-    reqwest::{Client, Error},
-    reqwest_eventsource::{Error as EventSourceError, Event, EventSource},
-    std::sync::Arc,
-    tokio::task::JoinHandle,
+    aliases::FeedAliases,
+    auth::{ApiKey, AuthProvider},
+    builder::HermesClientBuilder,
+    circuit_breaker::{CircuitBreaker, CircuitState},
+    clock::Clock,
+    diagnostics::{Registry, StreamRegistry},
+    error::HermesError,
+    etag::MetadataCache,
+    futures_util::future::select_ok,
+    groups::FeedGroups,
+    history::HistoryStore,
+    interceptor::Interceptor,
+    misuse::MisusePolicy,
+    policy::FeedPolicy,
+    rate_limit::RateLimiter,
+    request::{Chunking, RequestOptions},
+    reqwest::{
+        header::{AUTHORIZATION, RETRY_AFTER},
+        RequestBuilder, Response, StatusCode, Url,
+    },
+    retry::RetryPolicy,
+    rng::Rng,
+    signing::RequestSigner,
+    std::{collections::HashMap, sync::Arc},
+    stream::StreamOptions,
+    symbols::SymbolCache,
+    timer::Timer,
     types::*,
 };
 
+#[derive(Clone)]
 pub struct HermesClient {
     http: reqwest::Client,
     base_url: Arc<str>,
+    /// Endpoints tried in order when `base_url` fails
+    fallback_urls: Arc<[Arc<str>]>,
+    auth: Option<Arc<dyn AuthProvider>>,
+    api_key: Option<ApiKey>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    interceptors: Arc<[Arc<dyn Interceptor>]>,
+    history_store: Option<Arc<dyn HistoryStore>>,
+    request_options: RequestOptions,
+    retry_policy: Option<RetryPolicy>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    clock: Arc<dyn Clock>,
+    timer: Arc<dyn Timer>,
+    rng: Rng,
+    chunking: Chunking,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    feed_groups: FeedGroups,
+    feed_aliases: FeedAliases,
+    feed_policy: Arc<FeedPolicy>,
+    misuse_policy: MisusePolicy,
+    metadata_cache: MetadataCache,
+    symbol_cache: SymbolCache,
+    streams: StreamRegistry,
+    shared_streams: Registry<shared::Inner>,
+    catalogs: Registry<catalog::Inner>,
+    /// Header correlation ids are sent in, `None` if they are disabled
+    correlation_header: Option<Arc<str>>,
+    /// Id sent instead of a generated one, see [`HermesClient::with_correlation_id`]
+    correlation_id: Option<Arc<str>>,
 }
 
 impl HermesClient {
+    /// # Panics
+    ///
+    /// If the underlying http client cannot be initialized
     pub fn new(base_url: impl Into<String>) -> Self {
+        Self::builder(base_url)
+            .build()
+            .expect("failed to build http client")
+    }
+
+    /// Returns a builder for configuring the client
+    pub fn builder(base_url: impl Into<String>) -> HermesClientBuilder {
+        HermesClientBuilder::new(base_url)
+    }
+
+    /// Returns a client sharing this client's connections and configuration, which applies `options` to
+    /// every REST request
+    ///
+    /// ```no_run
+    /// # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+    /// use {pyth_hermes_rs::request::RequestOptions, std::time::Duration};
+    ///
+    /// let metadata = client
+    ///     .with_options(RequestOptions::timeout(Duration::from_secs(10)))
+    ///     .get_price_feeds_metadata(None, None)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_options(&self, options: RequestOptions) -> Self {
         Self {
-            http: Client::new(),
-            base_url: Arc::from(base_url.into()),
+            request_options: options,
+            ..self.clone()
         }
     }
 
+    /// State of the client's circuit breaker, `None` if it isn't configured
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.circuit_breaker.as_ref().map(|breaker| breaker.state())
+    }
+
     /// Get the latest price updates by price feed id.
-    pub async fn get_latest_price_feeds(&self, ids: &[&str]) -> Result<Vec<RpcPriceFeed>, Error> {
-        let url = format!("{}/v2/updates/price/latest", self.base_url);
-        let mut req = self.http.get(&url);
-        for id in ids {
-            req = req.query(&[("ids[]", *id)]);
-        }
-        let resp = req.send().await?.error_for_status()?;
-        let feeds = resp.json::<PriceUpdate>().await?;
+    pub async fn get_latest_price_feeds(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<Vec<RpcPriceFeed>, HermesError> {
+        let feeds = self.get_latest_price_updates(ids).await?;
         Ok(feeds.parsed.unwrap_or_default())
     }
 
+    /// Get the latest price updates by price feed id, including the binary update data
+    /// which can be submitted on-chain
+    pub async fn get_latest_price_updates(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<PriceUpdate, HermesError> {
+        self.latest_price().ids(ids).send().await
+    }
+
     /// This endpoint fetches all price feeds from the Pyth network. It can be filtered by asset type and query string.
     ///
     /// # Arguments
     ///
     /// * `query` - If provided results will be filtered for price feeds whose symbol contains the query string
-    /// * `asset_type` - If provides filter by asset type. Values are crypto, equity, fx, metal, rates
+    /// * `asset_type` - If provided results will be filtered by asset type
     pub async fn get_price_feeds_metadata(
         &self,
         query: Option<&str>,
-        asset_type: Option<&str>,
-    ) -> Result<Vec<PriceFeedMetadata>, Error> {
-        let url = format!("{}/v2/price_feeds", self.base_url);
+        asset_type: Option<AssetType>,
+    ) -> Result<Vec<PriceFeedMetadata>, HermesError> {
+        let url = self.url("/v2/price_feeds")?;
         let req = self
             .http
-            .get(&url)
-            .query(&[("query", query), ("asset_type", asset_type)]);
-        let resp = req.send().await?.error_for_status()?;
-        resp.json::<Vec<PriceFeedMetadata>>().await
+            .get(url)
+            .query(&[("query", query)])
+            .query(&[("asset_type", asset_type)]);
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    /// Fetches the metadata of feeds matching the filters joined with each feed's latest price
+    ///
+    /// Prices are requested in chunks of ids as configured by
+    /// [`HermesClientBuilder::chunking`](builder::HermesClientBuilder::chunking), so this works for the full catalog
+    /// of feeds. See
+    /// [`HermesClient::get_price_feeds_metadata`] for the filters. Feeds the client's [`FeedPolicy`] doesn't allow are
+    /// left out.
+    pub async fn get_feeds_with_prices(
+        &self,
+        query: Option<&str>,
+        asset_type: Option<AssetType>,
+    ) -> Result<Vec<FeedWithPrice>, HermesError> {
+        let mut metadata = self.get_price_feeds_metadata(query, asset_type).await?;
+        metadata.retain(|m| self.feed_policy.is_allowed(&m.id));
+        let ids: Vec<FeedId> = metadata.iter().map(|m| m.id).collect();
+        let mut prices: HashMap<FeedId, RpcPriceFeed> = HashMap::new();
+        for (_, update) in self.get_chunks(&ids, self.chunking.max_concurrency).await {
+            prices.extend(
+                update?
+                    .parsed
+                    .into_iter()
+                    .flatten()
+                    .map(|feed| (feed.id, feed)),
+            );
+        }
+        Ok(metadata
+            .into_iter()
+            .map(|metadata| FeedWithPrice {
+                price: prices.remove(&metadata.id),
+                metadata,
+            })
+            .collect())
     }
 
     /// Get the latest price updates by price feed id, with a publish time greater than `publish_time`
@@ -61,18 +254,42 @@ impl HermesClient {
     /// # Arguments
     ///
     /// * `publish_time` - Only return price feed updates that are greater than or equal to this timestamp
+    /// * `options` - Optional query parameters which change which historical updates are returned
+    ///
+    /// Feeds held by the client's [`history::HistoryStore`] are answered from it, only the others are requested.
     pub async fn get_price_updates_by_time(
         &self,
         publish_time: i64,
-        ids: &[&str],
-    ) -> Result<PriceUpdate, Error> {
-        let url = format!("{}/v2/updates/price/{}", self.base_url, publish_time);
-        let mut req = self.http.get(&url);
-        for id in ids {
-            req = req.query(&[("ids[]", *id)]);
+        ids: &[impl AsRef<FeedId>],
+        options: &PriceUpdatesByTimeOptions,
+    ) -> Result<PriceUpdate, HermesError> {
+        match &self.history_store {
+            Some(store) => {
+                self.get_stored_price_updates_by_time(&**store, publish_time, ids, options)
+                    .await
+            }
+            None => {
+                self.fetch_price_updates_by_time(publish_time, ids, options)
+                    .await
+            }
+        }
+    }
+
+    /// Requests the updates of [`HermesClient::get_price_updates_by_time`] from hermes
+    async fn fetch_price_updates_by_time(
+        &self,
+        publish_time: i64,
+        ids: &[impl AsRef<FeedId>],
+        options: &PriceUpdatesByTimeOptions,
+    ) -> Result<PriceUpdate, HermesError> {
+        let mut req = self.price_updates_by_time(publish_time).ids(ids);
+        if let Some(benchmarks_only) = options.benchmarks_only {
+            req = req.benchmarks_only(benchmarks_only);
         }
-        let resp = req.send().await?.error_for_status()?;
-        resp.json::<PriceUpdate>().await
+        if let Some(allow_unordered) = options.allow_unordered {
+            req = req.allow_unordered(allow_unordered);
+        }
+        req.send().await
     }
 
     /// Get the latest TWAP by price feed id with a custom time window.
@@ -82,124 +299,291 @@ impl HermesClient {
     pub async fn get_latest_twaps(
         &self,
         window_seconds: u64,
-        ids: &[&str],
-    ) -> Result<TwapsResponse, Error> {
-        let url = format!(
-            "{}/v2/updates/twap/{}/latest",
-            self.base_url, window_seconds
-        );
-        let mut req = self.http.get(&url);
-        for id in ids {
-            req = req.query(&[("ids[]", *id)]);
-        }
-        let resp = req.send().await?.error_for_status()?;
-        resp.json::<TwapsResponse>().await
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<TwapsResponse, HermesError> {
+        self.latest_twaps(window_seconds).ids(ids).send().await
     }
 
     /// Gets the most recent publisher stake caps update data
     pub async fn get_latest_publisher_stake_caps(
         &self,
-    ) -> Result<LatestPublisherStakeCapsUpdateDataResponse, Error> {
-        let url = format!("{}/v2/updates/publisher_stake_caps/latest", self.base_url);
-        let resp = self.http.get(&url).send().await?.error_for_status()?;
-        resp.json::<LatestPublisherStakeCapsUpdateDataResponse>()
-            .await
+    ) -> Result<LatestPublisherStakeCapsUpdateDataResponse, HermesError> {
+        let url = self.url("/v2/updates/publisher_stake_caps/latest")?;
+        Ok(self.send(self.http.get(url)).await?.json().await?)
     }
-    /// Spawns a task which streams price updates from the hermes api
+    /// Spawns a task which streams price updates from the hermes api, invoking `on_event` for each update
+    ///
+    /// Kept for compatibility with existing callers: the hex `ids` are parsed as with [`FeedId::parse_all`], and the
+    /// stream keeps running in the background when the returned handle is dropped.
     ///
     /// # Returns
     ///
-    /// [`JoinHandle`] which can be used to abort the spawned task
+    /// [`JoinHandle`](rt::TaskHandle) which can be used to abort the spawned task, stopping the stream
+    #[deprecated(
+        since = "0.0.9",
+        note = "use `subscribe`, or `stream_price_updates_with_options` to keep a callback"
+    )]
     pub async fn stream_price_updates<F>(
         &self,
         ids: Vec<String>,
-        mut on_event: F,
-    ) -> Result<JoinHandle<()>, Error>
+        on_event: F,
+    ) -> Result<rt::TaskHandle, HermesError>
     where
         F: FnMut(ParsedPriceUpdate) + Send + 'static,
     {
-        let base_url = self.base_url.clone();
-        let client = self.http.clone();
-
-        let handler = tokio::spawn(async move {
-            loop {
-                let url = format!("{}/v2/updates/price/stream", base_url);
-                let mut req = client.get(&url);
-                for id in &ids {
-                    req = req.query(&[("ids[]", id)]);
+        let ids = FeedId::parse_all(&ids)?;
+        let handle = self
+            .stream_price_updates_with_options(&ids, StreamOptions::default(), on_event)
+            .await?;
+        let stop = handle.cancellation_token().drop_guard();
+        let finished = handle.finished();
+        handle.detach();
+        Ok(rt::spawn(async move {
+            // aborting the task drops the guard, stopping the stream
+            let _stop = stop;
+            finished.cancelled().await;
+        }))
+    }
+
+    /// Sends a request with the client's options and correlation id
+    async fn send(&self, req: RequestBuilder) -> Result<Response, HermesError> {
+        let mut req = self.request_options.apply(req)?;
+        let Some((header, correlation_id)) = self.next_correlation_id() else {
+            return self.send_with_retries(req).await;
+        };
+        req = req.header(&*header, &correlation_id);
+        self.send_with_retries(req)
+            .await
+            .map_err(|err| HermesError::Correlated {
+                correlation_id,
+                source: Box::new(err),
+            })
+    }
+
+    /// Sends a request, retrying transient failures according to the client's retry policy
+    async fn send_with_retries(&self, mut req: RequestBuilder) -> Result<Response, HermesError> {
+        let Some(policy) = self.retry_policy else {
+            return self.send_failover(req).await;
+        };
+        let mut attempt = 1;
+        loop {
+            let retry = req.try_clone().filter(|_| attempt < policy.max_attempts);
+            let result = self.send_failover(req).await;
+            match retry {
+                Some(next) if result.as_ref().is_err_and(RetryPolicy::is_transient) => {
+                    let delay = match result {
+                        // waiting longer than the policy allows is left to the caller
+                        Err(HermesError::RateLimited {
+                            retry_after: Some(retry_after),
+                        }) if retry_after > policy.max_delay => return result,
+                        Err(HermesError::RateLimited {
+                            retry_after: Some(retry_after),
+                        }) => retry_after,
+                        _ => policy.delay_with(attempt, &self.rng),
+                    };
+                    self.timer.sleep(delay).await;
+                    req = next;
+                    attempt += 1;
                 }
+                _ => return result,
+            }
+        }
+    }
 
-                let mut es = match EventSource::new(req) {
-                    Ok(stream) => stream,
-                    Err(err) => {
-                        log::error!("failed to connect SSE {err:#?}");
-                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                        continue;
-                    }
+    /// Base urls in the order they are tried
+    fn endpoints(&self) -> impl Iterator<Item = &Arc<str>> {
+        std::iter::once(&self.base_url).chain(self.fallback_urls.iter())
+    }
+
+    /// Url of `path` under the base url, see [`endpoint_url`]
+    fn url(&self, path: &str) -> Result<Url, HermesError> {
+        endpoint_url(&self.base_url, path)
+    }
+
+    /// Sends a request to each endpoint in turn until one doesn't fail with a transient error
+    ///
+    /// When hedging, the request is sent to the first two endpoints concurrently and the first successful response
+    /// is used, with the remaining endpoints tried in turn if both fail.
+    async fn send_failover(&self, req: RequestBuilder) -> Result<Response, HermesError> {
+        if self.fallback_urls.is_empty() {
+            return self.send_once(req).await;
+        }
+        let (client, request) = req.build_split();
+        let request = request?;
+        // the request's path and query relative to the base url, resolved against each endpoint in turn
+        let base = self.url("")?;
+        let path = request.url().path();
+        let path = path.strip_prefix(base.path()).unwrap_or(path).to_string();
+        let query = request.url().query().unwrap_or_default();
+        let query = base
+            .query()
+            .and_then(|base| query.strip_prefix(base))
+            .unwrap_or(query)
+            .trim_start_matches('&')
+            .to_string();
+        let mut requests = Vec::new();
+        for endpoint in self.endpoints() {
+            let mut url = endpoint_url(endpoint, &path)?;
+            if !query.is_empty() {
+                let query = match url.query() {
+                    Some(own) if !own.is_empty() => format!("{own}&{query}"),
+                    _ => query.clone(),
                 };
+                url.set_query(Some(&query));
+            }
+            let Some(mut request) = request.try_clone() else {
+                // requests with streaming bodies can only be sent once
+                return self
+                    .send_once(RequestBuilder::from_parts(client, request))
+                    .await;
+            };
+            *request.url_mut() = url;
+            requests.push((
+                endpoint,
+                RequestBuilder::from_parts(client.clone(), request),
+            ));
+        }
 
-                while let Some(event) = es.next().await {
-                    match event {
-                        Ok(Event::Message(msg)) => {
-                            if let Ok(update) = serde_json::from_str::<PriceUpdate>(&msg.data) {
-                                if let Some(parsed) = update.parsed {
-                                    for item in parsed {
-                                        if let Some(metadata) = item.metadata.clone() {
-                                            let parsed_update = ParsedPriceUpdate {
-                                                id: item.id,
-                                                price: item.price,
-                                                ema_price: item.ema_price,
-                                                metadata,
-                                            };
-                                            on_event(parsed_update);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Ok(Event::Open) => {
-                            // Connection established
-                        }
-                        Err(EventSourceError::StreamEnded) => {
-                            log::error!("stream ended, reconnecting");
-                            break;
-                        }
-                        Err(err) => {
-                            log::error!("sse error {err:#?}");
-                            break;
-                        }
-                    }
+        let mut requests = requests.into_iter();
+        let mut result = None;
+        if self.request_options.hedge {
+            let hedged: Vec<_> = requests
+                .by_ref()
+                .take(2)
+                .map(|(_, req)| Box::pin(self.send_once(req)))
+                .collect();
+            // the slower request is cancelled when the remaining futures are dropped
+            match select_ok(hedged).await {
+                Ok((resp, _)) => return Ok(resp),
+                Err(err) if RetryPolicy::is_transient(&err) => {
+                    log::warn!("hedged requests failed, trying next endpoint: {err}");
+                    result = Some(Err(err));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        for (endpoint, req) in requests {
+            let attempt = self.send_once(req).await;
+            match &attempt {
+                Err(err) if RetryPolicy::is_transient(err) => {
+                    log::warn!("request to {endpoint} failed, trying next endpoint: {err}");
+                    result = Some(attempt);
                 }
+                _ => return attempt,
             }
-        });
+        }
+        result.expect("at least one endpoint is tried")
+    }
+
+    /// Sends a single attempt of a request, unless the circuit breaker is open
+    async fn send_once(&self, req: RequestBuilder) -> Result<Response, HermesError> {
+        let Some(breaker) = &self.circuit_breaker else {
+            return self.exchange(req).await;
+        };
+        breaker.check()?;
+        let result = self.exchange(req).await;
+        breaker.record(&result);
+        result
+    }
 
-        Ok(handler)
+    /// Sends a request, refreshing the token once if it is rejected
+    async fn exchange(&self, req: RequestBuilder) -> Result<Response, HermesError> {
+        let retry = req.try_clone().filter(|_| self.auth.is_some());
+        let mut resp = self.prepare(req, false).await?.send().await?;
+        self.intercept_response(&resp);
+        if let (StatusCode::UNAUTHORIZED, Some(retry)) = (resp.status(), retry) {
+            resp = self.prepare(retry, true).await?.send().await?;
+            self.intercept_response(&resp);
+        }
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(retry::parse_retry_after);
+            return Err(HermesError::RateLimited { retry_after });
+        }
+        Ok(resp.error_for_status()?)
     }
+
+    /// Waits for the rate limiter, then attaches credentials to the request, runs interceptors and signs it
+    async fn prepare(
+        &self,
+        mut req: RequestBuilder,
+        refresh_token: bool,
+    ) -> Result<RequestBuilder, HermesError> {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        if let Some(auth) = &self.auth {
+            let token = if refresh_token {
+                auth.refresh_token().await
+            } else {
+                auth.get_token().await
+            }
+            .map_err(HermesError::Auth)?;
+            req = req.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        if let Some(api_key) = &self.api_key {
+            req = api_key.apply(req);
+        }
+        if self.signer.is_some() || !self.interceptors.is_empty() {
+            let (client, request) = req.build_split();
+            let mut request = request?;
+            for interceptor in self.interceptors.iter() {
+                interceptor.on_request(&mut request);
+            }
+            if let Some(signer) = &self.signer {
+                signer.sign(&mut request).map_err(HermesError::Signing)?;
+            }
+            req = RequestBuilder::from_parts(client, request);
+        }
+        Ok(req)
+    }
+
+    fn intercept_response(&self, resp: &Response) {
+        for interceptor in self.interceptors.iter() {
+            interceptor.on_response(resp);
+        }
+    }
+}
+
+/// Url of `path` under `endpoint`, keeping the endpoint's path prefix and query
+///
+/// The endpoint is treated as a directory, so `https://host/hermes` and `https://host/hermes/` both resolve
+/// `/v2/price_feeds` to `https://host/hermes/v2/price_feeds`, where joining them as strings or with plain
+/// [`Url::join`] would double the slash or drop the prefix.
+pub(crate) fn endpoint_url(endpoint: &str, path: &str) -> Result<Url, HermesError> {
+    let invalid = |err| HermesError::InvalidUrl(format!("{endpoint}: {err}"));
+    let mut base = Url::parse(endpoint).map_err(invalid)?;
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let mut url = base.join(path.trim_start_matches('/')).map_err(invalid)?;
+    // joining replaces the query, keep one configured on the endpoint such as an api key
+    url.set_query(base.query());
+    Ok(url)
 }
 
 #[cfg(test)]
 mod test {
-    use super::{types::PUBLIC_BASE_URL, *};
-
-    const ETH_USD_FEED_ID: &str =
-        "ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
-    const SOL_USD_FEED_ID: &str =
-        "ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+    use {
+        super::{
+            feed_ids::{ETH_USD, SOL_USD},
+            types::PUBLIC_BASE_URL,
+            *,
+        },
+        mock::{price_update_json, MockHermes, RecordedResponse},
+    };
 
     #[tokio::test(flavor = "multi_thread")]
     async fn test_stream_price_updates_live() {
         let client = HermesClient::new(PUBLIC_BASE_URL);
-        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-
-        let handler = client
-            .stream_price_updates(
-                vec![ETH_USD_FEED_ID.to_string(), SOL_USD_FEED_ID.to_string()],
-                move |update| {
-                    let _ = tx.send(update);
-                },
-            )
+        let (mut rx, handler) = client
+            .subscribe(&[ETH_USD, SOL_USD])
             .await
-            .expect("Failed to start SSE stream");
+            .expect("Failed to start SSE stream")
+            .into_parts();
         let mut found_eth_feed = false;
         let mut found_sol_feed = false;
         let mut timer = tokio::time::interval(std::time::Duration::from_secs(20));
@@ -209,10 +593,10 @@ mod test {
                 result = rx.recv() => {
                     if let Some(update) = result {
                         println!("update {update:#?}");
-                        if update.id.contains(ETH_USD_FEED_ID) {
+                        if update.id == ETH_USD {
                             found_eth_feed = true;
                         }
-                        if update.id.contains(SOL_USD_FEED_ID) {
+                        if update.id == SOL_USD {
                             found_sol_feed = true;
                         }
                         if found_eth_feed && found_sol_feed {
@@ -237,20 +621,230 @@ mod test {
     async fn test_latest_price_feeds() {
         let hc = HermesClient::new(PUBLIC_BASE_URL);
 
-        let _ = hc.get_latest_price_feeds(&[ETH_USD_FEED_ID]).await.unwrap();
+        let _ = hc.get_latest_price_feeds(&[ETH_USD]).await.unwrap();
     }
 
     #[tokio::test]
     async fn test_get_latest_price_feeds_live() {
         let client = HermesClient::new(PUBLIC_BASE_URL);
-        let result = client
-            .get_latest_price_feeds(&[ETH_USD_FEED_ID])
+        let result = client.get_latest_price_feeds(&[ETH_USD]).await.unwrap();
+        assert!(!result.is_empty());
+        assert_eq!(result[0].id, ETH_USD);
+    }
+
+    #[tokio::test]
+    async fn test_get_feeds_with_prices() {
+        let metadata: Vec<_> = (0..=100)
+            .map(|i| serde_json::json!({"id": FeedId::from_bytes([i; 32]), "attributes": {}}))
+            .collect();
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json("/v2/price_feeds", serde_json::Value::Array(metadata)),
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[FeedId::from_bytes([1; 32])], 42, 1),
+            ),
+        ])
+        .await
+        .unwrap();
+
+        let feeds = HermesClient::builder(mock.url())
+            .chunking(50, 2)
+            .build()
+            .unwrap()
+            .get_feeds_with_prices(None, Some(AssetType::Crypto))
             .await
             .unwrap();
-        assert!(!result.is_empty());
+        assert_eq!(feeds.len(), 101);
+        assert!(feeds[0].price.is_none());
+        assert_eq!(feeds[1].price.as_ref().unwrap().price.price, "42");
+
+        let requests = mock.requests().await;
+        assert_eq!(requests[0].query.as_deref(), Some("asset_type=crypto"));
+        // 101 feeds in chunks of 50
+        assert_eq!(requests.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let primary = MockHermes::start(vec![
+            RecordedResponse::status("/v2/price_feeds", 502),
+            RecordedResponse::json("/v2/price_feeds", serde_json::json!([])),
+            RecordedResponse::status("/v2/updates/publisher_stake_caps/latest", 404),
+        ])
+        .await
+        .unwrap();
+        let fallback = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/price_feeds",
+            serde_json::json!([{"id": ETH_USD, "attributes": {}}]),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(primary.url())
+            .fallback_url(fallback.url())
+            .build()
+            .unwrap();
+
+        let metadata = client.get_price_feeds_metadata(None, None).await.unwrap();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(fallback.requests().await[0].path, "/v2/price_feeds");
+        // the primary is tried first on every request
+        let metadata = client.get_price_feeds_metadata(None, None).await.unwrap();
+        assert!(metadata.is_empty());
+        // non transient errors don't fail over
+        assert!(client.get_latest_publisher_stake_caps().await.is_err());
+        assert_eq!(fallback.requests().await.len(), 1);
+    }
+
+    #[test]
+    fn test_endpoint_url() {
+        for base in ["https://host/hermes", "https://host/hermes/"] {
+            assert_eq!(
+                endpoint_url(base, "/v2/price_feeds").unwrap().as_str(),
+                "https://host/hermes/v2/price_feeds"
+            );
+        }
+        assert_eq!(
+            endpoint_url("https://host", "/live").unwrap().as_str(),
+            "https://host/live"
+        );
         assert_eq!(
-            result[0].id.to_lowercase(),
-            ETH_USD_FEED_ID.trim_start_matches("0x").to_lowercase()
+            endpoint_url("https://host/hermes?key=1", "v2/price_feeds")
+                .unwrap()
+                .as_str(),
+            "https://host/hermes/v2/price_feeds?key=1"
+        );
+        assert!(matches!(
+            endpoint_url("host/hermes", "/live"),
+            Err(HermesError::InvalidUrl(_))
+        ));
+    }
+
+    /// Every endpoint, including the stream, is requested under the base url's path prefix
+    #[tokio::test]
+    async fn test_prefixed_base_url() {
+        for prefix in ["/hermes", "/hermes/"] {
+            let mock = MockHermes::start(vec![RecordedResponse::events(
+                "/hermes/v2/updates/price/stream",
+                vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+            )])
+            .await
+            .unwrap();
+            let client = HermesClient::new(format!("{}{prefix}", mock.url()));
+            let ids = [ETH_USD];
+            // the mock only serves the stream, responses to the other requests don't matter
+            let _ = client.get_price_feeds_metadata(None, None).await;
+            let _ = client.get_price_feeds_metadata_cached(None, None).await;
+            let _ = client.get_latest_price_updates(&ids).await;
+            let _ = client
+                .get_price_updates_by_time(1, &ids, &PriceUpdatesByTimeOptions::default())
+                .await;
+            let _ = client.get_latest_twaps(300, &ids).await;
+            let _ = client.get_latest_publisher_stake_caps().await;
+            let _ = client.is_live().await;
+            let _ = client.is_ready().await;
+            let mut subscription = client.subscribe(&ids).await.unwrap();
+            subscription.next().await.unwrap();
+            subscription.handle().abort();
+
+            let paths: Vec<_> = mock
+                .requests()
+                .await
+                .into_iter()
+                .map(|request| request.path)
+                .collect();
+            assert_eq!(
+                paths[..9],
+                [
+                    "/hermes/v2/price_feeds",
+                    "/hermes/v2/price_feeds",
+                    "/hermes/v2/updates/price/latest",
+                    "/hermes/v2/updates/price/1",
+                    "/hermes/v2/updates/twap/300/latest",
+                    "/hermes/v2/updates/publisher_stake_caps/latest",
+                    "/hermes/live",
+                    "/hermes/ready",
+                    "/hermes/v2/updates/price/stream",
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_failover() {
+        let primary = MockHermes::start(vec![RecordedResponse::status(
+            "/hermes/v2/price_feeds",
+            502,
+        )])
+        .await
+        .unwrap();
+        let fallback = MockHermes::start(vec![RecordedResponse::json(
+            "/mirror/hermes/v2/price_feeds",
+            serde_json::json!([]),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(format!("{}/hermes?key=1", primary.url()))
+            .fallback_url(format!("{}/mirror/hermes/", fallback.url()))
+            .build()
+            .unwrap();
+
+        let metadata = client
+            .get_price_feeds_metadata(Some("btc"), None)
+            .await
+            .unwrap();
+        assert!(metadata.is_empty());
+        let primary = &primary.requests().await[0];
+        assert_eq!(primary.query.as_deref(), Some("key=1&query=btc"));
+        // the base url's query belongs to the primary, only the request's own query is sent to the fallback
+        let fallback = &fallback.requests().await[0];
+        assert_eq!(fallback.path, "/mirror/hermes/v2/price_feeds");
+        assert_eq!(fallback.query.as_deref(), Some("query=btc"));
+    }
+
+    #[tokio::test]
+    async fn test_hedged_requests() {
+        let slow = crate::request::test::unresponsive_server().await;
+        let fast = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD], 1, 1),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(slow)
+            .fallback_url(fast.url())
+            .hedged(true)
+            .build()
+            .unwrap();
+
+        let feeds = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            client.get_latest_price_feeds(&[ETH_USD]),
+        )
+        .await
+        .expect("hedged request should not wait for the slow endpoint")
+        .unwrap();
+        assert_eq!(feeds.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_price_updates() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD, SOL_USD], 1, 1),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        let update = client
+            .get_latest_price_updates(&[ETH_USD, SOL_USD])
+            .await
+            .unwrap();
+        assert_eq!(update.binary.data, vec!["504e4155"]);
+        assert_eq!(update.parsed.unwrap().len(), 2);
+        let requests = mock.requests().await;
+        assert_eq!(
+            requests[0].query.as_deref(),
+            Some(format!("ids%5B%5D={ETH_USD}&ids%5B%5D={SOL_USD}").as_str())
         );
     }
 
@@ -265,7 +859,7 @@ mod test {
     async fn test_get_price_feeds_metadata_live() {
         let client = HermesClient::new(PUBLIC_BASE_URL);
         let metadata = client
-            .get_price_feeds_metadata(Some("bitcoin"), None)
+            .get_price_feeds_metadata(Some("bitcoin"), Some(AssetType::Crypto))
             .await
             .unwrap();
         assert!(!metadata.is_empty());
@@ -282,15 +876,30 @@ mod test {
     async fn test_get_price_updates_by_time_live() {
         let client = HermesClient::new(PUBLIC_BASE_URL);
         let result = client
-            .get_price_updates_by_time(1717632000, &[ETH_USD_FEED_ID])
+            .get_price_updates_by_time(
+                1717632000,
+                &[ETH_USD],
+                &PriceUpdatesByTimeOptions::default(),
+            )
+            .await;
+        assert!(result.is_ok() || matches!(result, Err(HermesError::Http(_))));
+        let result = client
+            .get_price_updates_by_time(
+                1717632000,
+                &[ETH_USD],
+                &PriceUpdatesByTimeOptions {
+                    benchmarks_only: Some(true),
+                    allow_unordered: Some(false),
+                },
+            )
             .await;
-        assert!(result.is_ok() || matches!(result, Err(reqwest::Error { .. })));
+        assert!(result.is_ok() || matches!(result, Err(HermesError::Http(_))));
     }
 
     #[tokio::test]
     async fn test_get_latest_twaps_live() {
         let client = HermesClient::new(PUBLIC_BASE_URL);
-        let result = client.get_latest_twaps(300, &[ETH_USD_FEED_ID]).await;
-        assert!(result.is_ok() || matches!(result, Err(reqwest::Error { .. })));
+        let result = client.get_latest_twaps(300, &[ETH_USD]).await;
+        assert!(result.is_ok() || matches!(result, Err(HermesError::Http(_))));
     }
 }
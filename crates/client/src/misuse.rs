@@ -0,0 +1,139 @@
+//! Catching integration mistakes, such as requesting no feeds, before anything is sent to hermes
+//!
+//! By default the client is lenient: duplicate ids are dropped and other requests are sent as given, leaving hermes
+//! to respond however it does. With [`MisusePolicy::Strict`] mistakes panic with a clear message in builds with
+//! debug assertions, surfacing them during development, while release builds return [`HermesError::Misuse`] rather
+//! than crashing in production.
+
+use {
+    crate::{error::HermesError, types::FeedId},
+    std::collections::HashSet,
+};
+
+/// Longest TWAP window hermes calculates, in seconds
+pub const MAX_TWAP_WINDOW_SECONDS: u64 = 600;
+
+/// How the client treats misuse, see
+/// [`HermesClientBuilder::misuse_policy`](crate::builder::HermesClientBuilder::misuse_policy)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MisusePolicy {
+    /// Requests are sent as given, with duplicate ids dropped
+    #[default]
+    Lenient,
+    /// Panics with debug assertions enabled, fails with [`HermesError::Misuse`] otherwise
+    Strict,
+}
+
+/// A mistake in how the client was called
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Misuse {
+    #[error("no feed ids given")]
+    NoFeedIds,
+    #[error("feed ids given more than once: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    DuplicateFeedIds(Vec<FeedId>),
+    #[error("twap window of {0}s is outside hermes' range of 1 to {MAX_TWAP_WINDOW_SECONDS}s")]
+    TwapWindow(u64),
+}
+
+impl MisusePolicy {
+    /// Checks a list of ids to request or subscribe to
+    pub fn check_ids(self, ids: &[impl AsRef<FeedId>]) -> Result<(), HermesError> {
+        if ids.is_empty() {
+            return self.report(Misuse::NoFeedIds);
+        }
+        let mut seen = HashSet::new();
+        // each duplicate is listed once, in the order it was first repeated
+        let mut reported = HashSet::new();
+        let duplicates: Vec<FeedId> = ids
+            .iter()
+            .map(|id| *id.as_ref())
+            .filter(|id| !seen.insert(*id) && reported.insert(*id))
+            .collect();
+        if duplicates.is_empty() {
+            return Ok(());
+        }
+        self.report(Misuse::DuplicateFeedIds(duplicates))
+    }
+
+    /// Checks the window of a TWAP request
+    pub fn check_twap_window(self, window_seconds: u64) -> Result<(), HermesError> {
+        if (1..=MAX_TWAP_WINDOW_SECONDS).contains(&window_seconds) {
+            return Ok(());
+        }
+        self.report(Misuse::TwapWindow(window_seconds))
+    }
+
+    fn report(self, misuse: Misuse) -> Result<(), HermesError> {
+        match self {
+            Self::Lenient => Ok(()),
+            Self::Strict if cfg!(debug_assertions) => {
+                panic!("{misuse} (reported by MisusePolicy::Strict)")
+            }
+            Self::Strict => Err(HermesError::Misuse(misuse)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::{BTC_USD, ETH_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+            HermesClient,
+        },
+    };
+
+    #[test]
+    fn test_lenient() {
+        let policy = MisusePolicy::Lenient;
+        assert!(policy.check_ids(&[] as &[FeedId]).is_ok());
+        assert!(policy.check_ids(&[ETH_USD, ETH_USD]).is_ok());
+        assert!(policy.check_twap_window(0).is_ok());
+    }
+
+    #[test]
+    fn test_strict() {
+        let policy = MisusePolicy::Strict;
+        assert!(policy.check_ids(&[ETH_USD]).is_ok());
+        assert!(policy.check_twap_window(MAX_TWAP_WINDOW_SECONDS).is_ok());
+        // tests are built with debug assertions
+        let panic = std::panic::catch_unwind(|| policy.check_twap_window(3600)).unwrap_err();
+        assert_eq!(
+            panic.downcast_ref::<String>().unwrap(),
+            "twap window of 3600s is outside hermes' range of 1 to 600s (reported by MisusePolicy::Strict)"
+        );
+        assert!(std::panic::catch_unwind(|| policy.check_ids(&[] as &[FeedId])).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_feed_ids() {
+        let misuse = Misuse::DuplicateFeedIds(vec![ETH_USD, BTC_USD]);
+        let panic = std::panic::catch_unwind(|| {
+            MisusePolicy::Strict.check_ids(&[ETH_USD, BTC_USD, ETH_USD, BTC_USD, ETH_USD])
+        })
+        .unwrap_err();
+        assert_eq!(
+            panic.downcast_ref::<String>().unwrap(),
+            &format!("{misuse} (reported by MisusePolicy::Strict)")
+        );
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "feed ids given more than once")]
+    async fn test_strict_client() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD], 1, 1),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .misuse_policy(MisusePolicy::Strict)
+            .build()
+            .unwrap();
+        assert!(client.get_latest_price_feeds(&[ETH_USD]).await.is_ok());
+        let _ = client.get_latest_price_feeds(&[ETH_USD, ETH_USD]).await;
+    }
+}
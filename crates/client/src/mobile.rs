@@ -0,0 +1,191 @@
+//! Bindings for Kotlin and Swift apps, generated with uniffi
+//!
+//! Built into the crate's cdylib when the `uniffi` feature is enabled. Generate the foreign code from the built
+//! library with the `uniffi-bindgen` cli matching the crate's uniffi version:
+//!
+//! ```text
+//! cargo build --release --features uniffi
+//! uniffi-bindgen generate --library target/release/libpyth_hermes_rs.so --language kotlin --out-dir out
+//! ```
+//!
+//! Requests block the calling thread, so apps should call them off the main thread. Subscriptions use the same
+//! streaming engine as the rust client, reconnecting with backoff, and invoke their listener from a background
+//! thread.
+
+use {
+    crate::{blocking, stream::StreamHandle, types::*},
+    std::sync::Arc,
+};
+
+/// Error raised to the app
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum HermesError {
+    #[error("invalid argument: {message}")]
+    InvalidArgument { message: String },
+    #[error("request failed: {message}")]
+    Request { message: String },
+}
+
+/// Price of a feed, where the value is `price * 10^expo`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct Price {
+    /// Hex encoded feed id
+    pub id: String,
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    /// Unix timestamp in seconds
+    pub publish_time: i64,
+}
+
+/// Receives the updates of a [`Subscription`], implemented by the app
+#[uniffi::export(with_foreign)]
+pub trait PriceListener: Send + Sync {
+    fn on_price(&self, price: Price);
+}
+
+/// Client for the Pyth Hermes API
+#[derive(uniffi::Object)]
+pub struct HermesClient(blocking::HermesClient);
+
+/// Stream of price updates, stopped when closed or released by the app
+#[derive(uniffi::Object)]
+pub struct Subscription(StreamHandle);
+
+#[uniffi::export]
+impl HermesClient {
+    #[uniffi::constructor]
+    pub fn new(base_url: String) -> Result<Arc<Self>, HermesError> {
+        let client = crate::HermesClient::builder(base_url)
+            .build()
+            .map_err(|err| HermesError::InvalidArgument {
+                message: err.to_string(),
+            })?;
+        Ok(Arc::new(Self(client.into())))
+    }
+
+    /// Latest prices of the feeds with the hex `ids`
+    pub fn latest_prices(&self, ids: Vec<String>) -> Result<Vec<Price>, HermesError> {
+        let ids = parse_ids(&ids)?;
+        let feeds = self.0.get_latest_price_feeds(&ids).map_err(request_error)?;
+        Ok(feeds
+            .iter()
+            .filter_map(|feed| to_price(feed.id, &feed.price))
+            .collect())
+    }
+
+    /// Streams price updates for the hex `ids` to `listener`
+    pub fn subscribe(
+        &self,
+        ids: Vec<String>,
+        listener: Arc<dyn PriceListener>,
+    ) -> Result<Arc<Subscription>, HermesError> {
+        let ids = parse_ids(&ids)?;
+        let handle = self
+            .0
+            .stream_price_updates(&ids, move |update: ParsedPriceUpdate| {
+                if let Some(price) = to_price(update.id, &update.price) {
+                    listener.on_price(price);
+                }
+            })
+            .map_err(request_error)?;
+        Ok(Arc::new(Subscription(handle)))
+    }
+}
+
+#[uniffi::export]
+impl Subscription {
+    /// Stops the stream
+    pub fn close(&self) {
+        self.0.abort();
+    }
+
+    /// Number of messages received from hermes
+    pub fn messages_received(&self) -> u64 {
+        self.0.stats().messages_received()
+    }
+
+    /// Number of times the stream reconnected to hermes
+    pub fn reconnects(&self) -> u64 {
+        self.0.stats().reconnects()
+    }
+}
+
+fn parse_ids(ids: &[String]) -> Result<Vec<FeedId>, HermesError> {
+    FeedId::parse_all(ids).map_err(|err| HermesError::InvalidArgument {
+        message: err.to_string(),
+    })
+}
+
+fn request_error(err: crate::error::HermesError) -> HermesError {
+    HermesError::Request {
+        message: err.to_string(),
+    }
+}
+
+fn to_price(id: FeedId, price: &RpcPrice) -> Option<Price> {
+    Some(Price {
+        id: id.to_hex(),
+        price: price.price.parse().ok()?,
+        conf: price.conf.parse().ok()?,
+        expo: price.expo,
+        publish_time: price.publish_time,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+        std::sync::{mpsc, Mutex},
+    };
+
+    struct ChannelListener(Mutex<mpsc::Sender<Price>>);
+
+    impl PriceListener for ChannelListener {
+        fn on_price(&self, price: Price) {
+            let _ = self.0.lock().unwrap().send(price);
+        }
+    }
+
+    #[test]
+    fn test_mobile_client() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mock = runtime
+            .block_on(MockHermes::start(vec![
+                RecordedResponse::json(
+                    "/v2/updates/price/latest",
+                    price_update_json(&[ETH_USD], 42, 7),
+                ),
+                RecordedResponse::events(
+                    "/v2/updates/price/stream",
+                    vec![price_update_json(&[ETH_USD], 43, 8).to_string()],
+                ),
+            ]))
+            .unwrap();
+        let client = HermesClient::new(mock.url()).unwrap();
+
+        let prices = client.latest_prices(vec![ETH_USD.to_hex()]).unwrap();
+        assert_eq!(prices[0].id, ETH_USD.to_hex());
+        assert_eq!((prices[0].price, prices[0].expo), (42, -8));
+        assert!(matches!(
+            client.latest_prices(vec!["eth".to_string()]),
+            Err(HermesError::InvalidArgument { .. })
+        ));
+
+        let (sender, receiver) = mpsc::channel();
+        let subscription = client
+            .subscribe(
+                vec![ETH_USD.to_hex()],
+                Arc::new(ChannelListener(Mutex::new(sender))),
+            )
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap().publish_time, 8);
+        assert!(subscription.messages_received() >= 1);
+        subscription.close();
+    }
+}
@@ -0,0 +1,453 @@
+//! Minimal mock of the Hermes API, used to reproduce issues against recorded responses
+//!
+//! A corpus is a JSON array of [`RecordedResponse`] entries. Loading a capture and pointing a client at it
+//! is a one-liner:
+//!
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! let mock = pyth_hermes_rs::mock::MockHermes::replay("incident.json").await?;
+//! let client = pyth_hermes_rs::HermesClient::new(mock.url());
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{rng::Rng, types::FeedId},
+    serde::{Deserialize, Serialize},
+    std::{collections::HashMap, net::SocketAddr, path::Path, sync::Arc, time::Duration},
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+        task::JoinHandle,
+    },
+};
+
+/// A single recorded response served by [`MockHermes`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// Request path the response is served for, excluding the query string
+    pub path: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    /// Additional response headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// JSON response body
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+    /// Exact response body, takes precedence over `body` and allows replaying malformed payloads
+    #[serde(default)]
+    pub raw_body: Option<String>,
+    /// SSE `data` payloads, when set the response is served as an event stream
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// SSE event ids, sent alongside the event at the same index
+    #[serde(default)]
+    pub event_ids: Vec<String>,
+}
+
+/// A request received by [`MockHermes`]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// Header names are lowercased
+    pub headers: HashMap<String, String>,
+}
+
+/// Degraded network conditions simulated by [`MockHermes`], see [`MockHermes::set_conditions`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkConditions {
+    /// Delay before each response and between SSE events
+    pub latency: Duration,
+    /// Upper bound of a uniformly distributed delay added to `latency`
+    pub jitter: Duration,
+    /// Probability in `[0, 1]` that a request's connection is closed without a response
+    pub drop_rate: f64,
+    /// Closes SSE connections after this many events, even if more remain
+    pub disconnect_after_events: Option<usize>,
+    /// Seed for the jitter and dropped connections, making them reproducible, random when `None`
+    pub seed: Option<u64>,
+}
+
+impl NetworkConditions {
+    fn delay(&self, rng: &Rng) -> Duration {
+        self.latency + self.jitter.mul_f64(rng.fraction())
+    }
+
+    fn drop(&self, rng: &Rng) -> bool {
+        self.drop_rate > 0.0 && rng.fraction() < self.drop_rate
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Responses are served in order per path, with the last one repeating
+    routes: HashMap<String, Vec<RecordedResponse>>,
+    served: HashMap<String, usize>,
+    requests: Vec<RecordedRequest>,
+    conditions: NetworkConditions,
+    /// Source of the conditions' randomness, reseeded when they are set
+    rng: Option<Rng>,
+    /// Connections accepted so far
+    connections: usize,
+    /// Whether connections stay open for further requests after JSON responses
+    keep_alive: bool,
+}
+
+/// Mock Hermes server listening on a random local port
+pub struct MockHermes {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    handle: JoinHandle<()>,
+}
+
+/// Price update payload for `ids` in the format returned by hermes, with every price set to `price`
+pub fn price_update_json(ids: &[FeedId], price: i64, publish_time: i64) -> serde_json::Value {
+    let rpc_price = serde_json::json!({
+        "price": price.to_string(),
+        "conf": "100",
+        "expo": -8,
+        "publish_time": publish_time,
+    });
+    let parsed: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            serde_json::json!({
+                "id": id,
+                "price": rpc_price,
+                "ema_price": rpc_price,
+                "metadata": {
+                    "slot": 1,
+                    "proof_available_time": publish_time,
+                    "prev_publish_time": publish_time - 1,
+                },
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "binary": {"encoding": "hex", "data": ["504e4155"]},
+        "parsed": parsed,
+    })
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl RecordedResponse {
+    /// Json response with a 200 status
+    pub fn json(path: impl Into<String>, body: serde_json::Value) -> Self {
+        Self {
+            path: path.into(),
+            status: default_status(),
+            headers: HashMap::new(),
+            body: Some(body),
+            raw_body: None,
+            events: Vec::new(),
+            event_ids: Vec::new(),
+        }
+    }
+
+    /// Empty response with the given status
+    pub fn status(path: impl Into<String>, status: u16) -> Self {
+        Self {
+            path: path.into(),
+            status,
+            headers: HashMap::new(),
+            body: None,
+            raw_body: None,
+            events: Vec::new(),
+            event_ids: Vec::new(),
+        }
+    }
+
+    /// SSE response which sends each event then closes the connection
+    pub fn events(path: impl Into<String>, events: Vec<String>) -> Self {
+        Self {
+            path: path.into(),
+            status: default_status(),
+            headers: HashMap::new(),
+            body: None,
+            raw_body: None,
+            events,
+            event_ids: Vec::new(),
+        }
+    }
+}
+
+impl MockHermes {
+    /// Starts a mock server serving the given responses
+    pub async fn start(corpus: Vec<RecordedResponse>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let mut state = State::default();
+        for response in corpus {
+            state
+                .routes
+                .entry(response.path.clone())
+                .or_default()
+                .push(response);
+        }
+        let state = Arc::new(Mutex::new(state));
+        let handle = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, state.clone()));
+                }
+            }
+        });
+        Ok(Self {
+            addr,
+            state,
+            handle,
+        })
+    }
+
+    /// Loads a recorded corpus from a JSON file and starts a mock server serving it
+    pub async fn replay(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let data = tokio::fs::read(path).await?;
+        let corpus: Vec<RecordedResponse> = serde_json::from_slice(&data)?;
+        Self::start(corpus).await
+    }
+
+    /// Base url to configure the client with
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Requests received so far
+    pub async fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().await.requests.clone()
+    }
+
+    /// Number of connections accepted so far
+    pub async fn connections(&self) -> usize {
+        self.state.lock().await.connections
+    }
+
+    /// Keeps connections open for further requests after JSON responses, as hermes does, rather than closing them
+    /// after every response
+    pub async fn set_keep_alive(&self, keep_alive: bool) {
+        self.state.lock().await.keep_alive = keep_alive;
+    }
+
+    /// Applies `conditions` to requests received from now on, allowing conditions to degrade during a test
+    pub async fn set_conditions(&self, conditions: NetworkConditions) {
+        let mut state = self.state.lock().await;
+        state.rng = conditions.seed.map(Rng::seeded);
+        state.conditions = conditions;
+    }
+}
+
+impl Drop for MockHermes {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    state.lock().await.connections += 1;
+    while let Some(request) = read_request(&mut stream).await {
+        if !respond(&mut stream, request, &state).await {
+            return;
+        }
+    }
+}
+
+/// Responds to `request`, returning whether the connection stays open for another
+async fn respond(stream: &mut TcpStream, request: RecordedRequest, state: &Mutex<State>) -> bool {
+    let (response, conditions, rng, keep_alive) = {
+        let mut state = state.lock().await;
+        state.requests.push(request.clone());
+        let served = state.served.get(&request.path).copied().unwrap_or(0);
+        let response = state
+            .routes
+            .get(&request.path)
+            .and_then(|responses| responses.get(served.min(responses.len() - 1)))
+            .cloned();
+        *state.served.entry(request.path.clone()).or_default() += 1;
+        let rng = state.rng.get_or_insert_with(Rng::from_entropy).clone();
+        (response, state.conditions.clone(), rng, state.keep_alive)
+    };
+    if conditions.drop(&rng) {
+        return false;
+    }
+    tokio::time::sleep(conditions.delay(&rng)).await;
+    let response = response.unwrap_or_else(|| RecordedResponse::status(request.path, 404));
+    // event streams end by closing the connection
+    let keep_alive = keep_alive && response.events.is_empty();
+    write_response(stream, &response, &conditions, &rng, keep_alive)
+        .await
+        .is_ok()
+        && keep_alive
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0_u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let mut target = request_line.next()?;
+    // requests sent through a proxy use the absolute form
+    if let Some((_, rest)) = target.split_once("://") {
+        target = rest.find('/').map_or("/", |i| &rest[i..]);
+    }
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    Some(RecordedRequest {
+        method,
+        path,
+        query,
+        headers,
+    })
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    response: &RecordedResponse,
+    conditions: &NetworkConditions,
+    rng: &Rng,
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nconnection: {connection}\r\n",
+        response.status
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if !response.events.is_empty() {
+        head.push_str("content-type: text/event-stream\r\ncache-control: no-cache\r\n\r\n");
+        stream.write_all(head.as_bytes()).await?;
+        let limit = conditions
+            .disconnect_after_events
+            .unwrap_or(response.events.len());
+        for (i, event) in response.events.iter().enumerate().take(limit) {
+            if i > 0 {
+                tokio::time::sleep(conditions.delay(rng)).await;
+            }
+            if let Some(id) = response.event_ids.get(i) {
+                stream.write_all(format!("id: {id}\n").as_bytes()).await?;
+            }
+            stream
+                .write_all(format!("data: {event}\n\n").as_bytes())
+                .await?;
+            stream.flush().await?;
+        }
+        return stream.shutdown().await;
+    }
+    let body = match (&response.raw_body, &response.body) {
+        (Some(raw), _) => raw.clone(),
+        (None, Some(body)) => body.to_string(),
+        (None, None) => String::new(),
+    };
+    head.push_str(&format!(
+        "content-type: application/json\r\ncontent-length: {}\r\n\r\n",
+        body.len()
+    ));
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    if keep_alive {
+        return stream.flush().await;
+    }
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{feed_ids::ETH_USD, types::FeedId, HermesClient},
+    };
+
+    #[tokio::test]
+    async fn test_replay_corpus() {
+        let corpus = serde_json::json!([
+            {
+                "path": "/v2/price_feeds",
+                "body": [{"id": format!("0x{}", ETH_USD.to_hex()), "attributes": {"symbol": "Crypto.BTC/USD"}}]
+            },
+            {
+                "path": "/v2/updates/publisher_stake_caps/latest",
+                "raw_body": "{\"binary\": "
+            }
+        ]);
+        let path = std::env::temp_dir().join(format!("hermes-corpus-{}.json", std::process::id()));
+        tokio::fs::write(&path, corpus.to_string()).await.unwrap();
+        let mock = MockHermes::replay(&path).await.unwrap();
+        let _ = tokio::fs::remove_file(&path).await;
+
+        let client = HermesClient::new(mock.url());
+        let metadata = client.get_price_feeds_metadata(None, None).await.unwrap();
+        assert_eq!(metadata[0].attributes["symbol"], "Crypto.BTC/USD");
+        assert!(client.get_latest_publisher_stake_caps().await.is_err());
+        assert!(client.get_latest_twaps(60, &[] as &[FeedId]).await.is_err());
+
+        let requests = mock.requests().await;
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].path, "/v2/price_feeds");
+    }
+
+    #[tokio::test]
+    async fn test_network_conditions() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json("/v2/price_feeds", serde_json::json!([])),
+            RecordedResponse::events(
+                "/v2/updates/price/stream",
+                (0..5).map(|i| i.to_string()).collect(),
+            ),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        mock.set_conditions(NetworkConditions {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .await;
+        let start = std::time::Instant::now();
+        client.get_price_feeds_metadata(None, None).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        mock.set_conditions(NetworkConditions {
+            drop_rate: 1.0,
+            ..Default::default()
+        })
+        .await;
+        assert!(client.get_price_feeds_metadata(None, None).await.is_err());
+
+        mock.set_conditions(NetworkConditions {
+            disconnect_after_events: Some(2),
+            ..Default::default()
+        })
+        .await;
+        let body = reqwest::get(format!("{}/v2/updates/price/stream", mock.url()))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "data: 0\n\ndata: 1\n\n");
+    }
+}
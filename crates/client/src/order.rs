@@ -0,0 +1,152 @@
+//! Latest prices returned in the order they were requested in
+//!
+//! Hermes doesn't guarantee the order of the feeds in its responses, and leaves out feeds it has no price for.
+//! [`HermesClient::get_latest_price_feeds_ordered`] marks the feeds left out, while
+//! [`HermesClient::get_latest_price_feeds_complete`] fails with [`HermesError::MissingFeeds`] listing them.
+
+use {
+    crate::{
+        error::HermesError,
+        types::{FeedId, RpcPriceFeed},
+        HermesClient,
+    },
+    std::collections::HashMap,
+};
+
+/// Price of a requested feed, or a marker for a feed missing from the response
+#[derive(Debug, Clone)]
+pub enum FeedSlot {
+    Found(Box<RpcPriceFeed>),
+    Missing(FeedId),
+}
+
+impl FeedSlot {
+    /// Id of the requested feed
+    pub fn id(&self) -> FeedId {
+        match self {
+            Self::Found(feed) => feed.id,
+            Self::Missing(id) => *id,
+        }
+    }
+
+    pub fn found(&self) -> Option<&RpcPriceFeed> {
+        match self {
+            Self::Found(feed) => Some(feed),
+            Self::Missing(_) => None,
+        }
+    }
+
+    pub fn into_found(self) -> Option<RpcPriceFeed> {
+        match self {
+            Self::Found(feed) => Some(*feed),
+            Self::Missing(_) => None,
+        }
+    }
+}
+
+/// Orders `feeds` to match `ids`, with a [`FeedSlot::Missing`] for each id not among them
+///
+/// Feeds which weren't requested are dropped, and ids requested more than once get a copy of their feed each.
+pub fn order_feeds(ids: &[impl AsRef<FeedId>], feeds: Vec<RpcPriceFeed>) -> Vec<FeedSlot> {
+    let feeds: HashMap<FeedId, RpcPriceFeed> =
+        feeds.into_iter().map(|feed| (feed.id, feed)).collect();
+    ids.iter()
+        .map(|id| match feeds.get(id.as_ref()) {
+            Some(feed) => FeedSlot::Found(Box::new(feed.clone())),
+            None => FeedSlot::Missing(*id.as_ref()),
+        })
+        .collect()
+}
+
+/// Unwraps every slot, failing with [`HermesError::MissingFeeds`] listing the missing feeds if there are any
+pub fn require_found(slots: Vec<FeedSlot>) -> Result<Vec<RpcPriceFeed>, HermesError> {
+    let missing: Vec<_> = slots
+        .iter()
+        .filter(|slot| slot.found().is_none())
+        .map(FeedSlot::id)
+        .collect();
+    if !missing.is_empty() {
+        return Err(HermesError::MissingFeeds(missing));
+    }
+    Ok(slots.into_iter().filter_map(FeedSlot::into_found).collect())
+}
+
+impl HermesClient {
+    /// Fetches the latest prices of `ids` like [`HermesClient::get_latest_price_feeds`], in the order of `ids`
+    ///
+    /// Each requested id has a slot in the result, [`FeedSlot::Missing`] if hermes didn't return its feed.
+    pub async fn get_latest_price_feeds_ordered(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<Vec<FeedSlot>, HermesError> {
+        let feeds = self.get_latest_price_feeds(ids).await?;
+        Ok(order_feeds(ids, feeds))
+    }
+
+    /// Fetches the latest prices of `ids` in their order, failing with [`HermesError::MissingFeeds`] unless hermes
+    /// returns every one of them
+    pub async fn get_latest_price_feeds_complete(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<Vec<RpcPriceFeed>, HermesError> {
+        require_found(self.get_latest_price_feeds_ordered(ids).await?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD, SOL_USD},
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_get_latest_price_feeds_ordered() {
+        // hermes responds out of order, without SOL
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD, BTC_USD], 1, 1),
+        )])
+        .await
+        .unwrap();
+        let slots = HermesClient::new(mock.url())
+            .get_latest_price_feeds_ordered(&[BTC_USD, SOL_USD, ETH_USD])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            slots.iter().map(FeedSlot::id).collect::<Vec<_>>(),
+            [BTC_USD, SOL_USD, ETH_USD]
+        );
+        assert_eq!(slots[0].found().unwrap().id, BTC_USD);
+        assert!(matches!(slots[1], FeedSlot::Missing(id) if id == SOL_USD));
+        assert_eq!(slots[2].clone().into_found().unwrap().id, ETH_USD);
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_price_feeds_complete() {
+        let path = "/v2/updates/price/latest";
+        let response = price_update_json(&[ETH_USD, BTC_USD], 1, 1);
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(path, response.clone()),
+            RecordedResponse::json(path, response),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let feeds = client
+            .get_latest_price_feeds_complete(&[BTC_USD, ETH_USD])
+            .await
+            .unwrap();
+        assert_eq!(feeds[0].id, BTC_USD);
+        assert_eq!(feeds[1].id, ETH_USD);
+
+        let err = client
+            .get_latest_price_feeds_complete(&[SOL_USD, ETH_USD])
+            .await
+            .unwrap_err();
+        assert!(matches!(err.uncorrelated(), HermesError::MissingFeeds(ids) if ids == &[SOL_USD]));
+    }
+}
@@ -0,0 +1,117 @@
+//! Restricting the feeds a client may use, so unapproved markets can't be requested by accident
+
+use {
+    crate::{error::HermesError, types::FeedId},
+    std::collections::HashSet,
+};
+
+/// Feeds a client may request or subscribe to, see
+/// [`HermesClientBuilder::feed_policy`](crate::builder::HermesClientBuilder::feed_policy)
+///
+/// Requests and subscriptions including a disallowed feed fail with [`HermesError::FeedNotAllowed`] before anything
+/// is sent to hermes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FeedPolicy {
+    #[default]
+    AllowAll,
+    /// Only the listed feeds are allowed
+    Allow(HashSet<FeedId>),
+    /// Every feed except the listed feeds is allowed
+    Deny(HashSet<FeedId>),
+}
+
+impl FeedPolicy {
+    pub fn allow(ids: impl IntoIterator<Item = FeedId>) -> Self {
+        Self::Allow(ids.into_iter().collect())
+    }
+
+    pub fn deny(ids: impl IntoIterator<Item = FeedId>) -> Self {
+        Self::Deny(ids.into_iter().collect())
+    }
+
+    pub fn is_allowed(&self, id: &FeedId) -> bool {
+        match self {
+            Self::AllowAll => true,
+            Self::Allow(ids) => ids.contains(id),
+            Self::Deny(ids) => !ids.contains(id),
+        }
+    }
+
+    /// Fails with every disallowed feed in `ids`
+    pub fn check<'a>(&self, ids: impl IntoIterator<Item = &'a FeedId>) -> Result<(), HermesError> {
+        let denied: Vec<FeedId> = ids
+            .into_iter()
+            .filter(|id| !self.is_allowed(id))
+            .copied()
+            .collect();
+        if denied.is_empty() {
+            return Ok(());
+        }
+        Err(HermesError::FeedNotAllowed(denied))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::{ETH_USD, SOL_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+            HermesClient,
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_feed_policy() {
+        let allow = FeedPolicy::allow([ETH_USD]);
+        assert!(allow.is_allowed(&ETH_USD));
+        assert!(!allow.is_allowed(&SOL_USD));
+        let deny = FeedPolicy::deny([ETH_USD]);
+        assert!(!deny.is_allowed(&ETH_USD));
+        assert!(deny.is_allowed(&SOL_USD));
+        assert!(FeedPolicy::AllowAll.check(&[ETH_USD]).is_ok());
+        assert!(matches!(
+            allow.check(&[ETH_USD, SOL_USD]),
+            Err(HermesError::FeedNotAllowed(ids)) if ids == [SOL_USD]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_feed_policy() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 1, 1),
+            ),
+            RecordedResponse::json(
+                "/v2/price_feeds",
+                json!([
+                    {"id": ETH_USD, "attributes": {}},
+                    {"id": SOL_USD, "attributes": {}},
+                ]),
+            ),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .feed_policy(FeedPolicy::deny([SOL_USD]))
+            .build()
+            .unwrap();
+
+        let err = client
+            .get_latest_price_feeds(&[ETH_USD, SOL_USD])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HermesError::FeedNotAllowed(ids) if ids == [SOL_USD]));
+        assert!(client.subscribe(&[SOL_USD]).await.is_err());
+        assert!(mock.requests().await.is_empty());
+
+        client.get_latest_price_feeds(&[ETH_USD]).await.unwrap();
+        // feeds from the catalog which aren't allowed are left out
+        let feeds = client.get_feeds_with_prices(None, None).await.unwrap();
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].metadata.id, ETH_USD);
+    }
+}
@@ -0,0 +1,239 @@
+//! Polling of latest prices within a request budget, for deployments where streaming isn't an option
+//!
+//! Feeds are polled in batches, each on its own schedule starting at a random phase within the interval, so requests
+//! are spread out rather than sent in bursts. Requests are capped at the budget's rate, and a `429 Too Many Requests`
+//! pauses every batch, with the pause doubling on each consecutive rejection.
+//!
+//! ```no_run
+//! # async fn example(client: pyth_hermes_rs::HermesClient) {
+//! use pyth_hermes_rs::{feed_ids, poll::PollOptions};
+//!
+//! let mut poller = client.poll_prices(
+//!     &[feed_ids::BTC_USD, feed_ids::ETH_USD],
+//!     PollOptions::public_endpoint_safe(),
+//! );
+//! while let Some(feed) = poller.next().await {
+//!     println!("{}: {}", feed.id, feed.price.price);
+//! }
+//! # }
+//! ```
+
+use {
+    crate::{
+        error::HermesError,
+        rate_limit::RateLimiter,
+        rt::{self, TaskHandle},
+        types::{FeedId, RpcPriceFeed},
+        HermesClient,
+    },
+    std::time::Duration,
+    tokio::sync::mpsc,
+};
+
+/// Budget and schedule of a [`PricePoller`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollOptions {
+    /// Time between polls of each batch
+    pub interval: Duration,
+    /// Maximum number of feeds requested at once
+    pub batch_size: usize,
+    /// Maximum rate of requests made by the poller
+    pub requests_per_second: f64,
+    /// Pause after the first rate limited request, doubled on each consecutive one
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            batch_size: 100,
+            requests_per_second: 10.0,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl PollOptions {
+    /// Budget for the public endpoint, which bans IPs making over 30 requests per 10 seconds
+    ///
+    /// Uses a third of the limit at most, leaving room for other requests, which polls 100 feeds every 5 seconds.
+    /// Rate limited requests pause polling for at least the limit's 10 second window.
+    pub fn public_endpoint_safe() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            batch_size: 20,
+            requests_per_second: 1.0,
+            backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Latest prices polled in the background, see [`HermesClient::poll_prices`]
+///
+/// Dropping the poller stops polling.
+#[must_use = "polling stops when the poller is dropped"]
+pub struct PricePoller {
+    updates: mpsc::UnboundedReceiver<RpcPriceFeed>,
+    task: TaskHandle,
+}
+
+impl PricePoller {
+    /// Waits for the next polled price, returns `None` once polling stops
+    pub async fn next(&mut self) -> Option<RpcPriceFeed> {
+        self.updates.recv().await
+    }
+
+    /// Returns the next polled price if one has already been received
+    pub fn try_next(&mut self) -> Option<RpcPriceFeed> {
+        self.updates.try_recv().ok()
+    }
+}
+
+impl Drop for PricePoller {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl HermesClient {
+    /// Polls the latest prices of `ids` in the background within the budget of `options`
+    ///
+    /// Every price returned by each poll is received, whether or not it changed. Requests which fail for reasons
+    /// other than rate limiting are logged and retried at the batch's next poll.
+    pub fn poll_prices(&self, ids: &[impl AsRef<FeedId>], options: PollOptions) -> PricePoller {
+        let batches = ids
+            .iter()
+            .map(|id| *id.as_ref())
+            .collect::<Vec<_>>()
+            .chunks(options.batch_size.max(1))
+            .map(<[FeedId]>::to_vec)
+            .collect();
+        let (sender, updates) = mpsc::unbounded_channel();
+        let task = rt::spawn(poll(self.clone(), batches, options, sender));
+        PricePoller { updates, task }
+    }
+}
+
+async fn poll(
+    client: HermesClient,
+    batches: Vec<Vec<FeedId>>,
+    options: PollOptions,
+    sender: mpsc::UnboundedSender<RpcPriceFeed>,
+) {
+    if batches.is_empty() {
+        return;
+    }
+    let limiter = RateLimiter::new(options.requests_per_second, 1);
+    let interval = options.interval.as_millis() as u64;
+    let start = rt::unix_timestamp_millis();
+    // when each batch is next due, in milliseconds since the unix epoch
+    let mut due: Vec<u64> = batches
+        .iter()
+        .map(|_| start + (interval as f64 * client.rng.fraction()) as u64)
+        .collect();
+    let mut backoff: Option<Duration> = None;
+    loop {
+        let (batch, &at) = due
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, at)| **at)
+            .expect("there is at least one batch");
+        let wait = at.saturating_sub(rt::unix_timestamp_millis());
+        if wait > 0 {
+            client.timer.sleep(Duration::from_millis(wait)).await;
+        }
+        limiter.acquire().await;
+        let result = client
+            .latest_price()
+            .ids(&batches[batch])
+            .ignore_invalid(true)
+            .send()
+            .await;
+        let now = rt::unix_timestamp_millis();
+        match result {
+            Ok(update) => {
+                backoff = None;
+                due[batch] = now + interval;
+                for feed in update.parsed.unwrap_or_default() {
+                    if sender.send(feed).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(err) => {
+                if let HermesError::RateLimited { retry_after } = err.uncorrelated() {
+                    let pause = backoff
+                        .map_or(options.backoff, |backoff| backoff * 2)
+                        .max(retry_after.unwrap_or_default())
+                        .min(options.max_backoff);
+                    log::warn!("polling rate limited, pausing for {pause:?}");
+                    backoff = Some(pause);
+                    let resume = now + pause.as_millis() as u64;
+                    due.iter_mut().for_each(|at| *at = (*at).max(resume));
+                } else {
+                    log::warn!("failed to poll {} feeds: {err}", batches[batch].len());
+                    due[batch] = now + interval;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::{BTC_USD, ETH_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+        std::time::Instant,
+    };
+
+    #[tokio::test]
+    async fn test_poll_backs_off_when_rate_limited() {
+        let path = "/v2/updates/price/latest";
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status(path, 429),
+            RecordedResponse::json(path, price_update_json(&[ETH_USD], 1, 1)),
+        ])
+        .await
+        .unwrap();
+        let started = Instant::now();
+        let mut poller = HermesClient::new(mock.url()).poll_prices(
+            &[ETH_USD, BTC_USD],
+            PollOptions {
+                interval: Duration::from_millis(50),
+                batch_size: 1,
+                requests_per_second: 100.0,
+                backoff: Duration::from_millis(300),
+                max_backoff: Duration::from_secs(1),
+            },
+        );
+
+        assert_eq!(poller.next().await.unwrap().id, ETH_USD);
+        // the rejected first request paused both batches
+        assert!(started.elapsed() >= Duration::from_millis(300));
+
+        // both batches keep being polled
+        for _ in 0..4 {
+            poller.next().await.unwrap();
+        }
+        let queries: Vec<_> = mock
+            .requests()
+            .await
+            .into_iter()
+            .filter_map(|r| r.query)
+            .collect();
+        // each request is for a single batch
+        assert!(queries
+            .iter()
+            .all(|q| { q.contains(&BTC_USD.to_hex()) != q.contains(&ETH_USD.to_hex()) }));
+        assert!(queries.iter().any(|q| q.contains(&BTC_USD.to_hex())));
+        assert!(queries.iter().any(|q| q.contains(&ETH_USD.to_hex())));
+    }
+}
@@ -0,0 +1,198 @@
+//! Named presets bundling the endpoints and defaults of a hermes environment
+//!
+//! Profiles let a fleet switch environments through configuration rather than urls in code:
+//!
+//! ```no_run
+//! # fn example() -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::{profile::HermesProfile, HermesClient};
+//!
+//! // HERMES_PROFILE=beta, or a url such as HERMES_PROFILE=https://hermes.internal
+//! let client = HermesClient::from_profile(HermesProfile::from_env()?)?;
+//! let client = HermesProfile::self_hosted("https://hermes.internal")
+//!     .fallback_url("https://hermes-backup.internal")
+//!     .builder()
+//!     .user_agent("pricing-service")
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{
+        builder::HermesClientBuilder, error::HermesError, retry::RetryPolicy,
+        types::PUBLIC_BASE_URL, HermesClient,
+    },
+    std::{str::FromStr, time::Duration},
+};
+
+/// Environment variable read by [`HermesProfile::from_env`]
+pub const PROFILE_ENV_VAR: &str = "HERMES_PROFILE";
+
+/// URL of the public hermes api serving the beta Pyth network
+pub const BETA_BASE_URL: &str = "https://hermes-beta.pyth.network";
+
+/// URL hermes listens on when run locally with its default configuration
+pub const LOCAL_BASE_URL: &str = "http://127.0.0.1:33999";
+
+/// Endpoints and defaults of a hermes environment, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HermesProfile {
+    /// Name the profile is selected by, or the base url of self hosted profiles
+    pub name: String,
+    pub base_url: String,
+    pub fallback_urls: Vec<String>,
+    /// Requests per second and burst, see [`HermesClientBuilder::rate_limit`]
+    pub rate_limit: Option<(f64, u32)>,
+    pub timeout: Option<Duration>,
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+impl HermesProfile {
+    /// The public hermes api, rate limited below its limit of 30 requests per 10 seconds per IP
+    pub fn public() -> Self {
+        Self {
+            name: "public".to_string(),
+            base_url: PUBLIC_BASE_URL.to_string(),
+            fallback_urls: Vec::new(),
+            rate_limit: Some((3.0, 10)),
+            timeout: Some(Duration::from_secs(10)),
+            retry_policy: Some(RetryPolicy::default()),
+        }
+    }
+
+    /// The public hermes api of the beta Pyth network, for staging environments
+    pub fn beta() -> Self {
+        Self {
+            name: "beta".to_string(),
+            base_url: BETA_BASE_URL.to_string(),
+            ..Self::public()
+        }
+    }
+
+    /// A hermes instance running locally, without rate limiting or retries
+    pub fn local() -> Self {
+        Self {
+            name: "local".to_string(),
+            ..Self::self_hosted(LOCAL_BASE_URL)
+        }
+    }
+
+    /// A self hosted or provider deployment at `base_url`, which is retried but not rate limited
+    pub fn self_hosted(base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into();
+        Self {
+            name: base_url.clone(),
+            base_url,
+            fallback_urls: Vec::new(),
+            rate_limit: None,
+            timeout: Some(Duration::from_secs(10)),
+            retry_policy: Some(RetryPolicy::default()),
+        }
+    }
+
+    /// Selects a profile by name, `public`, `beta` or `local`, or a self hosted profile from a url
+    pub fn from_name(name: &str) -> Result<Self, HermesError> {
+        match name.trim() {
+            "public" | "mainnet" => Ok(Self::public()),
+            "beta" | "staging" => Ok(Self::beta()),
+            "local" => Ok(Self::local()),
+            url if url.starts_with("http://") || url.starts_with("https://") => {
+                Ok(Self::self_hosted(url.trim_end_matches('/')))
+            }
+            name => Err(HermesError::UnknownProfile(name.to_string())),
+        }
+    }
+
+    /// Selects a profile with [`from_name`](Self::from_name) from the `HERMES_PROFILE` environment variable,
+    /// defaulting to [`public`](Self::public) when it isn't set
+    pub fn from_env() -> Result<Self, HermesError> {
+        match std::env::var(PROFILE_ENV_VAR) {
+            Ok(name) => Self::from_name(&name),
+            Err(_) => Ok(Self::public()),
+        }
+    }
+
+    pub fn fallback_url(mut self, url: impl Into<String>) -> Self {
+        self.fallback_urls.push(url.into());
+        self
+    }
+
+    /// Builder configured with the profile, which further options can be applied to
+    pub fn builder(&self) -> HermesClientBuilder {
+        let mut builder = HermesClientBuilder::new(&self.base_url);
+        for url in &self.fallback_urls {
+            builder = builder.fallback_url(url);
+        }
+        if let Some((requests_per_second, burst)) = self.rate_limit {
+            builder = builder.rate_limit(requests_per_second, burst);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(policy) = self.retry_policy {
+            builder = builder.retry_policy(policy);
+        }
+        builder
+    }
+}
+
+impl FromStr for HermesProfile {
+    type Err = HermesError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::from_name(name)
+    }
+}
+
+impl HermesClient {
+    /// Builds a client for a [`HermesProfile`]
+    pub fn from_profile(profile: HermesProfile) -> Result<Self, HermesError> {
+        profile.builder().build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::mock::{MockHermes, RecordedResponse},
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            HermesProfile::from_name("public").unwrap(),
+            HermesProfile::public()
+        );
+        assert_eq!(
+            HermesProfile::from_name("staging").unwrap().base_url,
+            BETA_BASE_URL
+        );
+        assert_eq!(
+            "local".parse::<HermesProfile>().unwrap().base_url,
+            LOCAL_BASE_URL
+        );
+        let custom = HermesProfile::from_name("https://hermes.internal/").unwrap();
+        assert_eq!(custom.base_url, "https://hermes.internal");
+        assert_eq!(custom.rate_limit, None);
+        assert!(matches!(
+            HermesProfile::from_name("prod"),
+            Err(HermesError::UnknownProfile(name)) if name == "prod"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_from_profile() {
+        let mock = MockHermes::start(vec![RecordedResponse::json("/v2/price_feeds", json!([]))])
+            .await
+            .unwrap();
+        let profile = HermesProfile::self_hosted("http://127.0.0.1:1").fallback_url(mock.url());
+        let client = HermesClient::from_profile(profile).unwrap();
+        assert!(client
+            .get_price_feeds_metadata(None, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! Python bindings, built into the `pyth_hermes` extension module with maturin
+//!
+//! The bindings wrap the [blocking client](crate::blocking), releasing the GIL while requests are in flight. Results
+//! are returned as the same dicts and lists hermes' JSON decodes to.
+//!
+//! ```python
+//! from pyth_hermes import HermesClient
+//!
+//! client = HermesClient("https://hermes.pyth.network")
+//! feeds = client.latest_price_feeds(["ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace"])
+//! subscription = client.subscribe([feeds[0]["id"]])
+//! while True:
+//!     for update in subscription.poll():
+//!         print(update["price"])
+//! ```
+
+use {
+    crate::{
+        blocking::{HermesClient, PollingSubscription},
+        types::{AssetType, FeedId},
+    },
+    pyo3::{
+        create_exception,
+        exceptions::{PyException, PyValueError},
+        prelude::*,
+    },
+    serde::Serialize,
+};
+
+create_exception!(
+    pyth_hermes,
+    HermesError,
+    PyException,
+    "Raised when a request to hermes fails"
+);
+
+/// Client for the Pyth Hermes API
+#[pyclass(name = "HermesClient", module = "pyth_hermes", frozen)]
+pub struct PyHermesClient(HermesClient);
+
+/// Price updates streamed in the background, drained with `poll`
+#[pyclass(name = "Subscription", module = "pyth_hermes")]
+pub struct PySubscription(PollingSubscription);
+
+#[pymethods]
+impl PyHermesClient {
+    #[new]
+    fn new(base_url: &str) -> PyResult<Self> {
+        let client = crate::HermesClient::builder(base_url)
+            .build()
+            .map_err(to_py_err)?;
+        Ok(Self(client.into()))
+    }
+
+    /// Latest prices of the feeds with the hex `ids`
+    fn latest_price_feeds<'py>(
+        &self,
+        py: Python<'py>,
+        ids: Vec<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let ids = parse_ids(&ids)?;
+        let feeds = py
+            .allow_threads(|| self.0.get_latest_price_feeds(&ids))
+            .map_err(to_py_err)?;
+        to_python(py, &feeds)
+    }
+
+    /// Metadata of feeds matching the filters, revalidated with hermes on each call
+    #[pyo3(signature = (query = None, asset_type = None))]
+    fn price_feeds_metadata<'py>(
+        &self,
+        py: Python<'py>,
+        query: Option<&str>,
+        asset_type: Option<&str>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let asset_type = asset_type
+            .map(|asset_type| {
+                serde_json::from_value::<AssetType>(asset_type.into()).map_err(|_| {
+                    PyValueError::new_err(format!("unknown asset type {asset_type:?}"))
+                })
+            })
+            .transpose()?;
+        let metadata = py
+            .allow_threads(|| self.0.get_price_feeds_metadata_cached(query, asset_type))
+            .map_err(to_py_err)?;
+        to_python(py, &*metadata)
+    }
+
+    /// Streams price updates for the hex `ids` in the background
+    fn subscribe(&self, py: Python<'_>, ids: Vec<String>) -> PyResult<PySubscription> {
+        let ids = parse_ids(&ids)?;
+        let subscription = py
+            .allow_threads(|| self.0.subscribe(&ids))
+            .map_err(to_py_err)?;
+        Ok(PySubscription(subscription))
+    }
+}
+
+#[pymethods]
+impl PySubscription {
+    /// Updates received since the last poll, without blocking
+    fn poll<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.0.poll_updates())
+    }
+
+    /// Stops the stream
+    fn close(&self) {
+        self.0.handle().abort();
+    }
+}
+
+/// Entry point of the `pyth_hermes` extension module
+#[pymodule]
+pub fn pyth_hermes(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyHermesClient>()?;
+    m.add_class::<PySubscription>()?;
+    m.add("HermesError", m.py().get_type::<HermesError>())?;
+    Ok(())
+}
+
+fn to_py_err(err: crate::error::HermesError) -> PyErr {
+    HermesError::new_err(err.to_string())
+}
+
+fn parse_ids(ids: &[String]) -> PyResult<Vec<FeedId>> {
+    FeedId::parse_all(ids).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+/// Converts `value` to the python objects its JSON decodes to
+fn to_python<'py>(
+    py: Python<'py>,
+    value: &(impl Serialize + ?Sized),
+) -> PyResult<Bound<'py, PyAny>> {
+    let json =
+        serde_json::to_string(value).map_err(|err| PyValueError::new_err(err.to_string()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::ETH_USD,
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[test]
+    fn test_python_client() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mock = runtime
+            .block_on(MockHermes::start(vec![RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 42, 1),
+            )]))
+            .unwrap();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let client = PyHermesClient::new(&mock.url()).unwrap();
+            let feeds = client
+                .latest_price_feeds(py, vec![ETH_USD.to_string()])
+                .unwrap();
+            let price: String = feeds
+                .get_item(0)
+                .and_then(|feed| feed.get_item("price"))
+                .and_then(|price| price.get_item("price"))
+                .and_then(|price| price.extract())
+                .unwrap();
+            assert_eq!(price, "42");
+
+            let err = client
+                .latest_price_feeds(py, vec!["eth".to_string()])
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+        });
+    }
+}
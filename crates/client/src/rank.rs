@@ -0,0 +1,212 @@
+//! Sorting and ranking of feeds joined with their prices, for screener style applications
+//!
+//! ```no_run
+//! # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use {pyth_hermes_rs::rank::FeedRanking, std::time::Duration};
+//!
+//! let mut feeds = client.get_feeds_with_prices(None, None).await?;
+//! let ids: Vec<_> = feeds.iter().map(|feed| feed.metadata.id).collect();
+//! let day_ago = client
+//!     .get_reference_prices(&ids, Duration::from_secs(24 * 60 * 60))
+//!     .await?;
+//! feeds.sort_by_change(&day_ago);
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{error::HermesError, types::*, HermesClient},
+    futures_util::{stream, StreamExt, TryStreamExt},
+    std::{cmp::Ordering, collections::HashMap, time::Duration},
+};
+
+impl FeedWithPrice {
+    /// Confidence interval as a fraction of the price, lower values indicate tighter pricing
+    pub fn confidence_ratio(&self) -> Option<f64> {
+        let price = &self.price.as_ref()?.price;
+        let conf = price.conf.parse::<f64>().ok()?;
+        let value = price.price.parse::<f64>().ok()?;
+        (value != 0.0).then(|| conf / value.abs())
+    }
+
+    /// Fractional change from `reference` to the current price, `0.05` being a 5% increase
+    pub fn change_since(&self, reference: &RpcPrice) -> Option<f64> {
+        let current = self.price.as_ref()?.price.to_f64()?;
+        let reference = reference.to_f64()?;
+        (reference != 0.0).then(|| (current - reference) / reference)
+    }
+
+    /// Publish time of the current price
+    pub fn publish_time(&self) -> Option<i64> {
+        Some(self.price.as_ref()?.price.publish_time)
+    }
+}
+
+/// Sorting helpers for joined feed data, feeds missing the value being sorted by are placed last
+pub trait FeedRanking {
+    /// Largest increase first, using `reference` prices keyed by feed id
+    fn sort_by_change(&mut self, reference: &HashMap<FeedId, RpcPrice>);
+    /// Tightest confidence interval first
+    fn sort_by_confidence_ratio(&mut self);
+    /// Most recently published first
+    fn sort_by_recency(&mut self);
+}
+
+impl FeedRanking for [FeedWithPrice] {
+    fn sort_by_change(&mut self, reference: &HashMap<FeedId, RpcPrice>) {
+        self.sort_by(|a, b| {
+            let change = |feed: &FeedWithPrice| {
+                reference
+                    .get(&feed.metadata.id)
+                    .and_then(|reference| feed.change_since(reference))
+            };
+            compare(change(a), change(b), true)
+        });
+    }
+
+    fn sort_by_confidence_ratio(&mut self) {
+        self.sort_by(|a, b| compare(a.confidence_ratio(), b.confidence_ratio(), false));
+    }
+
+    fn sort_by_recency(&mut self) {
+        self.sort_by(|a, b| compare(a.publish_time(), b.publish_time(), true));
+    }
+}
+
+/// Orders `Some` values in ascending or descending order, followed by `None`
+fn compare<T: PartialOrd>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl HermesClient {
+    /// Fetches the first price published at least `ago` before now for each feed, for use with
+    /// [`FeedRanking::sort_by_change`]
+    pub async fn get_reference_prices(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        ago: Duration,
+    ) -> Result<HashMap<FeedId, RpcPrice>, HermesError> {
+        let now = self
+            .now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let publish_time = now.saturating_sub(ago).as_secs() as i64;
+        let ids: Vec<FeedId> = ids.iter().map(|id| *id.as_ref()).collect();
+        // created up front, as futures created by a stream's closure don't satisfy `Send` in spawned tasks
+        let requests: Vec<_> = ids
+            .chunks(self.chunking.max_ids)
+            .map(|chunk| {
+                self.price_updates_by_time(publish_time)
+                    .ids(chunk)
+                    .ignore_invalid(true)
+                    .send()
+            })
+            .collect();
+        let updates: Vec<PriceUpdate> = stream::iter(requests)
+            .buffered(self.chunking.max_concurrency)
+            .try_collect()
+            .await?;
+        Ok(updates
+            .into_iter()
+            .flat_map(|update| update.parsed.unwrap_or_default())
+            .map(|feed| (feed.id, feed.price))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+        std::time::UNIX_EPOCH,
+    };
+
+    fn feed(id: u8, price: &str, conf: &str, publish_time: i64) -> FeedWithPrice {
+        let price = RpcPrice {
+            price: price.to_string(),
+            conf: conf.to_string(),
+            expo: 0,
+            publish_time,
+        };
+        FeedWithPrice {
+            metadata: PriceFeedMetadata {
+                id: FeedId::from_bytes([id; 32]),
+                attributes: HashMap::new(),
+            },
+            price: Some(RpcPriceFeed {
+                id: FeedId::from_bytes([id; 32]),
+                price: price.clone(),
+                ema_price: price,
+                metadata: None,
+                vaa: None,
+            }),
+        }
+    }
+
+    fn order(feeds: &[FeedWithPrice]) -> Vec<u8> {
+        feeds.iter().map(|f| f.metadata.id.to_bytes()[0]).collect()
+    }
+
+    #[test]
+    fn test_rank_feeds() {
+        let mut feeds = vec![
+            feed(1, "100", "10", 3),
+            feed(2, "100", "1", 1),
+            feed(3, "200", "5", 2),
+        ];
+        feeds[0].price = None;
+
+        feeds.sort_by_confidence_ratio();
+        assert_eq!(order(&feeds), [2, 3, 1]);
+
+        feeds.sort_by_recency();
+        assert_eq!(order(&feeds), [3, 2, 1]);
+
+        let reference: HashMap<_, _> = [(2, "50"), (3, "400")]
+            .into_iter()
+            .map(|(id, price)| {
+                let feed = feed(id, price, "1", 0);
+                (feed.metadata.id, feed.price.unwrap().price)
+            })
+            .collect();
+        feeds.sort_by_change(&reference);
+        assert_eq!(order(&feeds), [2, 3, 1]);
+        assert_eq!(
+            feeds[0].change_since(&reference[&feeds[0].metadata.id]),
+            Some(1.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_reference_prices() {
+        let id = FeedId::from_bytes([1; 32]);
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/900",
+            price_update_json(&[id], 7, 900),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .clock(|| UNIX_EPOCH + Duration::from_secs(1000))
+            .chunking(2, 1)
+            .build()
+            .unwrap();
+        let prices = client
+            .get_reference_prices(
+                &[id, FeedId::from_bytes([2; 32]), FeedId::from_bytes([3; 32])],
+                Duration::from_secs(100),
+            )
+            .await
+            .unwrap();
+        assert_eq!(prices[&id].price, "7");
+        // requested in chunks of the client's chunk size
+        assert_eq!(mock.requests().await.len(), 2);
+    }
+}
@@ -0,0 +1,115 @@
+//! Client side rate limiting shared by every request made through a [`HermesClient`](crate::HermesClient)
+
+use {
+    crate::rt,
+    std::{sync::Mutex, time::Duration},
+};
+
+/// Token bucket limiting the rate of requests, see
+/// [`HermesClientBuilder::rate_limit`](crate::builder::HermesClientBuilder::rate_limit)
+///
+/// The bucket starts full, allowing `burst` requests to be made immediately, and refills at
+/// `requests_per_second`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at_millis: u64,
+}
+
+impl RateLimiter {
+    /// # Panics
+    ///
+    /// If `requests_per_second` is not positive or `burst` is zero
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        assert!(
+            requests_per_second > 0.0,
+            "requests_per_second must be positive"
+        );
+        assert!(burst > 0, "burst must be at least 1");
+        Self {
+            requests_per_second,
+            burst: burst as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                refilled_at_millis: rt::unix_timestamp_millis(),
+            }),
+        }
+    }
+
+    /// Waits until a request may be made
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            rt::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if one is available, otherwise returns how long until one will be
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let now = rt::unix_timestamp_millis();
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_sub(bucket.refilled_at_millis) as f64 / 1000.0;
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst);
+        bucket.refilled_at_millis = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = (1.0 - bucket.tokens) / self.requests_per_second;
+        // round up to the clock's resolution so the next attempt succeeds
+        Err(Duration::from_millis((wait * 1000.0).ceil() as u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            mock::{MockHermes, RecordedResponse},
+            HermesClient,
+        },
+        serde_json::json,
+        std::time::Instant,
+    };
+
+    #[test]
+    fn test_try_acquire() {
+        let limiter = RateLimiter::new(10.0, 2);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_client() {
+        let mock = MockHermes::start(vec![RecordedResponse::json("/v2/price_feeds", json!([]))])
+            .await
+            .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .rate_limit(20.0, 1)
+            .build()
+            .unwrap();
+
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.get_price_feeds_metadata(None, None).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        // the first request is immediate, the remaining four are spaced 50ms apart
+        assert!(start.elapsed() >= Duration::from_millis(190));
+        assert_eq!(mock.requests().await.len(), 5);
+    }
+}
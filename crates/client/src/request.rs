@@ -0,0 +1,539 @@
+//! Fluent builders for price queries, allowing query parameters to be added without changing method signatures
+//!
+//! ```no_run
+//! # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::types::{EncodingType, FeedId};
+//!
+//! let eth_usd = FeedId::from_hex("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace");
+//! let update = client
+//!     .latest_price()
+//!     .ids([eth_usd])
+//!     .encoding(EncodingType::Base64)
+//!     .parsed(false)
+//!     .ignore_invalid(true)
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{error::HermesError, types::*, HermesClient},
+    futures_util::{stream, StreamExt, TryStreamExt},
+    reqwest::RequestBuilder,
+    serde::Serialize,
+    std::{collections::HashSet, time::Duration},
+};
+
+/// Options applied to individual REST requests, see [`HermesClient::with_options`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Deadline for the whole request, including reading the response body
+    pub timeout: Option<Duration>,
+    /// Send requests to the first two endpoints concurrently and use the first successful response, see
+    /// [`HermesClientBuilder::hedged`](crate::builder::HermesClientBuilder::hedged)
+    pub hedge: bool,
+}
+
+impl RequestOptions {
+    /// Options with a deadline for the whole request
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..Self::default()
+        }
+    }
+
+    /// Applies the options to a request, without overriding values already set on the request
+    pub(crate) fn apply(&self, req: RequestBuilder) -> Result<RequestBuilder, HermesError> {
+        let Some(timeout) = self.timeout else {
+            return Ok(req);
+        };
+        let (client, request) = req.build_split();
+        let mut request = request?;
+        request.timeout_mut().get_or_insert(timeout);
+        Ok(RequestBuilder::from_parts(client, request))
+    }
+}
+
+/// How price requests for many feeds are split, see
+/// [`HermesClientBuilder::chunking`](crate::builder::HermesClientBuilder::chunking)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Chunking {
+    pub(crate) max_ids: usize,
+    pub(crate) max_concurrency: usize,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self {
+            max_ids: 100,
+            max_concurrency: 4,
+        }
+    }
+}
+
+/// Query parameters shared by the price update endpoints
+#[derive(Debug, Default, Clone, Serialize)]
+struct QueryParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<EncodingType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parsed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ignore_invalid_price_ids: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    benchmarks_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allow_unordered: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct PriceQuery {
+    ids: Vec<FeedId>,
+    /// Ids passed to `hex_ids` which failed to parse, reported when the request is sent
+    invalid_ids: Vec<(String, FeedIdError)>,
+    params: QueryParams,
+    timeout: Option<Duration>,
+}
+
+impl PriceQuery {
+    /// Unique ids of the query, failing if any are invalid or not allowed by the client's policy
+    fn validate(&self, client: &HermesClient) -> Result<Vec<FeedId>, HermesError> {
+        if !self.invalid_ids.is_empty() {
+            return Err(HermesError::InvalidFeedId(self.invalid_ids.clone()));
+        }
+        client.misuse_policy.check_ids(&self.ids)?;
+        let ids = unique_ids(&self.ids);
+        client.feed_policy.check(&ids)?;
+        Ok(ids)
+    }
+
+    /// Sends the query for price updates, split into requests of at most the client's chunk size
+    ///
+    /// The responses are merged in the order of the chunks.
+    async fn send_chunked(
+        &self,
+        client: &HermesClient,
+        path: &str,
+    ) -> Result<PriceUpdate, HermesError> {
+        let ids = self.validate(client)?;
+        let Chunking {
+            max_ids,
+            max_concurrency,
+        } = client.chunking;
+        if ids.len() <= max_ids {
+            let req = self.request(client, path)?;
+            return Ok(client.send(req).await?.json().await?);
+        }
+        // created up front, as futures created by a stream's closure don't satisfy `Send` in spawned tasks
+        let requests: Vec<_> = ids
+            .chunks(max_ids)
+            .map(|chunk| {
+                let query = Self {
+                    ids: chunk.to_vec(),
+                    ..self.clone()
+                };
+                async move {
+                    let req = query.request(client, path)?;
+                    Ok::<PriceUpdate, HermesError>(client.send(req).await?.json().await?)
+                }
+            })
+            .collect();
+        let updates: Vec<PriceUpdate> = stream::iter(requests)
+            .buffered(max_concurrency)
+            .try_collect()
+            .await?;
+        Ok(merge_updates(updates))
+    }
+
+    fn request(&self, client: &HermesClient, path: &str) -> Result<RequestBuilder, HermesError> {
+        let ids = self.validate(client)?;
+        let url = client.url(path)?;
+        let mut req = client.http.get(url).query(&self.params);
+        for id in &ids {
+            req = req.query(&[("ids[]", id)]);
+        }
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        Ok(req)
+    }
+}
+
+/// Combines the responses to chunks of a request into a single response
+pub(crate) fn merge_updates(updates: Vec<PriceUpdate>) -> PriceUpdate {
+    let mut updates = updates.into_iter();
+    let mut merged = updates.next().expect("at least one chunk");
+    for update in updates {
+        merged.binary.data.extend(update.binary.data);
+        if let Some(parsed) = update.parsed {
+            merged.parsed.get_or_insert_with(Vec::new).extend(parsed);
+        }
+    }
+    merged
+}
+
+/// `ids` in the order they first appear, without duplicates, which would only lengthen the query string
+pub(crate) fn unique_ids(ids: &[impl AsRef<FeedId>]) -> Vec<FeedId> {
+    let mut seen = HashSet::new();
+    ids.iter()
+        .map(|id| *id.as_ref())
+        .filter(|id| seen.insert(*id))
+        .collect()
+}
+
+/// Request for the latest price updates, created by [`HermesClient::latest_price`]
+#[must_use = "requests do nothing unless sent"]
+pub struct LatestPriceRequest<'a> {
+    client: &'a HermesClient,
+    query: PriceQuery,
+}
+
+/// Request for price updates at a publish time, created by [`HermesClient::price_updates_by_time`]
+#[must_use = "requests do nothing unless sent"]
+pub struct PriceUpdatesByTimeRequest<'a> {
+    client: &'a HermesClient,
+    publish_time: i64,
+    query: PriceQuery,
+}
+
+/// Request for the latest TWAPs, created by [`HermesClient::latest_twaps`]
+#[must_use = "requests do nothing unless sent"]
+pub struct LatestTwapsRequest<'a> {
+    client: &'a HermesClient,
+    window_seconds: u64,
+    query: PriceQuery,
+}
+
+/// Setters for the parameters accepted by every price update endpoint
+macro_rules! common_params {
+    () => {
+        /// Adds price feed ids to the request, ids added more than once are only sent once
+        pub fn ids<I>(mut self, ids: I) -> Self
+        where
+            I: IntoIterator,
+            I::Item: AsRef<FeedId>,
+        {
+            self.query
+                .ids
+                .extend(ids.into_iter().map(|id| *id.as_ref()));
+            self
+        }
+
+        /// Adds hex encoded price feed ids to the request, optionally prefixed with `0x`
+        ///
+        /// Sending the request fails with [`HermesError::InvalidFeedId`] listing the invalid ids, without contacting
+        /// hermes.
+        pub fn hex_ids<I>(mut self, ids: I) -> Self
+        where
+            I: IntoIterator,
+            I::Item: AsRef<str>,
+        {
+            for id in ids {
+                match FeedId::parse(id.as_ref()) {
+                    Ok(parsed) => self.query.ids.push(parsed),
+                    Err(err) => self.query.invalid_ids.push((id.as_ref().to_string(), err)),
+                }
+            }
+            self
+        }
+
+        /// Encoding of the binary update data
+        pub fn encoding(mut self, encoding: EncodingType) -> Self {
+            self.query.params.encoding = Some(encoding);
+            self
+        }
+
+        /// Whether hermes should include parsed prices in the response
+        pub fn parsed(mut self, parsed: bool) -> Self {
+            self.query.params.parsed = Some(parsed);
+            self
+        }
+
+        /// Whether hermes should ignore invalid price feed ids instead of failing the request
+        pub fn ignore_invalid(mut self, ignore_invalid: bool) -> Self {
+            self.query.params.ignore_invalid_price_ids = Some(ignore_invalid);
+            self
+        }
+
+        /// Deadline for this request, overriding the client's [`RequestOptions`]
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.query.timeout = Some(timeout);
+            self
+        }
+    };
+}
+
+impl LatestPriceRequest<'_> {
+    common_params!();
+
+    /// Sends the request, split into several when it has more feeds than the client's chunk size
+    pub async fn send(self) -> Result<PriceUpdate, HermesError> {
+        self.query
+            .send_chunked(self.client, "/v2/updates/price/latest")
+            .await
+    }
+}
+
+impl PriceUpdatesByTimeRequest<'_> {
+    common_params!();
+
+    /// Only return benchmark prices which are the initial price updates at the publish time
+    pub fn benchmarks_only(mut self, benchmarks_only: bool) -> Self {
+        self.query.params.benchmarks_only = Some(benchmarks_only);
+        self
+    }
+
+    /// Allow unordered price updates to be included
+    pub fn allow_unordered(mut self, allow_unordered: bool) -> Self {
+        self.query.params.allow_unordered = Some(allow_unordered);
+        self
+    }
+
+    /// Sends the request, split into several when it has more feeds than the client's chunk size
+    pub async fn send(self) -> Result<PriceUpdate, HermesError> {
+        let path = format!("/v2/updates/price/{}", self.publish_time);
+        self.query.send_chunked(self.client, &path).await
+    }
+}
+
+impl LatestTwapsRequest<'_> {
+    common_params!();
+
+    pub async fn send(self) -> Result<TwapsResponse, HermesError> {
+        self.client
+            .misuse_policy
+            .check_twap_window(self.window_seconds)?;
+        let path = format!("/v2/updates/twap/{}/latest", self.window_seconds);
+        let req = self.query.request(self.client, &path)?;
+        Ok(self.client.send(req).await?.json().await?)
+    }
+}
+
+impl HermesClient {
+    /// Builds a request for the latest price updates
+    pub fn latest_price(&self) -> LatestPriceRequest<'_> {
+        LatestPriceRequest {
+            client: self,
+            query: PriceQuery::default(),
+        }
+    }
+
+    /// Builds a request for price updates with a publish time greater than or equal to `publish_time`
+    pub fn price_updates_by_time(&self, publish_time: i64) -> PriceUpdatesByTimeRequest<'_> {
+        PriceUpdatesByTimeRequest {
+            client: self,
+            publish_time,
+            query: PriceQuery::default(),
+        }
+    }
+
+    /// Builds a request for the latest TWAPs calculated over `window_seconds`
+    pub fn latest_twaps(&self, window_seconds: u64) -> LatestTwapsRequest<'_> {
+        LatestTwapsRequest {
+            client: self,
+            window_seconds,
+            query: PriceQuery::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+    };
+
+    /// Returns the url of a server which accepts connections but never responds
+    pub(crate) async fn unresponsive_server() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let client = HermesClient::new(unresponsive_server().await);
+        let timeout = Duration::from_millis(50);
+        let HermesError::Http(err) = client
+            .with_options(RequestOptions::timeout(timeout))
+            .get_price_feeds_metadata(None, None)
+            .await
+            .unwrap_err()
+        else {
+            panic!("expected http error");
+        };
+        assert!(err.is_timeout());
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client
+                .with_options(RequestOptions::timeout(Duration::from_secs(60)))
+                .latest_price()
+                .ids([ETH_USD])
+                .timeout(timeout)
+                .send(),
+        )
+        .await
+        .expect("request timeout should override the client timeout");
+        assert!(matches!(result, Err(HermesError::Http(err)) if err.is_timeout()));
+    }
+
+    #[tokio::test]
+    async fn test_latest_price_request_params() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 1, 1),
+            ),
+            RecordedResponse::json(
+                "/v2/updates/price/100",
+                price_update_json(&[ETH_USD], 1, 100),
+            ),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        client
+            .latest_price()
+            .ids([ETH_USD])
+            .encoding(EncodingType::Base64)
+            .parsed(false)
+            .ignore_invalid(true)
+            .send()
+            .await
+            .unwrap();
+        client
+            .price_updates_by_time(100)
+            .ids([ETH_USD])
+            .benchmarks_only(true)
+            .send()
+            .await
+            .unwrap();
+
+        let requests = mock.requests().await;
+        assert_eq!(
+            requests[0].query.as_deref(),
+            Some(
+                format!(
+                    "encoding=base64&parsed=false&ignore_invalid_price_ids=true&ids%5B%5D={ETH_USD}"
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(
+            requests[1].query.as_deref(),
+            Some(format!("benchmarks_only=true&ids%5B%5D={ETH_USD}").as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hex_ids_validated_before_sending() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD], 1, 1),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let err = client
+            .latest_price()
+            .hex_ids(["eth", &format!("0x{ETH_USD}"), &"g".repeat(64)])
+            .send()
+            .await
+            .unwrap_err();
+        match err {
+            HermesError::InvalidFeedId(invalid) => assert_eq!(
+                invalid,
+                [
+                    ("eth".to_string(), FeedIdError::InvalidLength(3)),
+                    ("g".repeat(64), FeedIdError::InvalidCharacter('g')),
+                ]
+            ),
+            err => panic!("expected invalid feed ids, got {err}"),
+        }
+        assert!(mock.requests().await.is_empty());
+
+        let update = client
+            .latest_price()
+            .hex_ids([format!("0x{ETH_USD}")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(update.parsed.unwrap()[0].id, ETH_USD);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_ids_sent_once() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 1, 1),
+            ),
+            RecordedResponse::json(
+                "/v2/updates/price/100",
+                price_update_json(&[ETH_USD], 1, 100),
+            ),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        let feeds = client
+            .get_latest_price_feeds(&[ETH_USD, ETH_USD])
+            .await
+            .unwrap();
+        assert_eq!(feeds.len(), 1);
+        client
+            .price_updates_by_time(100)
+            .ids([ETH_USD])
+            .hex_ids([ETH_USD.to_hex()])
+            .send()
+            .await
+            .unwrap();
+
+        for request in mock.requests().await {
+            assert_eq!(request.query.unwrap().matches("ids%5B%5D=").count(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_requests() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/updates/price/latest",
+            price_update_json(&[ETH_USD], 1, 1),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .chunking(2, 2)
+            .build()
+            .unwrap();
+        let ids: Vec<_> = (1..=5).map(|i| FeedId::from_bytes([i; 32])).collect();
+
+        let update = client.latest_price().ids(&ids).send().await.unwrap();
+        // one response per chunk, merged
+        assert_eq!(update.parsed.unwrap().len(), 3);
+        assert_eq!(update.binary.data.len(), 3);
+
+        let mut sent: Vec<_> = mock
+            .requests()
+            .await
+            .into_iter()
+            .map(|request| request.query.unwrap().matches("ids%5B%5D=").count())
+            .collect();
+        sent.sort();
+        assert_eq!(sent, [1, 2, 2]);
+    }
+}
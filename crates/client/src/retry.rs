@@ -0,0 +1,311 @@
+//! Retrying REST requests which fail with transient errors
+
+use {
+    crate::{error::HermesError, rng::Rng},
+    std::time::Duration,
+};
+
+/// Retry policy for REST requests, see [`HermesClientBuilder::retry_policy`](crate::builder::HermesClientBuilder::retry_policy)
+///
+/// Connection errors and 5xx responses are retried, with the delay doubling after each attempt. Rate limited
+/// requests are retried after the delay requested by hermes' `Retry-After` header, unless it exceeds
+/// [`RetryPolicy::max_delay`], in which case [`HermesError::RateLimited`] is returned straight away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first request
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_delay: Duration,
+    /// Randomizes each delay between half and the full value, spreading out retries from many clients
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Default policy making at most `max_attempts` attempts
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Delay before the attempt following `attempt`, where the first request is attempt 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.delay_with(attempt, &Rng::from_entropy())
+    }
+
+    /// Delay before the attempt following `attempt`, jittered with values drawn from `rng`
+    pub fn delay_with(&self, attempt: u32, rng: &Rng) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        if self.jitter {
+            delay.mul_f64(0.5 + rng.fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+
+    /// Whether `err` is a transient failure which is worth retrying
+    pub fn is_transient(err: &HermesError) -> bool {
+        match err {
+            HermesError::Http(err) => {
+                is_connect(err) || err.status().is_some_and(|s| s.is_server_error())
+            }
+            HermesError::RateLimited { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_connect(err: &reqwest::Error) -> bool {
+    err.is_connect()
+}
+
+/// The fetch api doesn't distinguish connection failures from other request errors
+#[cfg(target_arch = "wasm32")]
+fn is_connect(err: &reqwest::Error) -> bool {
+    err.is_request()
+}
+
+/// Parses a `Retry-After` header, given either as a number of seconds or an HTTP date
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = parse_http_date(value)?;
+    Some(Duration::from_secs(
+        at.saturating_sub(crate::rt::unix_timestamp()),
+    ))
+}
+
+/// Parses an IMF-fixdate such as `Sun, 06 Nov 1994 08:49:37 GMT` into seconds since the unix epoch
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let mut parts = value.split_once(", ")?.1.split(' ');
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year: u64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|p| p.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if parts.next()? != "GMT" || year < 1970 {
+        return None;
+    }
+    // days since the epoch, from Howard Hinnant's days_from_civil
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146097 + doe).checked_sub(719468)?;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            mock::{MockHermes, RecordedResponse},
+            HermesClient,
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::new(5)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(300))
+            .jitter(false);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(300));
+        assert_eq!(policy.delay(40), Duration::from_millis(300));
+
+        let policy = policy.jitter(true);
+        for attempt in 1..5 {
+            let delay = policy.delay(attempt);
+            assert!(delay >= policy.jitter(false).delay(attempt) / 2);
+            assert!(delay <= policy.jitter(false).delay(attempt));
+        }
+
+        // seeded jitter is reproducible
+        let delays = |rng: Rng| {
+            (1..5)
+                .map(|a| policy.delay_with(a, &rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(delays(Rng::seeded(3)), delays(Rng::seeded(3)));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784111777)
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT"),
+            Some(1709164800)
+        );
+        // dates in the past don't require waiting
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited() {
+        let mut limited = RecordedResponse::status("/v2/price_feeds", 429);
+        limited
+            .headers
+            .insert("retry-after".to_string(), "1".to_string());
+        let mock = MockHermes::start(vec![
+            limited.clone(),
+            limited,
+            RecordedResponse::json("/v2/price_feeds", json!([])),
+        ])
+        .await
+        .unwrap();
+
+        let err = HermesClient::new(mock.url())
+            .get_price_feeds_metadata(None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            HermesError::RateLimited {
+                retry_after: Some(d)
+            } if d == Duration::from_secs(1)
+        ));
+
+        let client = HermesClient::builder(mock.url())
+            .retry_policy(RetryPolicy::new(2))
+            .build()
+            .unwrap();
+        assert!(client.get_price_feeds_metadata(None, None).await.is_ok());
+        assert_eq!(mock.requests().await.len(), 3);
+    }
+
+    async fn retry_after_exceeding_max_delay(retry_after: &str) {
+        let mut limited = RecordedResponse::status("/v2/price_feeds", 429);
+        limited
+            .headers
+            .insert("retry-after".to_string(), retry_after.to_string());
+        let mock = MockHermes::start(vec![
+            limited,
+            RecordedResponse::json("/v2/price_feeds", json!([])),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .retry_policy(RetryPolicy::new(3).max_delay(Duration::from_secs(10)))
+            .build()
+            .unwrap();
+
+        let err = client
+            .get_price_feeds_metadata(None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            HermesError::RateLimited {
+                retry_after: Some(d)
+            } if d > Duration::from_secs(10)
+        ));
+        assert_eq!(mock.requests().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_seconds_exceeding_max_delay() {
+        retry_after_exceeding_max_delay("86400").await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_date_exceeding_max_delay() {
+        retry_after_exceeding_max_delay("Fri, 01 Jan 2100 00:00:00 GMT").await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_failures() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status("/v2/price_feeds", 503),
+            RecordedResponse::status("/v2/price_feeds", 502),
+            RecordedResponse::json("/v2/price_feeds", json!([])),
+            RecordedResponse::status("/v2/updates/publisher_stake_caps/latest", 400),
+        ])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .retry_policy(RetryPolicy::new(3).base_delay(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+
+        let metadata = client.get_price_feeds_metadata(None, None).await.unwrap();
+        assert!(metadata.is_empty());
+        // client errors are not retried
+        assert!(client.get_latest_publisher_stake_caps().await.is_err());
+        assert_eq!(mock.requests().await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let mock = MockHermes::start(vec![RecordedResponse::status("/v2/price_feeds", 500)])
+            .await
+            .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .retry_policy(RetryPolicy::new(2).base_delay(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+
+        let err = client
+            .get_price_feeds_metadata(None, None)
+            .await
+            .unwrap_err();
+        assert!(RetryPolicy::is_transient(&err));
+        assert_eq!(mock.requests().await.len(), 2);
+    }
+}
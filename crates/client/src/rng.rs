@@ -0,0 +1,93 @@
+//! Seedable randomness behind the client's jitter and scheduling, so tests of retries and failover are reproducible
+//!
+//! Clients draw a random seed unless one is set with
+//! [`HermesClientBuilder::seed`](crate::builder::HermesClientBuilder::seed). The seed is reported in
+//! [`Diagnostics`](crate::diagnostics::Diagnostics), so a client which behaved unexpectedly can be rebuilt with the
+//! same sequence of random values.
+
+use {
+    crate::rt,
+    std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Increment of the splitmix64 state
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Source of random values seeded with a known seed, whose clones share the same sequence
+///
+/// Uses splitmix64, which is fast and well distributed but not suitable for cryptography.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    seed: u64,
+    state: Arc<AtomicU64>,
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seed,
+            state: Arc::new(AtomicU64::new(seed)),
+        }
+    }
+
+    /// Generator with a seed taken from the standard library's hash keys and the current time
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(rt::unix_timestamp_millis());
+        Self::seeded(hasher.finish())
+    }
+
+    /// Seed the generator was created with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let mut z = self
+            .state
+            .fetch_add(GOLDEN_GAMMA, Ordering::Relaxed)
+            .wrapping_add(GOLDEN_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Random value in `[0, 1)`
+    pub fn fraction(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng() {
+        let rng = Rng::seeded(7);
+        let values: Vec<_> = (0..4).map(|_| rng.next_u64()).collect();
+        // the same seed reproduces the sequence
+        let replay = Rng::seeded(7);
+        assert_eq!(
+            (0..4).map(|_| replay.next_u64()).collect::<Vec<_>>(),
+            values
+        );
+        assert_ne!(Rng::seeded(8).next_u64(), values[0]);
+
+        // clones continue the shared sequence
+        let clone = replay.clone();
+        assert_eq!(clone.next_u64(), rng.next_u64());
+        assert_eq!(clone.seed(), 7);
+
+        assert!((0..100)
+            .map(|_| rng.fraction())
+            .all(|f| (0.0..1.0).contains(&f)));
+    }
+}
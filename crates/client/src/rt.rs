@@ -0,0 +1,85 @@
+//! Task spawning and timers for the native and wasm32 targets
+
+use std::{future::Future, time::Duration};
+
+/// Handle to a spawned stream task, which can be used to abort it
+#[cfg(not(target_arch = "wasm32"))]
+pub type TaskHandle = tokio::task::JoinHandle<()>;
+
+/// Handle to a spawned stream task, which can be used to abort it
+#[cfg(target_arch = "wasm32")]
+pub type TaskHandle = futures_util::future::AbortHandle;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn spawn(task: impl Future<Output = ()> + Send + 'static) -> TaskHandle {
+    tokio::spawn(task)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn spawn(task: impl Future<Output = ()> + 'static) -> TaskHandle {
+    let (task, handle) = futures_util::future::abortable(task);
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = task.await;
+    });
+    handle
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    futures_timer::Delay::new(duration).await
+}
+
+/// Seconds since the unix epoch
+pub(crate) fn unix_timestamp() -> u64 {
+    unix_timestamp_millis() / 1000
+}
+
+/// Milliseconds since the unix epoch
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unix_timestamp_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Milliseconds since the unix epoch
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn unix_timestamp_millis() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+/// Measures elapsed time, as `Instant` panics on wasm32
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Stopwatch(std::time::Instant);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(std::time::Instant::now())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// Measures elapsed time, as `Instant` panics on wasm32
+#[cfg(target_arch = "wasm32")]
+pub(crate) struct Stopwatch(f64);
+
+#[cfg(target_arch = "wasm32")]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(js_sys::Date::now())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64((js_sys::Date::now() - self.0).max(0.0) / 1000.0)
+    }
+}
@@ -0,0 +1,334 @@
+//! Synthetic price paths for stress testing consumers under conditions which are hard to capture live
+//!
+//! Scenarios are deterministic for a given seed, and produce updates for a [`Backtest`](crate::backtest::Backtest)
+//! or SSE payloads for a mock server.
+//!
+//! ```
+//! use pyth_hermes_rs::{backtest::Backtest, scenario::Scenario, types::FeedId};
+//!
+//! let id = FeedId::from_hex("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace");
+//! let updates = Scenario::gbm(id, 2000.0)
+//!     .volatility(0.01)
+//!     .flash_crash(50, 0.3, 10)
+//!     .stale(80, 5)
+//!     .generate();
+//! assert_eq!(updates.len(), 100);
+//! let backtest = Backtest::new(updates);
+//! ```
+
+use {
+    crate::types::*,
+    serde_json::json,
+    std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    },
+};
+
+/// Generates a price path by geometric brownian motion with injected events
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    id: FeedId,
+    start_price: f64,
+    start_time: i64,
+    interval: i64,
+    steps: usize,
+    drift: f64,
+    volatility: f64,
+    expo: i32,
+    confidence: f64,
+    seed: u64,
+    jumps: Option<(f64, f64)>,
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    FlashCrash {
+        at: usize,
+        depth: f64,
+        recovery: usize,
+    },
+    Stale {
+        from: usize,
+        steps: usize,
+    },
+    WideningConfidence {
+        from: usize,
+        steps: usize,
+        factor: f64,
+    },
+}
+
+impl Scenario {
+    /// 100 one second steps starting at `start_price`, with no drift, 0.1% volatility and confidence per step
+    pub fn gbm(id: FeedId, start_price: f64) -> Self {
+        Self {
+            id,
+            start_price,
+            start_time: 1_700_000_000,
+            interval: 1,
+            steps: 100,
+            drift: 0.0,
+            volatility: 0.001,
+            expo: -8,
+            confidence: 0.001,
+            seed: 0,
+            jumps: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Publish time of the first update and seconds between updates
+    pub fn timing(mut self, start_time: i64, interval: i64) -> Self {
+        self.start_time = start_time;
+        self.interval = interval;
+        self
+    }
+
+    /// Expected log return per step
+    pub fn drift(mut self, drift: f64) -> Self {
+        self.drift = drift;
+        self
+    }
+
+    /// Standard deviation of the log return per step
+    pub fn volatility(mut self, volatility: f64) -> Self {
+        self.volatility = volatility;
+        self
+    }
+
+    /// Exponent of the generated prices
+    pub fn expo(mut self, expo: i32) -> Self {
+        self.expo = expo;
+        self
+    }
+
+    /// Confidence interval as a fraction of the price
+    pub fn confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Each step has `probability` of a jump of up to `size` as a fraction of the price, in either direction
+    pub fn jumps(mut self, probability: f64, size: f64) -> Self {
+        self.jumps = Some((probability, size));
+        self
+    }
+
+    /// Drops the price by `depth` as a fraction at step `at`, recovering linearly over `recovery` steps
+    pub fn flash_crash(mut self, at: usize, depth: f64, recovery: usize) -> Self {
+        self.events.push(Event::FlashCrash {
+            at,
+            depth,
+            recovery,
+        });
+        self
+    }
+
+    /// Repeats the last update, with its original publish time, for `steps` steps starting at `from`
+    pub fn stale(mut self, from: usize, steps: usize) -> Self {
+        self.events.push(Event::Stale { from, steps });
+        self
+    }
+
+    /// Widens the confidence interval linearly to `factor` times its normal width over `steps` steps from `from`
+    pub fn widening_confidence(mut self, from: usize, steps: usize, factor: f64) -> Self {
+        self.events.push(Event::WideningConfidence {
+            from,
+            steps,
+            factor,
+        });
+        self
+    }
+
+    pub fn generate(&self) -> Vec<ParsedPriceUpdate> {
+        let mut rng = Rng::new(self.seed);
+        let scale = 10_f64.powi(-self.expo);
+        let mut price = self.start_price;
+        let mut updates: Vec<ParsedPriceUpdate> = Vec::with_capacity(self.steps);
+        for step in 0..self.steps {
+            if step > 0 {
+                let log_return =
+                    self.drift - self.volatility.powi(2) / 2.0 + self.volatility * rng.normal();
+                price *= log_return.exp();
+                if let Some((probability, size)) = self.jumps {
+                    if rng.uniform() < probability {
+                        price *= 1.0 + size * (rng.uniform() * 2.0 - 1.0);
+                    }
+                }
+            }
+            let stale = self.events.iter().any(|event| {
+                matches!(*event, Event::Stale { from, steps } if (from..from + steps).contains(&step))
+            });
+            if let (true, Some(last)) = (stale, updates.last()) {
+                updates.push(last.clone());
+                continue;
+            }
+
+            let (mut shown, mut confidence) = (price, self.confidence);
+            for event in &self.events {
+                match *event {
+                    Event::FlashCrash {
+                        at,
+                        depth,
+                        recovery,
+                    } if step >= at && step <= at + recovery => {
+                        let remaining = 1.0 - (step - at) as f64 / (recovery + 1) as f64;
+                        shown *= 1.0 - depth * remaining;
+                    }
+                    Event::WideningConfidence {
+                        from,
+                        steps,
+                        factor,
+                    } if step >= from => {
+                        let progress = ((step - from + 1) as f64 / steps.max(1) as f64).min(1.0);
+                        confidence *= 1.0 + (factor - 1.0) * progress;
+                    }
+                    _ => {}
+                }
+            }
+
+            let publish_time = self.start_time + step as i64 * self.interval;
+            let rpc_price = RpcPrice {
+                price: ((shown * scale).round() as i64).to_string(),
+                conf: ((shown * confidence * scale).round() as i64).to_string(),
+                expo: self.expo,
+                publish_time,
+            };
+            updates.push(ParsedPriceUpdate {
+                id: self.id,
+                price: rpc_price.clone(),
+                ema_price: rpc_price,
+                metadata: Some(RpcPriceFeedMetadata {
+                    emitter_chain: None,
+                    prev_publish_time: updates.last().map(|u| u.price.publish_time),
+                    price_service_receive_time: Some(publish_time),
+                    slot: Some(step as i64),
+                }),
+                snapshot: false,
+            });
+        }
+        updates
+    }
+
+    /// Generated updates as SSE payloads in the format streamed by hermes, one update per event
+    pub fn events(&self) -> Vec<String> {
+        self.generate()
+            .iter()
+            .map(|update| {
+                let price = |price: &RpcPrice| {
+                    json!({
+                        "price": price.price,
+                        "conf": price.conf,
+                        "expo": price.expo,
+                        "publish_time": price.publish_time,
+                    })
+                };
+                json!({
+                    "binary": {"encoding": "hex", "data": []},
+                    "parsed": [{
+                        "id": update.id,
+                        "price": price(&update.price),
+                        "ema_price": price(&update.ema_price),
+                        "metadata": update.metadata,
+                    }],
+                })
+                .to_string()
+            })
+            .collect()
+    }
+}
+
+/// SplitMix64, deterministic for a given seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Standard normal by the Box-Muller transform
+    fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.uniform();
+        let v = self.uniform();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::feed_ids::ETH_USD};
+
+    fn prices(updates: &[ParsedPriceUpdate]) -> Vec<f64> {
+        updates.iter().map(|u| u.price.to_f64().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_scenario_events() {
+        let calm = Scenario::gbm(ETH_USD, 100.0).volatility(0.0).steps(20);
+        assert!(prices(&calm.generate()).iter().all(|p| *p == 100.0));
+
+        let crash = calm.clone().flash_crash(5, 0.5, 4).generate();
+        assert_eq!(
+            prices(&crash)[4..11],
+            [100.0, 50.0, 60.0, 70.0, 80.0, 90.0, 100.0]
+        );
+
+        let stale = calm.clone().stale(10, 3).generate();
+        assert_eq!(stale[12].price.publish_time, stale[9].price.publish_time);
+        assert_eq!(
+            stale[13].price.publish_time,
+            stale[9].price.publish_time + 4
+        );
+
+        let wide = calm.clone().widening_confidence(0, 10, 5.0).generate();
+        assert_eq!(wide[9].price.conf, "50000000");
+        assert_eq!(wide[19].price.conf, "50000000");
+    }
+
+    #[test]
+    fn test_scenario_deterministic() {
+        let scenario = Scenario::gbm(ETH_USD, 100.0)
+            .volatility(0.05)
+            .jumps(0.1, 0.2)
+            .seed(7);
+        assert_eq!(prices(&scenario.generate()), prices(&scenario.generate()));
+        assert_ne!(
+            prices(&scenario.generate()),
+            prices(&scenario.clone().seed(8).generate())
+        );
+
+        let events = scenario.events();
+        let update: PriceUpdate = serde_json::from_str(&events[1]).unwrap();
+        assert_eq!(
+            update.parsed.unwrap()[0].price.price,
+            scenario.generate()[1].price.price
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Detection of fields returned by hermes which are not modelled by the crate's types
+
+use {
+    crate::{error::HermesError, types::*, HermesClient},
+    serde::de::DeserializeOwned,
+};
+
+/// Differences between a hermes response and the crate's types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDrift {
+    /// Path of the endpoint the response was fetched from
+    pub endpoint: String,
+    /// Paths of fields present in the response but ignored during deserialization
+    pub unknown_fields: Vec<String>,
+    /// Set when the response could not be deserialized at all
+    pub error: Option<String>,
+}
+
+/// Deserializes `raw` into `T`, returning the paths of any fields which were ignored
+pub fn unknown_fields<T: DeserializeOwned>(
+    raw: &[u8],
+) -> Result<(T, Vec<String>), serde_json::Error> {
+    let mut fields = Vec::new();
+    let mut de = serde_json::Deserializer::from_slice(raw);
+    let value = serde_ignored::deserialize(&mut de, |path| fields.push(path.to_string()))?;
+    Ok((value, fields))
+}
+
+impl HermesClient {
+    /// Fetches responses from each REST endpoint and reports fields which are not modelled by the crate's types
+    ///
+    /// Only endpoints with drift are returned, so an empty result means the types are up to date.
+    pub async fn schema_check(&self) -> Result<Vec<SchemaDrift>, HermesError> {
+        let mut drift = Vec::new();
+
+        let raw = self.get_raw("/v2/price_feeds", &[]).await?;
+        let metadata = check::<Vec<PriceFeedMetadata>>("/v2/price_feeds", &raw, &mut drift);
+        let Some(id) = metadata.and_then(|m| m.first().map(|m| m.id.to_string())) else {
+            return Ok(drift);
+        };
+        let ids = [("ids[]", id.as_str())];
+
+        let path = "/v2/updates/price/latest";
+        let raw = self.get_raw(path, &ids).await?;
+        let latest = check::<PriceUpdate>(path, &raw, &mut drift);
+
+        let publish_time = latest
+            .and_then(|update| update.parsed)
+            .and_then(|parsed| parsed.first().map(|feed| feed.price.publish_time));
+        if let Some(publish_time) = publish_time {
+            let path = format!("/v2/updates/price/{publish_time}");
+            let raw = self.get_raw(&path, &ids).await?;
+            check::<PriceUpdate>(&path, &raw, &mut drift);
+        }
+
+        let path = "/v2/updates/twap/300/latest";
+        let raw = self.get_raw(path, &ids).await?;
+        check::<TwapsResponse>(path, &raw, &mut drift);
+
+        let path = "/v2/updates/publisher_stake_caps/latest";
+        let raw = self.get_raw(path, &[]).await?;
+        check::<LatestPublisherStakeCapsUpdateDataResponse>(path, &raw, &mut drift);
+
+        Ok(drift)
+    }
+
+    async fn get_raw(&self, path: &str, query: &[(&str, &str)]) -> Result<Vec<u8>, HermesError> {
+        let url = self.url(path)?;
+        let resp = self.send(self.http.get(url).query(query)).await?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+fn check<T: DeserializeOwned>(
+    endpoint: &str,
+    raw: &[u8],
+    drift: &mut Vec<SchemaDrift>,
+) -> Option<T> {
+    match unknown_fields::<T>(raw) {
+        Ok((value, unknown_fields)) => {
+            if !unknown_fields.is_empty() {
+                drift.push(SchemaDrift {
+                    endpoint: endpoint.to_string(),
+                    unknown_fields,
+                    error: None,
+                });
+            }
+            Some(value)
+        }
+        Err(err) => {
+            drift.push(SchemaDrift {
+                endpoint: endpoint.to_string(),
+                unknown_fields: Vec::new(),
+                error: Some(err.to_string()),
+            });
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{MockHermes, RecordedResponse},
+        },
+        serde_json::json,
+    };
+
+    #[tokio::test]
+    async fn test_schema_check() {
+        let price = json!({"price": "1", "conf": "1", "expo": -8, "publish_time": 100});
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/price_feeds",
+                json!([{"id": ETH_USD.to_hex(), "attributes": {}, "new_field": 1}]),
+            ),
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                json!({
+                    "binary": {"encoding": "hex", "data": []},
+                    "parsed": [{"id": ETH_USD.to_hex(), "price": price, "ema_price": price, "extra": true}]
+                }),
+            ),
+            RecordedResponse::json(
+                "/v2/updates/price/100",
+                json!({"binary": {"encoding": "hex", "data": []}}),
+            ),
+            RecordedResponse::json("/v2/updates/twap/300/latest", json!({"parsed": []})),
+            RecordedResponse::json(
+                "/v2/updates/publisher_stake_caps/latest",
+                json!({"binary": {"encoding": "hex", "data": []}}),
+            ),
+        ])
+        .await
+        .unwrap();
+
+        let drift = HermesClient::new(mock.url()).schema_check().await.unwrap();
+        assert_eq!(drift.len(), 3);
+        assert_eq!(drift[0].endpoint, "/v2/price_feeds");
+        assert_eq!(drift[0].unknown_fields, vec!["0.new_field"]);
+        assert_eq!(drift[1].endpoint, "/v2/updates/price/latest");
+        assert_eq!(drift[1].unknown_fields, vec!["parsed.?.0.extra"]);
+        assert_eq!(drift[2].endpoint, "/v2/updates/twap/300/latest");
+        assert!(drift[2].error.is_some());
+    }
+}
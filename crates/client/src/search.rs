@@ -0,0 +1,175 @@
+//! Local search over feed metadata, for offering matching feeds as a user types
+//!
+//! Queries match the `symbol`, `display_symbol`, `base`, `quote_currency` and `description` attributes, ignoring
+//! case. Exact matches rank above prefixes, which rank above matches at the start of a word, substrings, and finally
+//! fuzzy matches where the query's characters appear in order, such as `btcusd` for `Crypto.BTC/USD`.
+
+use {crate::types::PriceFeedMetadata, std::sync::Arc};
+
+/// Attributes searched, with the percentage their scores are weighted by and whether they match fuzzily
+const FIELDS: [(&str, u32, bool); 5] = [
+    ("symbol", 100, true),
+    ("display_symbol", 100, true),
+    ("base", 100, true),
+    ("quote_currency", 50, false),
+    ("description", 50, false),
+];
+
+/// Search index over a set of feeds
+#[derive(Debug, Clone)]
+pub struct FeedIndex {
+    feeds: Arc<[PriceFeedMetadata]>,
+    /// Lowercased searchable attributes of each feed, with their weight and whether they match fuzzily
+    fields: Vec<Vec<(String, u32, bool)>>,
+}
+
+/// A feed matching a search, see [`FeedIndex::search`]
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub feed: PriceFeedMetadata,
+    /// Higher scores are better matches
+    pub score: u32,
+}
+
+impl FeedIndex {
+    pub fn new(feeds: impl Into<Arc<[PriceFeedMetadata]>>) -> Self {
+        let feeds = feeds.into();
+        let fields = feeds
+            .iter()
+            .map(|feed| {
+                FIELDS
+                    .iter()
+                    .filter_map(|&(name, weight, fuzzy)| {
+                        Some((feed.attributes.get(name)?.to_lowercase(), weight, fuzzy))
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { feeds, fields }
+    }
+
+    /// Up to `limit` feeds matching `query`, best matches first
+    ///
+    /// Feeds with equal scores are ordered by symbol, shortest first. An empty query matches nothing.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut hits: Vec<_> = self
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(|(index, fields)| {
+                let score = fields
+                    .iter()
+                    .filter_map(|(text, weight, fuzzy)| {
+                        Some(match_score(text, &query, *fuzzy)? * weight / 100)
+                    })
+                    .max()?;
+                Some((index, score))
+            })
+            .collect();
+        let symbol = |index: usize| {
+            self.feeds[index]
+                .attributes
+                .get("symbol")
+                .map_or("", String::as_str)
+        };
+        hits.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| symbol(*a).len().cmp(&symbol(*b).len()))
+                .then_with(|| symbol(*a).cmp(symbol(*b)))
+        });
+        hits.into_iter()
+            .take(limit)
+            .map(|(index, score)| SearchHit {
+                feed: self.feeds[index].clone(),
+                score,
+            })
+            .collect()
+    }
+}
+
+/// Scores how well `query` matches `text`, both lowercased
+fn match_score(text: &str, query: &str, fuzzy: bool) -> Option<u32> {
+    if text == query {
+        Some(1000)
+    } else if text.starts_with(query) {
+        Some(800)
+    } else if text
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.starts_with(query))
+    {
+        Some(600)
+    } else if text.contains(query) {
+        Some(400)
+    } else if fuzzy {
+        fuzzy_score(text, query)
+    } else {
+        None
+    }
+}
+
+/// Scores `query` as a subsequence of `text`, penalizing characters skipped between matches
+fn fuzzy_score(text: &str, query: &str) -> Option<u32> {
+    let mut text = text.chars();
+    let mut skipped = 0;
+    for wanted in query.chars() {
+        let mut gap = 0;
+        loop {
+            match text.next() {
+                Some(c) if c == wanted => break,
+                Some(_) => gap += 1,
+                None => return None,
+            }
+        }
+        skipped += gap;
+    }
+    Some(200_u32.saturating_sub(skipped * 10).max(1))
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::types::FeedId, std::collections::HashMap};
+
+    fn feed(byte: u8, symbol: &str, base: &str, quote: &str) -> PriceFeedMetadata {
+        PriceFeedMetadata {
+            id: FeedId::from_bytes([byte; 32]),
+            attributes: HashMap::from([
+                ("symbol".to_string(), symbol.to_string()),
+                ("base".to_string(), base.to_string()),
+                ("quote_currency".to_string(), quote.to_string()),
+                ("description".to_string(), format!("{base} / {quote}")),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let index = FeedIndex::new(vec![
+            feed(1, "Crypto.ETH/BTC", "ETH", "BTC"),
+            feed(2, "Crypto.WBTC/USD", "WBTC", "USD"),
+            feed(3, "Crypto.BTC/USD", "BTC", "USD"),
+            feed(4, "Crypto.ETH/USD", "ETH", "USD"),
+        ]);
+        let symbols = |query: &str| {
+            index
+                .search(query, 10)
+                .into_iter()
+                .map(|hit| hit.feed.attributes["symbol"].clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            symbols("BTC"),
+            ["Crypto.BTC/USD", "Crypto.ETH/BTC", "Crypto.WBTC/USD"]
+        );
+        assert_eq!(symbols("crypto.e"), ["Crypto.ETH/BTC", "Crypto.ETH/USD"]);
+        assert_eq!(symbols("btcusd"), ["Crypto.BTC/USD", "Crypto.WBTC/USD"]);
+        assert!(symbols("sol").is_empty());
+        assert!(symbols(" ").is_empty());
+        assert_eq!(index.search("usd", 1).len(), 1);
+    }
+}
@@ -0,0 +1,481 @@
+//! A single price update stream shared by several parts of an application
+//!
+//! Each [`SharedSubscription`] receives the updates of its own feeds, while the underlying stream subscribes to
+//! every feed requested by any of them once. Feeds are added to the running stream as subscriptions need them and
+//! removed once none does, see [`StreamHandle::add_feeds`], and the stream stops along with its last subscription.
+//! Should the stream stop on its own, e.g. after [`StreamOptions::reconnect`] gives up, every subscription ends, and
+//! subscribing again starts a new stream. Each subscription holds up to [`StreamOptions::capacity`] updates, further
+//! ones are handled as [`StreamOptions::backpressure`] dictates, where [`Backpressure::Block`] pauses the stream for
+//! every subscription until the slowest catches up.
+//! Consumers only interested in the latest price of a feed can watch it with [`SharedStream::subscribe_feed`].
+//!
+//! ```no_run
+//! # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::{feed_ids, stream::StreamOptions};
+//!
+//! let shared = client.shared_stream(StreamOptions::default());
+//! let mut liquidations = shared.subscribe(&[feed_ids::BTC_USD, feed_ids::ETH_USD]).await?;
+//! let mut quotes = shared.subscribe(&[feed_ids::ETH_USD]).await?;
+//! while let Some(update) = liquidations.next().await {
+//!     println!("{}: {}", update.id, update.price.price);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Backpressure::Block`]: crate::stream::Backpressure::Block
+
+use {
+    crate::{
+        diagnostics::SharedStreamDiagnostics,
+        error::HermesError,
+        request::unique_ids,
+        rt,
+        stream::{forward, Queued, Release, StreamBatch, StreamHandle, StreamOptions, StreamStats},
+        types::{FeedId, ParsedPriceUpdate},
+        HermesClient,
+    },
+    futures_util::{future, Stream},
+    std::{
+        pin::{pin, Pin},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex, MutexGuard,
+        },
+        task::{Context, Poll},
+    },
+    tokio::sync::{mpsc, watch},
+    tokio_util::sync::{CancellationToken, DropGuard},
+};
+
+/// Price update stream fanned out to any number of subscriptions, see [`HermesClient::shared_stream`]
+///
+/// Clones share the same stream.
+#[derive(Clone)]
+pub struct SharedStream {
+    inner: Arc<Inner>,
+}
+
+pub(crate) struct Inner {
+    client: HermesClient,
+    options: StreamOptions,
+    /// Serializes subscribing, which may start the stream
+    starting: tokio::sync::Mutex<()>,
+    handle: Mutex<Option<StreamHandle>>,
+    /// Shared with the stream's callback fanning updates out
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    next_id: AtomicU64,
+}
+
+struct Subscriber {
+    id: u64,
+    ids: Vec<FeedId>,
+    sink: Sink,
+}
+
+/// Where a subscriber's updates are delivered
+enum Sink {
+    /// Queue of the task forwarding updates to a [`SharedSubscription`]
+    Updates(mpsc::UnboundedSender<Queued<Vec<ParsedPriceUpdate>>>),
+    Latest {
+        sender: Arc<watch::Sender<Option<ParsedPriceUpdate>>>,
+        /// Cancels the token of the task watching for the receivers to be dropped once the subscriber is removed
+        _removed: DropGuard,
+    },
+}
+
+impl Sink {
+    /// Delivers the updates of a message among `updates` which are of `ids`
+    fn send(&self, ids: &[FeedId], updates: &[ParsedPriceUpdate], release: &Option<Arc<Release>>) {
+        let mut updates = updates
+            .iter()
+            .filter(|update| ids.contains(&update.id))
+            .cloned()
+            .peekable();
+        if updates.peek().is_none() {
+            return;
+        }
+        match self {
+            Self::Updates(queue) => {
+                let _ = queue.send(Queued {
+                    updates: updates.collect(),
+                    release: release.clone(),
+                });
+            }
+            Self::Latest { sender, .. } => {
+                for update in updates {
+                    sender.send_replace(Some(update));
+                }
+            }
+        }
+    }
+}
+
+/// Price updates of a subscription to a [`SharedStream`]
+///
+/// Dropping the subscription unsubscribes the stream from the feeds no other subscription needs.
+#[must_use = "the subscription is removed when dropped"]
+pub struct SharedSubscription {
+    id: u64,
+    updates: mpsc::Receiver<ParsedPriceUpdate>,
+    stream: SharedStream,
+}
+
+impl HermesClient {
+    /// Stream of price updates shared by subscriptions to overlapping feeds, connecting with `options` once
+    /// subscribed to
+    pub fn shared_stream(&self, options: StreamOptions) -> SharedStream {
+        let inner = Arc::new(Inner {
+            client: self.clone(),
+            options,
+            starting: tokio::sync::Mutex::new(()),
+            handle: Mutex::new(None),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            next_id: AtomicU64::new(1),
+        });
+        self.shared_streams.register(&inner);
+        SharedStream { inner }
+    }
+}
+
+impl SharedStream {
+    /// Subscribes to the updates of `ids`, starting the stream or adding the feeds it isn't subscribed to yet
+    ///
+    /// Updates received before subscribing aren't delivered, and adding feeds reconnects the stream as described by
+    /// [`StreamHandle::add_feeds`].
+    pub async fn subscribe(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> Result<SharedSubscription, HermesError> {
+        self.inner.client.misuse_policy.check_ids(ids)?;
+        let (queue, queued) = mpsc::unbounded_channel();
+        let (sender, updates) = mpsc::channel(self.inner.options.capacity.max(1));
+        let id = self
+            .add_subscriber(unique_ids(ids), Sink::Updates(queue))
+            .await?;
+        // ends once the subscriber is removed or the subscription dropped
+        drop(rt::spawn(forward(
+            queued,
+            sender,
+            self.inner.options.backpressure,
+            || {},
+        )));
+        Ok(SharedSubscription {
+            id,
+            updates,
+            stream: self.clone(),
+        })
+    }
+
+    /// Watches the latest price of `id`, which is `None` until the stream receives an update of the feed
+    ///
+    /// The feed is subscribed to like [`SharedStream::subscribe`], until every receiver is dropped.
+    pub async fn subscribe_feed(
+        &self,
+        id: FeedId,
+    ) -> Result<watch::Receiver<Option<ParsedPriceUpdate>>, HermesError> {
+        let (sender, latest) = watch::channel(None);
+        let sender = Arc::new(sender);
+        let removed = CancellationToken::new();
+        let sink = Sink::Latest {
+            sender: sender.clone(),
+            _removed: removed.clone().drop_guard(),
+        };
+        let subscriber = self.add_subscriber(vec![id], sink).await?;
+        let stream = self.clone();
+        drop(rt::spawn(async move {
+            // dropping the sender once the subscriber is removed ends the receivers' updates
+            future::select(pin!(sender.closed()), pin!(removed.cancelled())).await;
+            stream.unsubscribe(subscriber);
+        }));
+        Ok(latest)
+    }
+
+    /// Registers a subscriber of `ids`, starting the stream or adding the feeds it isn't subscribed to yet
+    async fn add_subscriber(&self, ids: Vec<FeedId>, sink: Sink) -> Result<u64, HermesError> {
+        let _starting = self.inner.starting.lock().await;
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.inner.subscribers).push(Subscriber { id, ids, sink });
+        if let Err(err) = self.start().await {
+            self.unsubscribe(id);
+            return Err(err);
+        }
+        Ok(id)
+    }
+
+    /// Starts the stream unless it's running, and subscribes it to the feeds of every subscriber
+    async fn start(&self) -> Result<(), HermesError> {
+        let running = lock(&self.inner.handle)
+            .as_ref()
+            .is_some_and(|handle| handle.stats().is_running());
+        if !running {
+            let subscribers = self.inner.subscribers.clone();
+            let options = self.inner.options.clone();
+            let permits = options.backpressure.permits(options.capacity.max(1));
+            let handle = self
+                .inner
+                .client
+                .spawn_stream(
+                    &self.feeds(),
+                    options,
+                    true,
+                    permits.clone(),
+                    Arc::new(StreamStats::new()),
+                    move |batch: StreamBatch| {
+                        let release = Release::of(&batch, &permits);
+                        let updates: Vec<ParsedPriceUpdate> = batch.into_updates().collect();
+                        for subscriber in lock(&subscribers).iter() {
+                            subscriber.sink.send(&subscriber.ids, &updates, &release);
+                        }
+                    },
+                )
+                .await?;
+            let stream_id = handle.id();
+            let finished = handle.finished();
+            if let Some(stopped) = lock(&self.inner.handle).replace(handle) {
+                stopped.abort();
+            }
+            let inner = Arc::downgrade(&self.inner);
+            drop(rt::spawn(async move {
+                finished.cancelled().await;
+                if let Some(inner) = inner.upgrade() {
+                    SharedStream { inner }.close(stream_id).await;
+                }
+            }));
+        }
+        // catches up with subscribers removed while the stream was starting
+        self.update_feeds()
+    }
+
+    /// Removes every subscriber, ending their subscriptions, once stream `id` has stopped on its own
+    async fn close(&self, id: u64) {
+        // a stream started meanwhile by a new subscriber serves the existing ones too
+        let _starting = self.inner.starting.lock().await;
+        if lock(&self.inner.handle).as_ref().map(StreamHandle::id) != Some(id) {
+            return;
+        }
+        let closed = std::mem::take(&mut *lock(&self.inner.subscribers));
+        // only removes feeds, which can't fail
+        let _ = self.update_feeds();
+        drop(closed);
+    }
+
+    /// Removes a subscriber, unsubscribing the stream from the feeds no other one needs
+    fn unsubscribe(&self, id: u64) {
+        lock(&self.inner.subscribers).retain(|subscriber| subscriber.id != id);
+        // only removes feeds, which can't fail
+        let _ = self.update_feeds();
+    }
+
+    /// Feeds needed by the stream's subscriptions
+    pub fn feeds(&self) -> Vec<FeedId> {
+        let mut feeds = Vec::new();
+        for subscriber in lock(&self.inner.subscribers).iter() {
+            for id in &subscriber.ids {
+                if !feeds.contains(id) {
+                    feeds.push(*id);
+                }
+            }
+        }
+        feeds
+    }
+
+    /// Number of subscriptions sharing the stream
+    pub fn subscriber_count(&self) -> usize {
+        lock(&self.inner.subscribers).len()
+    }
+
+    /// Subscribes the running stream to the feeds its subscriptions need, stopping it once there are none
+    fn update_feeds(&self) -> Result<(), HermesError> {
+        let feeds = self.feeds();
+        let mut handle = lock(&self.inner.handle);
+        if feeds.is_empty() {
+            if let Some(handle) = handle.take() {
+                handle.stop();
+            }
+            return Ok(());
+        }
+        let Some(handle) = handle.as_ref() else {
+            return Ok(());
+        };
+        let unneeded: Vec<FeedId> = handle
+            .feeds()
+            .into_iter()
+            .filter(|id| !feeds.contains(id))
+            .collect();
+        handle.remove_feeds(&unneeded);
+        handle.add_feeds(&feeds)
+    }
+}
+
+impl Inner {
+    pub(crate) fn diagnostics(&self) -> SharedStreamDiagnostics {
+        let subscribers = lock(&self.subscribers);
+        let mut feeds: Vec<FeedId> = subscribers
+            .iter()
+            .flat_map(|subscriber| subscriber.ids.iter().copied())
+            .collect();
+        feeds.sort_unstable();
+        feeds.dedup();
+        SharedStreamDiagnostics {
+            stream_id: lock(&self.handle).as_ref().map(StreamHandle::id),
+            subscribers: subscribers.len(),
+            feed_count: feeds.len(),
+        }
+    }
+}
+
+impl SharedSubscription {
+    /// Waits for the next update of the subscription's feeds, returns `None` once the stream has stopped
+    pub async fn next(&mut self) -> Option<ParsedPriceUpdate> {
+        self.updates.recv().await
+    }
+
+    /// Returns the next update if one has already been received
+    pub fn try_next(&mut self) -> Option<ParsedPriceUpdate> {
+        self.updates.try_recv().ok()
+    }
+
+    /// Stream the subscription receives updates from
+    pub fn stream(&self) -> &SharedStream {
+        &self.stream
+    }
+}
+
+impl Stream for SharedSubscription {
+    type Item = ParsedPriceUpdate;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.updates.poll_recv(cx)
+    }
+}
+
+impl Drop for SharedSubscription {
+    fn drop(&mut self) {
+        self.stream.unsubscribe(self.id);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::{BTC_USD, ETH_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+            stream::ReconnectPolicy,
+        },
+        std::time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_shared_stream() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD, BTC_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        let shared = HermesClient::new(mock.url()).shared_stream(StreamOptions::default());
+
+        let mut eth = shared.subscribe(&[ETH_USD]).await.unwrap();
+        assert_eq!(eth.next().await.unwrap().id, ETH_USD);
+        let mut both = shared.subscribe(&[BTC_USD, ETH_USD]).await.unwrap();
+        assert_eq!(shared.feeds(), [ETH_USD, BTC_USD]);
+        assert_eq!(shared.subscriber_count(), 2);
+        // updates of the added feed arrive once the stream has resubscribed
+        while both.next().await.unwrap().id != BTC_USD {}
+        // only updates of its own feeds reach a subscription
+        while let Some(update) = eth.try_next() {
+            assert_eq!(update.id, ETH_USD);
+        }
+
+        // every feed is streamed over a single connection at a time
+        let queries: Vec<String> = mock
+            .requests()
+            .await
+            .into_iter()
+            .filter_map(|request| request.query)
+            .collect();
+        assert_eq!(queries[0], format!("ids%5B%5D={ETH_USD}"));
+        assert_eq!(
+            queries.last().unwrap(),
+            &format!("ids%5B%5D={ETH_USD}&ids%5B%5D={BTC_USD}")
+        );
+
+        drop(eth);
+        assert_eq!(shared.feeds(), [BTC_USD, ETH_USD]);
+        drop(both);
+        assert_eq!(shared.subscriber_count(), 0);
+        // the stream stops along with its last subscription
+        assert!(lock(&shared.inner.handle).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_feed() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![
+                price_update_json(&[ETH_USD, BTC_USD], 1, 1).to_string(),
+                price_update_json(&[ETH_USD, BTC_USD], 2, 2).to_string(),
+            ],
+        )])
+        .await
+        .unwrap();
+        let shared = HermesClient::new(mock.url()).shared_stream(StreamOptions::default());
+
+        let mut eth = shared.subscribe_feed(ETH_USD).await.unwrap();
+        let btc = shared.subscribe_feed(BTC_USD).await.unwrap();
+        assert_eq!(shared.feeds(), [ETH_USD, BTC_USD]);
+        let latest = eth
+            .wait_for(|update| update.as_ref().is_some_and(|u| u.price.price == "2"))
+            .await
+            .unwrap()
+            .clone()
+            .unwrap();
+        assert_eq!(latest.id, ETH_USD);
+
+        // the feed is unsubscribed from once its receivers are dropped
+        drop(btc);
+        while shared.feeds() != [ETH_USD] {
+            tokio::task::yield_now().await;
+        }
+        drop(eth);
+        while shared.subscriber_count() > 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_gives_up() {
+        let mock = MockHermes::start(vec![RecordedResponse::status(
+            "/v2/updates/price/stream",
+            503,
+        )])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            reconnect: ReconnectPolicy::new(2).base_delay(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let shared = HermesClient::new(mock.url()).shared_stream(options);
+
+        let mut eth = shared.subscribe(&[ETH_USD]).await.unwrap();
+        let mut btc = shared.subscribe_feed(BTC_USD).await.unwrap();
+        // every subscription ends once the stream gives up
+        assert!(eth.next().await.is_none());
+        assert!(btc.changed().await.is_err());
+        assert_eq!(shared.subscriber_count(), 0);
+        assert!(lock(&shared.inner.handle).is_none());
+
+        // subscribing again starts a new stream
+        let _eth = shared.subscribe(&[ETH_USD]).await.unwrap();
+        assert!(lock(&shared.inner.handle)
+            .as_ref()
+            .is_some_and(|handle| handle.stats().is_running()));
+    }
+}
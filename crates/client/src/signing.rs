@@ -0,0 +1,124 @@
+//! Request signing for gateway fronted deployments
+
+use {
+    crate::{error::BoxError, rt},
+    hmac::{Hmac, Mac},
+    reqwest::{header::HeaderValue, Request},
+    sha2::Sha256,
+    std::fmt::Write,
+};
+
+/// Signs every outgoing REST request and SSE connection, after auth headers have been attached
+pub trait RequestSigner: Send + Sync {
+    fn sign(&self, request: &mut Request) -> Result<(), BoxError>;
+}
+
+/// Signs requests with an HMAC-SHA256 over `{method}\n{path and query}\n{timestamp}`
+///
+/// The unix timestamp in seconds is sent in the `x-timestamp` header and the hex encoded signature in the
+/// `x-signature` header, both header names can be overridden.
+pub struct HmacSigner {
+    key: Vec<u8>,
+    signature_header: &'static str,
+    timestamp_header: &'static str,
+}
+
+impl HmacSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            signature_header: "x-signature",
+            timestamp_header: "x-timestamp",
+        }
+    }
+
+    pub fn with_headers(
+        mut self,
+        signature_header: &'static str,
+        timestamp_header: &'static str,
+    ) -> Self {
+        self.signature_header = signature_header;
+        self.timestamp_header = timestamp_header;
+        self
+    }
+
+    /// Returns the hex encoded signature for a request
+    pub fn signature(&self, method: &str, path_and_query: &str, timestamp: u64) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("hmac accepts any key length");
+        mac.update(format!("{method}\n{path_and_query}\n{timestamp}").as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, b| {
+                let _ = write!(hex, "{b:02x}");
+                hex
+            })
+    }
+}
+
+impl RequestSigner for HmacSigner {
+    fn sign(&self, request: &mut Request) -> Result<(), BoxError> {
+        let timestamp = rt::unix_timestamp();
+        let url = request.url();
+        let path_and_query = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let signature = self.signature(request.method().as_str(), &path_and_query, timestamp);
+        let headers = request.headers_mut();
+        headers.insert(self.timestamp_header, HeaderValue::from(timestamp));
+        headers.insert(self.signature_header, HeaderValue::from_str(&signature)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            mock::{MockHermes, RecordedResponse},
+            HermesClient,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_hmac_signer() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/price_feeds",
+            serde_json::json!([]),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .request_signer(HmacSigner::new("secret"))
+            .build()
+            .unwrap();
+        client
+            .get_price_feeds_metadata(Some("btc"), None)
+            .await
+            .unwrap();
+
+        let requests = mock.requests().await;
+        let timestamp = requests[0].headers["x-timestamp"].parse().unwrap();
+        assert_eq!(
+            requests[0].headers["x-signature"],
+            HmacSigner::new("secret").signature("GET", "/v2/price_feeds?query=btc", timestamp)
+        );
+    }
+
+    #[test]
+    fn test_hmac_signature() {
+        let signer = HmacSigner::new("key");
+        assert_eq!(signer.signature("GET", "/", 0).len(), 64);
+        assert_ne!(
+            signer.signature("GET", "/", 0),
+            signer.signature("GET", "/", 1)
+        );
+        assert_ne!(
+            signer.signature("GET", "/", 0),
+            HmacSigner::new("other").signature("GET", "/", 0)
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Fetching the latest prices of many feeds at once, e.g. to warm up a bot tracking hundreds of feeds at startup
+//!
+//! ```no_run
+//! # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::feed_ids;
+//!
+//! let ids: Vec<_> = feed_ids::ALL.iter().map(|(_, id)| *id).collect();
+//! let prices = client.get_snapshot(&ids, 8).await?;
+//! println!("{} feeds", prices.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{
+        error::HermesError,
+        request::unique_ids,
+        rt,
+        types::{FeedId, PriceUpdate, RpcPriceFeed},
+        HermesClient,
+    },
+    futures_util::{
+        future::{self, Either},
+        stream, StreamExt, TryStreamExt,
+    },
+    std::{collections::HashMap, sync::Arc, time::Duration},
+};
+
+/// Why a feed's price is missing from a [`SnapshotReport`]
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum FeedFailure {
+    /// Hermes didn't return the feed, usually as it doesn't exist
+    #[error("not returned by hermes")]
+    NotReturned,
+    /// The request for the chunk of ids including the feed failed
+    #[error("request failed: {0}")]
+    Request(Arc<HermesError>),
+    /// The time budget of [`HermesClient::warm_cache`] elapsed before the feed's price was received
+    #[error("time budget exceeded")]
+    BudgetExceeded,
+}
+
+/// Outcome for each feed of a bulk fetch, see [`HermesClient::get_snapshot_report`]
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotReport {
+    pub prices: HashMap<FeedId, RpcPriceFeed>,
+    /// Feeds without a price, in the order they were requested
+    pub failed: Vec<(FeedId, FeedFailure)>,
+    /// Feeds which weren't requested as the client's [`FeedPolicy`](crate::policy::FeedPolicy) doesn't allow them
+    pub skipped: Vec<FeedId>,
+}
+
+impl SnapshotReport {
+    /// Whether every requested feed has a price
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.skipped.is_empty()
+    }
+
+    /// Records the outcome of the request for the feeds in `chunk`
+    fn record(&mut self, chunk: &[FeedId], update: Result<PriceUpdate, HermesError>) {
+        match update {
+            Ok(update) => {
+                let feeds = update.parsed.unwrap_or_default();
+                self.prices
+                    .extend(feeds.into_iter().map(|feed| (feed.id, feed)));
+                self.failed.extend(
+                    chunk
+                        .iter()
+                        .filter(|id| !self.prices.contains_key(id))
+                        .map(|id| (*id, FeedFailure::NotReturned)),
+                );
+            }
+            Err(err) => {
+                let err = Arc::new(err);
+                self.failed.extend(
+                    chunk
+                        .iter()
+                        .map(|id| (*id, FeedFailure::Request(err.clone()))),
+                );
+            }
+        }
+    }
+}
+
+impl HermesClient {
+    /// Fetches the latest price of every feed in `ids`, keyed by feed id
+    ///
+    /// The ids are split into requests of the client's chunk size, see
+    /// [`HermesClientBuilder::chunking`](crate::builder::HermesClientBuilder::chunking), with up to
+    /// `max_concurrency` requests in flight at once. Feeds hermes doesn't return are absent from the map, and the
+    /// first failed request fails the whole snapshot.
+    ///
+    /// # Panics
+    ///
+    /// If `max_concurrency` is zero
+    pub async fn get_snapshot(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        max_concurrency: usize,
+    ) -> Result<HashMap<FeedId, RpcPriceFeed>, HermesError> {
+        assert!(max_concurrency > 0, "max_concurrency must be positive");
+        self.misuse_policy.check_ids(ids)?;
+        let ids = unique_ids(ids);
+        // created up front, as futures created by a stream's closure don't satisfy `Send` in spawned tasks
+        let requests: Vec<_> = ids
+            .chunks(self.chunking.max_ids)
+            .map(|chunk| self.get_latest_price_feeds(chunk))
+            .collect();
+        stream::iter(requests)
+            .buffer_unordered(max_concurrency)
+            .try_fold(
+                HashMap::with_capacity(ids.len()),
+                |mut prices, feeds| async move {
+                    prices.extend(feeds.into_iter().map(|feed| (feed.id, feed)));
+                    Ok(prices)
+                },
+            )
+            .await
+    }
+
+    /// Fetches the latest price of every feed in `ids` like [`HermesClient::get_snapshot`], reporting the outcome
+    /// for each feed rather than failing as a whole
+    ///
+    /// Hermes is asked to ignore unknown ids, so they are reported as [`FeedFailure::NotReturned`] without failing
+    /// the rest of their chunk. Only misuse of the client, see [`crate::misuse`], fails the call.
+    ///
+    /// # Panics
+    ///
+    /// If `max_concurrency` is zero
+    pub async fn get_snapshot_report(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        max_concurrency: usize,
+    ) -> Result<SnapshotReport, HermesError> {
+        assert!(max_concurrency > 0, "max_concurrency must be positive");
+        self.misuse_policy.check_ids(ids)?;
+        let (allowed, skipped): (Vec<FeedId>, Vec<FeedId>) = unique_ids(ids)
+            .into_iter()
+            .partition(|id| self.feed_policy.is_allowed(id));
+        let mut report = SnapshotReport {
+            skipped,
+            ..Default::default()
+        };
+        for (chunk, update) in self.get_chunks(&allowed, max_concurrency).await {
+            report.record(chunk, update);
+        }
+        Ok(report)
+    }
+
+    /// Latest prices of `ids` requested in chunks of the client's chunk size, ignoring unknown ids, along with the
+    /// ids of each chunk in their order
+    pub(crate) async fn get_chunks<'a>(
+        &self,
+        ids: &'a [FeedId],
+        max_concurrency: usize,
+    ) -> Vec<(&'a [FeedId], Result<PriceUpdate, HermesError>)> {
+        // created up front, as futures created by a stream's closure don't satisfy `Send` in spawned tasks
+        let requests: Vec<_> = ids
+            .chunks(self.chunking.max_ids)
+            .map(|chunk| async move {
+                let update = self
+                    .latest_price()
+                    .ids(chunk)
+                    .ignore_invalid(true)
+                    .send()
+                    .await;
+                (chunk, update)
+            })
+            .collect();
+        stream::iter(requests)
+            .buffered(max_concurrency)
+            .collect()
+            .await
+    }
+
+    /// Fetches as many of the latest prices of `ids` as possible within `budget`, for services which would rather
+    /// start serving from a partial cache than wait for a full snapshot
+    ///
+    /// `ids` are in order of priority, so requests for the first are sent first, with up to the client's chunking
+    /// concurrency in flight at once. Requests still in flight once the budget has elapsed are abandoned and their
+    /// feeds reported as [`FeedFailure::BudgetExceeded`], along with those never requested. Otherwise feeds are
+    /// reported like [`HermesClient::get_snapshot_report`], and [`SnapshotReport::failed`] follows the priority
+    /// order.
+    ///
+    /// ```no_run
+    /// # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+    /// use {pyth_hermes_rs::feed_ids, std::time::Duration};
+    ///
+    /// let ids: Vec<_> = feed_ids::ALL.iter().map(|(_, id)| *id).collect();
+    /// let cache = client.warm_cache(&ids, Duration::from_millis(500)).await?;
+    /// println!("{} feeds warm, {} missing", cache.prices.len(), cache.failed.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn warm_cache(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        budget: Duration,
+    ) -> Result<SnapshotReport, HermesError> {
+        self.misuse_policy.check_ids(ids)?;
+        let (allowed, skipped): (Vec<FeedId>, Vec<FeedId>) = unique_ids(ids)
+            .into_iter()
+            .partition(|id| self.feed_policy.is_allowed(id));
+        // created up front, as futures created by a stream's closure don't satisfy `Send` in spawned tasks
+        let requests: Vec<_> = allowed
+            .chunks(self.chunking.max_ids)
+            .map(|chunk| async move {
+                let update = self
+                    .latest_price()
+                    .ids(chunk)
+                    .ignore_invalid(true)
+                    .send()
+                    .await;
+                (chunk, update)
+            })
+            .collect();
+        // buffer_unordered starts requests in priority order, but keeps responses arriving out of order
+        let mut responses = stream::iter(requests).buffer_unordered(self.chunking.max_concurrency);
+
+        let mut report = SnapshotReport {
+            skipped,
+            ..Default::default()
+        };
+        let mut deadline = std::pin::pin!(rt::sleep(budget));
+        while let Either::Left((Some((chunk, update)), _)) =
+            future::select(responses.next(), deadline.as_mut()).await
+        {
+            report.record(chunk, update);
+        }
+
+        let mut failed: HashMap<FeedId, FeedFailure> = report.failed.drain(..).collect();
+        report.failed = allowed
+            .iter()
+            .filter(|id| !report.prices.contains_key(id))
+            .map(|id| {
+                let failure = failed.remove(id).unwrap_or(FeedFailure::BudgetExceeded);
+                (*id, failure)
+            })
+            .collect();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD, SOL_USD},
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_get_snapshot() {
+        let ids = [ETH_USD, BTC_USD, SOL_USD];
+        let mock = MockHermes::start(
+            ids.iter()
+                .map(|id| {
+                    RecordedResponse::json(
+                        "/v2/updates/price/latest",
+                        price_update_json(&[*id], 1, 1),
+                    )
+                })
+                .collect(),
+        )
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .chunking(1, 1)
+            .build()
+            .unwrap();
+
+        let snapshot = client.get_snapshot(&ids, 2).await.unwrap();
+        assert_eq!(snapshot.len(), 3);
+        for id in ids {
+            assert_eq!(snapshot[&id].id, id);
+        }
+        // one request per chunk, regardless of the client's own concurrency
+        assert_eq!(mock.requests().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_snapshot_report() {
+        // hermes returns ETH but not SOL, then fails the chunk with BTC
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 1, 1),
+            ),
+            RecordedResponse::status("/v2/updates/price/latest", 400),
+        ])
+        .await
+        .unwrap();
+        let denied = FeedId::from_bytes([1; 32]);
+        let client = HermesClient::builder(mock.url())
+            .chunking(2, 1)
+            .feed_policy(crate::policy::FeedPolicy::deny([denied]))
+            .build()
+            .unwrap();
+
+        let report = client
+            .get_snapshot_report(&[ETH_USD, denied, SOL_USD, BTC_USD], 1)
+            .await
+            .unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(report.prices.len(), 1);
+        assert!(report.prices.contains_key(&ETH_USD));
+        assert_eq!(report.skipped, [denied]);
+        assert!(matches!(
+            report.failed.as_slice(),
+            [
+                (sol, FeedFailure::NotReturned),
+                (btc, FeedFailure::Request(_)),
+            ] if *sol == SOL_USD && *btc == BTC_USD
+        ));
+        let query = mock.requests().await[0].query.clone().unwrap();
+        assert!(query.contains("ignore_invalid_price_ids=true"));
+    }
+
+    #[tokio::test]
+    async fn test_warm_cache() {
+        let ids = [ETH_USD, BTC_USD, SOL_USD];
+        let mock = MockHermes::start(
+            ids.iter()
+                .map(|id| {
+                    RecordedResponse::json(
+                        "/v2/updates/price/latest",
+                        price_update_json(&[*id], 1, 1),
+                    )
+                })
+                .collect(),
+        )
+        .await
+        .unwrap();
+        mock.set_conditions(crate::mock::NetworkConditions {
+            latency: Duration::from_millis(400),
+            ..Default::default()
+        })
+        .await;
+        let client = HermesClient::builder(mock.url())
+            .chunking(1, 1)
+            .build()
+            .unwrap();
+
+        // only the first request completes within the budget
+        let report = client
+            .warm_cache(&ids, Duration::from_millis(600))
+            .await
+            .unwrap();
+        assert_eq!(report.prices.len(), 1);
+        assert!(report.prices.contains_key(&ETH_USD));
+        assert!(matches!(
+            report.failed.as_slice(),
+            [
+                (btc, FeedFailure::BudgetExceeded),
+                (sol, FeedFailure::BudgetExceeded),
+            ] if *btc == BTC_USD && *sol == SOL_USD
+        ));
+        assert_eq!(mock.requests().await.len(), 2);
+    }
+}
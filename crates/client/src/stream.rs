@@ -0,0 +1,2951 @@
+//! Streaming of price updates over SSE
+
+use {
+    crate::{
+        error::HermesError,
+        misuse::MisusePolicy,
+        policy::FeedPolicy,
+        request::unique_ids,
+        retry::RetryPolicy,
+        rng::Rng,
+        rt::{self, TaskHandle},
+        snapshot::FeedFailure,
+        types::*,
+        HermesClient,
+    },
+    futures_util::{
+        future::{self, Either},
+        stream::{self, Stream},
+        StreamExt, TryStreamExt,
+    },
+    reqwest::StatusCode,
+    reqwest_eventsource::{Error as EventSourceError, Event, EventSource},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{HashMap, HashSet, VecDeque},
+        fmt,
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        task::{Context, Poll},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::sync::{broadcast, mpsc, Notify, Semaphore},
+    tokio_util::sync::CancellationToken,
+};
+
+/// Callback invoked with the context of each stream failure
+pub type FailureHook = Arc<dyn Fn(&StreamFailure) + Send + Sync>;
+
+/// Callback invoked with the url of each connection of a stream which opens
+pub type ConnectedHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Callback invoked with the url of each open connection of a stream which closes, and why it closed
+pub type DisconnectedHook = Arc<dyn Fn(&str, &DisconnectReason) + Send + Sync>;
+
+/// Callback invoked before a stream reconnects, with the number of consecutive failed connections and the delay
+/// before reconnecting
+pub type ReconnectingHook = Arc<dyn Fn(u32, Duration) + Send + Sync>;
+
+/// Source of subscription ids, unique within the process
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Options controlling how a price update stream connects to hermes
+#[derive(Clone)]
+pub struct StreamOptions {
+    /// Send the id of the last received event in the `Last-Event-ID` header when reconnecting, allowing SSE aware
+    /// proxies to resume the stream without gaps. Servers which don't assign event ids are unaffected, see
+    /// [`Self::backfill`] for filling the gaps they leave.
+    pub resume_from_last_event_id: bool,
+    /// Event id sent in the `Last-Event-ID` header of the first connection
+    pub last_event_id: Option<String>,
+    /// Fetch updates missed while reconnecting over REST, for gaps of up to this long, as a fallback for servers
+    /// which don't resume the stream from the `Last-Event-ID`
+    ///
+    /// A feed's first update after reconnecting reveals a gap when it was published over a second after the
+    /// feed's previous update. The missed updates are then fetched one publish time at a time and delivered before
+    /// it. Only streams of parsed updates are backfilled. `None` never fetches missed updates.
+    pub backfill: Option<Duration>,
+    /// Called whenever the stream fails to connect, is disconnected or receives a message it can't parse, in
+    /// addition to the failure being logged
+    pub on_failure: Option<FailureHook>,
+    /// Called whenever a connection opens, including reconnections
+    pub on_connected: Option<ConnectedHook>,
+    /// Called whenever an open connection closes, including when the stream is stopped
+    pub on_disconnected: Option<DisconnectedHook>,
+    /// Called before waiting to reconnect, allowing flapping connections to be alerted on
+    pub on_reconnecting: Option<ReconnectingHook>,
+    /// Feeds streamed over each SSE connection, streams of more feeds are sharded across several connections
+    ///
+    /// Keeps the stream's urls within the limits of hermes and the proxies in front of it. Updates from every
+    /// connection are delivered to the same callback or subscription.
+    pub max_feeds_per_connection: usize,
+    /// Fetch the latest price of every feed over REST when the stream starts, delivering them marked as
+    /// [`ParsedPriceUpdate::snapshot`] before any update from the stream
+    ///
+    /// Consumers then start with a price for every feed rather than waiting for slow moving feeds to update. A
+    /// feed missing from the snapshot is reported like a stream failure, and the stream continues without it.
+    pub snapshot: bool,
+    /// Token shutting the stream down gracefully once cancelled, see [`StreamHandle::shutdown`]
+    ///
+    /// Lets one token stop several streams, e.g. along with the rest of an application. Each stream creates its own
+    /// when `None`.
+    pub cancellation: Option<CancellationToken>,
+    /// How long to keep reconnecting after failures, and the delays in between
+    pub reconnect: ReconnectPolicy,
+    /// Reconnect when a connection receives no events for this long, as it may have stalled without being closed
+    ///
+    /// Should comfortably exceed the interval between updates of the slowest subscribed feed. `None` waits forever.
+    pub idle_timeout: Option<Duration>,
+    /// Also stream price updates published out of order, which arrive sooner than waiting for ordered delivery
+    ///
+    /// Consumers then have to tolerate updates older than ones already received. `None` uses hermes' default.
+    pub allow_unordered: Option<bool>,
+    /// Only stream benchmark prices, the initial price updates at each publish time. `None` uses hermes' default.
+    pub benchmarks_only: Option<bool>,
+    /// Updates a [`Subscription`] or [`SharedSubscription`] holds until they are taken, beyond which
+    /// [`Self::backpressure`] applies
+    ///
+    /// [`SharedSubscription`]: crate::shared::SharedSubscription
+    pub capacity: usize,
+    /// How a [`Subscription`], a [`SharedSubscription`] or the channel of [`HermesClient::stream_to_channel`]
+    /// handles updates while it's full
+    ///
+    /// [`SharedSubscription`]: crate::shared::SharedSubscription
+    pub backpressure: Backpressure,
+}
+
+/// How a stream delivering to a bounded channel keeps up with a slower consumer, see
+/// [`StreamOptions::backpressure`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Stop reading from hermes until the consumer catches up, which pauses hermes through TCP flow control
+    ///
+    /// No update is lost, but updates arrive late while the consumer is behind, and hermes may close a connection
+    /// which stays paused for long.
+    #[default]
+    Block,
+    /// Hold back as many further updates as fit in the channel, dropping the oldest of them to make room
+    DropOldest,
+    /// Drop updates which don't fit in the channel
+    DropNewest,
+    /// Hold back only the latest update of each feed, replacing any older update of the feed held back
+    ///
+    /// Suits consumers which only act on the latest price, as each feed's price is at most one update behind.
+    /// Whole messages, see [`HermesClient::subscribe_batches`] and [`HermesClient::subscribe_binary`], aren't of a
+    /// single feed and are held back like [`Self::DropOldest`].
+    CoalescePerFeed,
+}
+
+impl Backpressure {
+    /// Permits bounding the messages a stream reads ahead of a consumer of a channel of `capacity` updates, see
+    /// [`Self::Block`]
+    pub(crate) fn permits(self, capacity: usize) -> Option<Arc<Semaphore>> {
+        (self == Self::Block).then(|| Arc::new(Semaphore::new(capacity)))
+    }
+
+    /// Holds back `update` which didn't fit in a channel of `capacity` updates, or drops it
+    fn hold<T: FeedUpdate>(self, held: &mut VecDeque<T>, update: T, capacity: usize) {
+        let feed = match self {
+            Self::Block | Self::DropNewest => return,
+            Self::DropOldest => None,
+            Self::CoalescePerFeed => update.feed_id(),
+        };
+        match feed {
+            Some(id) => match held.iter_mut().find(|held| held.feed_id() == Some(id)) {
+                Some(held) => *held = update,
+                None => held.push_back(update),
+            },
+            None => {
+                if held.len() >= capacity {
+                    held.pop_front();
+                }
+                held.push_back(update);
+            }
+        }
+    }
+}
+
+/// Item delivered to a bounded channel, see [`Backpressure::CoalescePerFeed`]
+pub(crate) trait FeedUpdate {
+    /// Feed the item is an update of, `None` for items holding updates of several feeds
+    fn feed_id(&self) -> Option<FeedId>;
+}
+
+impl FeedUpdate for ParsedPriceUpdate {
+    fn feed_id(&self) -> Option<FeedId> {
+        Some(self.id)
+    }
+}
+
+impl FeedUpdate for BinaryPriceUpdate {
+    fn feed_id(&self) -> Option<FeedId> {
+        Some(self.update.id)
+    }
+}
+
+impl FeedUpdate for StreamBatch {
+    fn feed_id(&self) -> Option<FeedId> {
+        None
+    }
+}
+
+impl FeedUpdate for BinaryUpdate {
+    fn feed_id(&self) -> Option<FeedId> {
+        None
+    }
+}
+
+/// Reconnection policy of a stream, see [`StreamOptions::reconnect`]
+///
+/// The delay before reconnecting doubles with each connection which fails without receiving a message. Once more
+/// than `max_retries` connections in a row fail, the stream gives up: the failure is reported, every connection of
+/// the stream stops and its [`Subscription`] ends, with [`StreamStats::gave_up`] set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Consecutive failed connections tolerated before giving up, `None` reconnects forever
+    pub max_retries: Option<u32>,
+    /// Delay before the first reconnection
+    pub base_delay: Duration,
+    /// Upper bound on the delay between reconnections
+    pub max_delay: Duration,
+    /// Randomizes each delay between half and the full value, spreading out reconnections from many clients
+    pub jitter: bool,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: None,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Default policy reconnecting forever
+    pub fn forever() -> Self {
+        Self::default()
+    }
+
+    /// Default policy giving up after `max_retries` consecutive failed connections
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries: Some(max_retries),
+            ..Self::default()
+        }
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Delay before reconnecting after `failures` consecutive failed connections, jittered with values from `rng`
+    pub fn delay_with(&self, failures: u32, rng: &Rng) -> Duration {
+        RetryPolicy {
+            max_attempts: u32::MAX,
+            base_delay: self.base_delay,
+            max_delay: self.max_delay,
+            jitter: self.jitter,
+        }
+        .delay_with(failures, rng)
+    }
+
+    /// Whether the stream gives up after `failures` consecutive failed connections
+    pub fn gives_up(&self, failures: u32) -> bool {
+        self.max_retries.is_some_and(|max| failures > max)
+    }
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            resume_from_last_event_id: true,
+            last_event_id: None,
+            backfill: None,
+            on_failure: None,
+            on_connected: None,
+            on_disconnected: None,
+            on_reconnecting: None,
+            max_feeds_per_connection: 100,
+            snapshot: false,
+            cancellation: None,
+            reconnect: ReconnectPolicy::default(),
+            idle_timeout: None,
+            allow_unordered: None,
+            benchmarks_only: None,
+            capacity: 1024,
+            backpressure: Backpressure::default(),
+        }
+    }
+}
+
+impl fmt::Debug for StreamOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamOptions")
+            .field("resume_from_last_event_id", &self.resume_from_last_event_id)
+            .field("last_event_id", &self.last_event_id)
+            .field("backfill", &self.backfill)
+            .field("on_failure", &self.on_failure.is_some())
+            .field("on_connected", &self.on_connected.is_some())
+            .field("on_disconnected", &self.on_disconnected.is_some())
+            .field("on_reconnecting", &self.on_reconnecting.is_some())
+            .field("max_feeds_per_connection", &self.max_feeds_per_connection)
+            .field("snapshot", &self.snapshot)
+            .field("cancellation", &self.cancellation)
+            .field("reconnect", &self.reconnect)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("allow_unordered", &self.allow_unordered)
+            .field("benchmarks_only", &self.benchmarks_only)
+            .field("capacity", &self.capacity)
+            .field("backpressure", &self.backpressure)
+            .finish()
+    }
+}
+
+/// Context describing a failure of a stream's background task
+///
+/// The `Display` implementation renders everything on a single line, suitable for logging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamFailure {
+    /// Id of the failing stream, see [`StreamHandle::id`]
+    pub subscription_id: u64,
+    /// Url the stream was connected to
+    pub endpoint: String,
+    /// Number of feeds subscribed to
+    pub feed_count: usize,
+    /// When the last message was received, if any
+    pub last_activity: Option<SystemTime>,
+    pub error: String,
+}
+
+impl fmt::Display for StreamFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stream {} failed: {} (endpoint={} feeds={} last_activity=",
+            self.subscription_id, self.error, self.endpoint, self.feed_count
+        )?;
+        match self
+            .last_activity
+            .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        {
+            Some(at) => write!(f, "{}.{:03})", at.as_secs(), at.subsec_millis()),
+            None => write!(f, "never)"),
+        }
+    }
+}
+
+/// Why an open connection of a stream closed, see [`StreamOptions::on_disconnected`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Hermes ended the stream, as it does periodically
+    Ended,
+    /// Hermes rejected the connection's credentials, which are refreshed before reconnecting
+    Unauthorized,
+    /// The connection failed
+    Error(String),
+    /// No events were received within [`StreamOptions::idle_timeout`]
+    Idle,
+    /// The stream was stopped or gave up reconnecting
+    Stopped,
+    /// The stream's feeds changed, and it reconnects subscribed to them, see [`StreamHandle::add_feeds`]
+    Resubscribed,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ended => write!(f, "stream ended"),
+            Self::Unauthorized => write!(f, "unauthorized"),
+            Self::Error(err) => write!(f, "{err}"),
+            Self::Idle => write!(f, "idle timeout"),
+            Self::Stopped => write!(f, "stream stopped"),
+            Self::Resubscribed => write!(f, "feeds changed"),
+        }
+    }
+}
+
+/// Everything needed to report a failure from within a stream task
+struct FailureReporter {
+    subscription_id: u64,
+    stats: Arc<StreamStats>,
+    hook: Option<FailureHook>,
+}
+
+impl FailureReporter {
+    fn report(&self, endpoint: &str, error: impl fmt::Display) {
+        let failure = StreamFailure {
+            subscription_id: self.subscription_id,
+            endpoint: endpoint.to_string(),
+            feed_count: self.stats.feed_count(),
+            last_activity: self.stats.last_message_at(),
+            error: error.to_string(),
+        };
+        log::error!("{failure}");
+        *self.stats.lock_last_error() = Some(failure.error.clone());
+        if let Some(hook) = &self.hook {
+            hook(&failure);
+        }
+    }
+}
+
+/// Handle to a running price update stream
+///
+/// Dropping the handle stops the stream, use [`StreamHandle::detach`] to keep it running in the background.
+#[must_use = "the stream is stopped when the handle is dropped, call `detach` to keep it running"]
+pub struct StreamHandle {
+    id: u64,
+    task: TaskHandle,
+    stats: Arc<StreamStats>,
+    cancellation: CancellationToken,
+    /// Cancelled once the stream's task ends
+    finished: CancellationToken,
+    /// Set once the stream is aborted or detached, so dropping the handle is silent
+    released: AtomicBool,
+    feeds: Arc<StreamFeeds>,
+}
+
+/// Feeds a stream is subscribed to, which its handle can change while it runs
+struct StreamFeeds {
+    ids: Mutex<Vec<FeedId>>,
+    /// Notified whenever `ids` change, for the stream to reconnect subscribed to them
+    changed: Notify,
+    misuse_policy: MisusePolicy,
+    policy: Arc<FeedPolicy>,
+}
+
+impl StreamFeeds {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<FeedId>> {
+        self.ids.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Price updates received by a stream, see [`HermesClient::subscribe`]
+///
+/// Updates are those of each feed by default, or whole messages for [`HermesClient::subscribe_batches`]. Up to
+/// [`StreamOptions::capacity`] of them wait to be taken, further ones are handled as
+/// [`StreamOptions::backpressure`] dictates. Dropping the subscription stops the stream.
+#[must_use = "the stream is stopped when the subscription is dropped"]
+pub struct Subscription<T = ParsedPriceUpdate> {
+    updates: mpsc::Receiver<T>,
+    handle: StreamHandle,
+}
+
+/// One message of a stream, holding the update of every feed hermes included in it, see
+/// [`HermesClient::subscribe_batches`]
+#[derive(Debug, Clone)]
+pub struct StreamBatch {
+    /// Parsed prices along with the binary update data which can be submitted on chain
+    pub update: PriceUpdate,
+    /// Set on batches fetched over REST when the stream starts, see [`StreamOptions::snapshot`]
+    pub snapshot: bool,
+}
+
+/// Update of a feed along with the binary data of the message it was received in, see
+/// [`HermesClient::subscribe_with_binary`]
+#[derive(Debug, Clone)]
+pub struct BinaryPriceUpdate {
+    pub update: ParsedPriceUpdate,
+    /// Update data of every feed in the message, shared by the updates split from it
+    pub binary: Arc<BinaryUpdate>,
+}
+
+impl StreamBatch {
+    /// Splits the batch into the update of each feed
+    pub fn into_updates(self) -> impl Iterator<Item = ParsedPriceUpdate> {
+        split_feeds(self.update.parsed, self.snapshot)
+    }
+
+    /// Splits the batch into the update of each feed like [`StreamBatch::into_updates`], each along with the
+    /// batch's binary data
+    pub fn into_binary_updates(self) -> impl Iterator<Item = BinaryPriceUpdate> {
+        let binary = Arc::new(self.update.binary);
+        split_feeds(self.update.parsed, self.snapshot).map(move |update| BinaryPriceUpdate {
+            update,
+            binary: binary.clone(),
+        })
+    }
+}
+
+/// Updates of each feed in a message
+fn split_feeds(
+    feeds: Option<Vec<RpcPriceFeed>>,
+    snapshot: bool,
+) -> impl Iterator<Item = ParsedPriceUpdate> {
+    feeds
+        .unwrap_or_default()
+        .into_iter()
+        .map(move |feed| ParsedPriceUpdate {
+            snapshot,
+            ..feed.into()
+        })
+}
+
+/// Connection state of a stream, see [`StreamHandle::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamState {
+    /// Opening a connection to hermes, or waiting for every connection of a sharded stream to open
+    Connecting,
+    /// Every connection is open
+    Connected,
+    /// Waiting before reconnecting after a failure
+    BackingOff,
+    /// The stream's task has ended, as it was stopped or aborted
+    Stopped,
+}
+
+/// Counters describing the health of a stream
+#[derive(Debug)]
+pub struct StreamStats {
+    messages_received: AtomicU64,
+    bytes_received: AtomicU64,
+    reconnects: AtomicU64,
+    /// Zero until the first message is received
+    last_message_at_millis: AtomicU64,
+    started_at_millis: u64,
+    /// Cleared once the stream's task stops
+    running: AtomicBool,
+    /// Set once the stream stops as its reconnect policy gave up
+    gave_up: AtomicBool,
+    /// Set while updates are buffered by a [`Subscription`] which counts them in `buffered`
+    buffer_tracked: AtomicBool,
+    buffered: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    /// Number of feeds subscribed to
+    feeds: AtomicU64,
+    /// Number of SSE connections the stream's feeds are sharded across
+    connections: AtomicU64,
+    /// Connections which are open, and waiting to reconnect
+    connected: AtomicU64,
+    backing_off: AtomicU64,
+}
+
+/// Counts a connection in one of the stream's states for as long as it's alive
+struct StateGuard<'a>(&'a AtomicU64);
+
+impl<'a> StateGuard<'a> {
+    fn enter(counter: &'a AtomicU64) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for StateGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Marks a stream as stopped when its task ends or is aborted
+struct RunningGuard {
+    stats: Arc<StreamStats>,
+    finished: CancellationToken,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.stats.running.store(false, Ordering::Relaxed);
+        self.finished.cancel();
+    }
+}
+
+impl StreamHandle {
+    /// Id identifying the stream in logs and [`StreamFailure`]s
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Stops the stream gracefully, returning once it has stopped
+    ///
+    /// Unlike [`StreamHandle::abort`], each connection finishes handling the event it's receiving and closes its
+    /// event source, and every update already received is delivered before the stream's task ends.
+    pub async fn shutdown(&self) {
+        self.released.store(true, Ordering::Relaxed);
+        self.cancellation.cancel();
+        self.finished.cancelled().await;
+    }
+
+    /// Token cancelled once the stream's task has ended, for whatever reason
+    pub(crate) fn finished(&self) -> CancellationToken {
+        self.finished.clone()
+    }
+
+    /// Token which shuts the stream down gracefully when cancelled, see [`StreamHandle::shutdown`]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Stops the stream gracefully without waiting for it to stop, see [`StreamHandle::shutdown`]
+    pub fn stop(&self) {
+        self.released.store(true, Ordering::Relaxed);
+        self.cancellation.cancel();
+    }
+
+    /// Current connection state of the stream
+    pub fn state(&self) -> StreamState {
+        self.stats.state()
+    }
+
+    /// When the last SSE message was received, if any
+    pub fn last_event_at(&self) -> Option<SystemTime> {
+        self.stats.last_message_at()
+    }
+
+    /// Aborts the stream
+    pub fn abort(&self) {
+        self.released.store(true, Ordering::Relaxed);
+        self.task.abort();
+    }
+
+    /// Keeps the stream running in the background until the runtime shuts down, returning its counters
+    pub fn detach(self) -> Arc<StreamStats> {
+        // dropping the underlying task handle doesn't abort the task
+        self.released.store(true, Ordering::Relaxed);
+        self.stats.clone()
+    }
+
+    /// Counters for the stream, updated as messages are received
+    pub fn stats(&self) -> &StreamStats {
+        &self.stats
+    }
+
+    /// Feeds the stream is currently subscribed to
+    pub fn feeds(&self) -> Vec<FeedId> {
+        self.feeds.lock().clone()
+    }
+
+    /// Subscribes the running stream to `ids` as well, reconnecting with the updated feeds
+    ///
+    /// Updates keep being delivered to the same callback or [`Subscription`], with no updates of the other feeds
+    /// missed beyond those published while reconnecting. Feeds already subscribed to are ignored, and the stream
+    /// only reconnects if any are left. Added feeds aren't fetched over REST, even with
+    /// [`StreamOptions::snapshot`].
+    pub fn add_feeds(&self, ids: &[impl AsRef<FeedId>]) -> Result<(), HermesError> {
+        self.feeds.misuse_policy.check_ids(ids)?;
+        let ids = unique_ids(ids);
+        self.feeds.policy.check(&ids)?;
+        let mut feeds = self.feeds.lock();
+        let count = feeds.len();
+        for id in ids {
+            if !feeds.contains(&id) {
+                feeds.push(id);
+            }
+        }
+        if feeds.len() != count {
+            self.resubscribe(feeds);
+        }
+        Ok(())
+    }
+
+    /// Unsubscribes the running stream from `ids`, reconnecting with the remaining feeds
+    ///
+    /// Feeds the stream isn't subscribed to are ignored. Removing every feed closes the stream's connections
+    /// until feeds are added again, without stopping it.
+    pub fn remove_feeds(&self, ids: &[impl AsRef<FeedId>]) {
+        let mut feeds = self.feeds.lock();
+        let count = feeds.len();
+        feeds.retain(|feed| !ids.iter().any(|id| id.as_ref() == feed));
+        if feeds.len() != count {
+            self.resubscribe(feeds);
+        }
+    }
+
+    fn resubscribe(&self, feeds: std::sync::MutexGuard<'_, Vec<FeedId>>) {
+        self.stats
+            .feeds
+            .store(feeds.len() as u64, Ordering::Relaxed);
+        drop(feeds);
+        self.feeds.changed.notify_one();
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        if !self.released.load(Ordering::Relaxed) {
+            log::warn!(
+                "stream {} stopped as its handle was dropped, call `StreamHandle::detach` to keep it running",
+                self.id
+            );
+            self.task.abort();
+        }
+    }
+}
+
+impl<T> Subscription<T> {
+    /// Waits for the next update, returns `None` once the stream is aborted
+    pub async fn next(&mut self) -> Option<T> {
+        let update = self.updates.recv().await;
+        self.taken(&update);
+        update
+    }
+
+    /// Returns the next update if one has already been received
+    pub fn try_next(&mut self) -> Option<T> {
+        let update = self.updates.try_recv().ok();
+        self.taken(&update);
+        update
+    }
+
+    fn taken(&self, update: &Option<T>) {
+        if update.is_some() {
+            self.handle.stats.buffered.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Handle to the underlying stream, for its id and counters
+    pub fn handle(&self) -> &StreamHandle {
+        &self.handle
+    }
+
+    /// Separates the received updates from the handle controlling the stream
+    pub fn into_parts(self) -> (mpsc::Receiver<T>, StreamHandle) {
+        // updates taken from the receiver can't be counted
+        self.handle
+            .stats
+            .buffer_tracked
+            .store(false, Ordering::Relaxed);
+        (self.updates, self.handle)
+    }
+}
+
+/// Updates can also be consumed with [`StreamExt`] combinators, see [`HermesClient::price_update_stream`]
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let update = std::task::ready!(self.updates.poll_recv(cx));
+        self.taken(&update);
+        Poll::Ready(update)
+    }
+}
+
+impl StreamStats {
+    pub(crate) fn new() -> Self {
+        Self {
+            messages_received: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            last_message_at_millis: AtomicU64::new(0),
+            started_at_millis: rt::unix_timestamp_millis(),
+            running: AtomicBool::new(true),
+            gave_up: AtomicBool::new(false),
+            buffer_tracked: AtomicBool::new(false),
+            buffered: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+            feeds: AtomicU64::new(0),
+            connections: AtomicU64::new(1),
+            connected: AtomicU64::new(0),
+            backing_off: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of SSE messages received from hermes
+    pub fn messages_received(&self) -> u64 {
+        self.messages_received.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes of SSE message data received from hermes
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the stream reconnected to hermes
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+
+    /// When the last SSE message was received, if any
+    pub fn last_message_at(&self) -> Option<SystemTime> {
+        match self.last_message_at_millis.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(UNIX_EPOCH + Duration::from_millis(millis)),
+        }
+    }
+
+    /// Time since the stream was started
+    pub fn uptime(&self) -> Duration {
+        Duration::from_millis(rt::unix_timestamp_millis().saturating_sub(self.started_at_millis))
+    }
+
+    /// Number of feeds the stream is subscribed to, see [`StreamHandle::add_feeds`]
+    pub fn feed_count(&self) -> usize {
+        self.feeds.load(Ordering::Relaxed) as usize
+    }
+
+    /// Whether the stream's task is still running
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Whether the stream stopped as connections kept failing, see [`ReconnectPolicy`]
+    pub fn gave_up(&self) -> bool {
+        self.gave_up.load(Ordering::Relaxed)
+    }
+
+    /// Connection state of the stream, see [`StreamHandle::state`]
+    pub fn state(&self) -> StreamState {
+        if !self.is_running() {
+            StreamState::Stopped
+        } else if self.connected.load(Ordering::Relaxed) >= self.connections.load(Ordering::Relaxed)
+        {
+            StreamState::Connected
+        } else if self.backing_off.load(Ordering::Relaxed) > 0 {
+            StreamState::BackingOff
+        } else {
+            StreamState::Connecting
+        }
+    }
+
+    /// Number of updates waiting to be taken from the stream's [`Subscription`]
+    ///
+    /// `None` for streams delivering updates to a callback, and once the subscription is split with
+    /// [`Subscription::into_parts`].
+    pub fn buffered(&self) -> Option<u64> {
+        self.buffer_tracked
+            .load(Ordering::Relaxed)
+            .then(|| self.buffered.load(Ordering::Relaxed))
+    }
+
+    /// Error of the stream's most recent failure, if any
+    pub fn last_error(&self) -> Option<String> {
+        self.lock_last_error().clone()
+    }
+
+    fn lock_last_error(&self) -> std::sync::MutexGuard<'_, Option<String>> {
+        self.last_error.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HermesClient {
+    /// Streams price updates for `ids`, which are received from the returned [`Subscription`]
+    pub async fn subscribe(&self, ids: &[impl AsRef<FeedId>]) -> Result<Subscription, HermesError> {
+        self.subscribe_with_options(ids, StreamOptions::default())
+            .await
+    }
+
+    /// Streams price updates for `ids` as a [`Stream`], for use with [`StreamExt`] combinators
+    ///
+    /// The stream connects when it's first polled and stops once dropped. Failing to start, e.g. as a feed isn't
+    /// allowed by the client's policy, yields a single error, while later disconnections are retried like those of
+    /// a [`Subscription`].
+    ///
+    /// ```no_run
+    /// # async fn example(client: pyth_hermes_rs::HermesClient) {
+    /// use {futures_util::StreamExt, pyth_hermes_rs::feed_ids};
+    ///
+    /// let mut updates = Box::pin(
+    ///     client
+    ///         .price_update_stream(&[feed_ids::BTC_USD, feed_ids::ETH_USD])
+    ///         .filter_map(|update| async move { update.ok() })
+    ///         .filter(|update| std::future::ready(update.id == feed_ids::BTC_USD)),
+    /// );
+    /// while let Some(update) = updates.next().await {
+    ///     println!("{}", update.price.price);
+    /// }
+    /// # }
+    /// ```
+    pub fn price_update_stream(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+    ) -> impl Stream<Item = Result<ParsedPriceUpdate, HermesError>> + Send + 'static {
+        let client = self.clone();
+        let ids: Vec<FeedId> = ids.iter().map(|id| *id.as_ref()).collect();
+        stream::once(async move {
+            let subscription = client.subscribe(&ids).await?;
+            Ok::<_, HermesError>(subscription.map(Ok))
+        })
+        .try_flatten()
+    }
+
+    /// Streams price updates for `ids` with `options` like [`HermesClient::price_update_stream`], yielding each
+    /// failure of the stream as an error between the updates
+    ///
+    /// Failures are those passed to [`StreamOptions::on_failure`], which is still called: failed connections,
+    /// disconnections and messages which can't be parsed, each yielded as a [`HermesError::Stream`]. The stream
+    /// keeps reconnecting after them, and ends once it's stopped or its [`ReconnectPolicy`] gives up.
+    ///
+    /// ```no_run
+    /// # async fn example(client: pyth_hermes_rs::HermesClient) {
+    /// use {
+    ///     futures_util::StreamExt,
+    ///     pyth_hermes_rs::{feed_ids, stream::StreamOptions},
+    /// };
+    ///
+    /// let mut updates =
+    ///     Box::pin(client.try_price_update_stream(&[feed_ids::BTC_USD], StreamOptions::default()));
+    /// while let Some(update) = updates.next().await {
+    ///     match update {
+    ///         Ok(update) => println!("{}", update.price.price),
+    ///         Err(err) => eprintln!("{err}"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn try_price_update_stream(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<ParsedPriceUpdate, HermesError>> + Send + 'static {
+        let client = self.clone();
+        let ids: Vec<FeedId> = ids.iter().map(|id| *id.as_ref()).collect();
+        stream::once(async move {
+            let (tx, items) = mpsc::unbounded_channel();
+            let failures = tx.clone();
+            let on_failure = options.on_failure.clone();
+            let options = StreamOptions {
+                on_failure: Some(Arc::new(move |failure: &StreamFailure| {
+                    if let Some(hook) = &on_failure {
+                        hook(failure);
+                    }
+                    let _ = failures.send(Err(HermesError::Stream(failure.clone())));
+                })),
+                ..options
+            };
+            let handle = client
+                .stream_price_updates_with_options(&ids, options, move |update| {
+                    let _ = tx.send(Ok(update));
+                })
+                .await?;
+            // the channel closes once the stream's task ends, the handle stops the stream when the items are dropped
+            Ok::<_, HermesError>(stream::unfold(
+                (items, handle),
+                |(mut items, handle)| async move {
+                    let item = items.recv().await?;
+                    Some((item, (items, handle)))
+                },
+            ))
+        })
+        .try_flatten()
+    }
+
+    /// Streams price updates for `ids` with `options`, see [`HermesClient::subscribe`]
+    pub async fn subscribe_with_options(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+    ) -> Result<Subscription, HermesError> {
+        self.subscribe_to(ids, options, true, StreamBatch::into_updates)
+            .await
+    }
+
+    /// Streams every message for `ids` as a whole, rather than the update of each feed
+    ///
+    /// Each [`StreamBatch`] holds the prices of every feed hermes included in a message along with their binary
+    /// update data, for relayers which submit them on chain together. Streams sharded across several connections,
+    /// see [`StreamOptions::max_feeds_per_connection`], deliver the messages of each connection separately. A
+    /// [`StreamOptions::snapshot`] is delivered as a batch per REST request.
+    pub async fn subscribe_batches(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+    ) -> Result<Subscription<StreamBatch>, HermesError> {
+        self.subscribe_to(ids, options, true, std::iter::once).await
+    }
+
+    /// Streams price updates for `ids` like [`HermesClient::subscribe_with_options`], each along with the binary
+    /// data of the message it was received in
+    ///
+    /// On chain pushers can relay an update as soon as it's received, without requesting its data over REST.
+    pub async fn subscribe_with_binary(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+    ) -> Result<Subscription<BinaryPriceUpdate>, HermesError> {
+        self.subscribe_to(ids, options, true, StreamBatch::into_binary_updates)
+            .await
+    }
+
+    /// Streams the binary update data of each message for `ids`, without parsed prices
+    ///
+    /// Hermes is asked to leave parsed prices out of its messages and only their binary data is deserialized,
+    /// considerably reducing the CPU used by relayers which submit updates on chain without inspecting them. A
+    /// [`StreamOptions::snapshot`] is delivered as the data of each REST request, before any streamed message.
+    pub async fn subscribe_binary(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+    ) -> Result<Subscription<BinaryUpdate>, HermesError> {
+        self.subscribe_to(ids, options, false, |batch: StreamBatch| {
+            std::iter::once(batch.update.binary)
+        })
+        .await
+    }
+
+    /// Spawns a task which streams price updates for `ids` like
+    /// [`HermesClient::stream_price_updates_with_options`], invoking `on_event` with each update along with the
+    /// binary data of the message it was received in
+    pub async fn stream_price_updates_with_binary<F>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+        mut on_event: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(BinaryPriceUpdate) + Send + 'static,
+    {
+        let stats = Arc::new(StreamStats::new());
+        self.spawn_stream(ids, options, true, None, stats, move |batch| {
+            batch.into_binary_updates().for_each(&mut on_event)
+        })
+        .await
+    }
+
+    /// Streams `ids` to a [`Subscription`] receiving the items `split` makes of each batch, only requesting the
+    /// binary data of messages unless `parsed`
+    async fn subscribe_to<T, I>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+        parsed: bool,
+        mut split: impl FnMut(StreamBatch) -> I + Send + 'static,
+    ) -> Result<Subscription<T>, HermesError>
+    where
+        T: FeedUpdate + Send + 'static,
+        I: IntoIterator<Item = T> + Send + 'static,
+        I::IntoIter: Send,
+    {
+        let capacity = options.capacity.max(1);
+        let backpressure = options.backpressure;
+        let permits = backpressure.permits(capacity);
+        let (sender, updates) = mpsc::channel(capacity);
+        let (queue, queued) = mpsc::unbounded_channel();
+        let stats = Arc::new(StreamStats::new());
+        stats.buffer_tracked.store(true, Ordering::Relaxed);
+        let handle = self
+            .spawn_stream(
+                ids,
+                options,
+                parsed,
+                permits.clone(),
+                stats.clone(),
+                move |batch| {
+                    let release = Release::of(&batch, &permits);
+                    let _ = queue.send(Queued {
+                        updates: split(batch),
+                        release,
+                    });
+                },
+            )
+            .await?;
+        drop(rt::spawn(forward(
+            queued,
+            sender,
+            backpressure,
+            move || {
+                stats.buffered.fetch_add(1, Ordering::Relaxed);
+            },
+        )));
+        Ok(Subscription { updates, handle })
+    }
+
+    /// Spawns a task which streams price updates from the hermes api, invoking `on_event` for each update
+    ///
+    /// # Returns
+    ///
+    /// [`StreamHandle`] which can be used to monitor and abort the spawned task
+    pub async fn stream_price_updates_with_options<F>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+        mut on_event: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(ParsedPriceUpdate) + Send + 'static,
+    {
+        let stats = Arc::new(StreamStats::new());
+        self.spawn_stream(ids, options, true, None, stats, move |batch| {
+            batch.into_updates().for_each(&mut on_event)
+        })
+        .await
+    }
+
+    /// Streams price updates for `ids` with `options`, awaiting `on_event` for each update
+    ///
+    /// Updates are handled one at a time in the order they are received, so a handler writing to a database or a
+    /// bounded channel can await without spawning a task per update. Updates received while it's busy are buffered
+    /// up to [`StreamOptions::capacity`], beyond which [`StreamOptions::backpressure`] applies.
+    /// The handler's task stops along with the stream once the remaining updates are handled.
+    ///
+    /// ```no_run
+    /// # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+    /// use pyth_hermes_rs::{feed_ids, stream::StreamOptions};
+    ///
+    /// let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    /// let handle = client
+    ///     .stream_price_updates_async(&[feed_ids::BTC_USD], StreamOptions::default(), move |update| {
+    ///         let tx = tx.clone();
+    ///         async move {
+    ///             let _ = tx.send(update).await;
+    ///         }
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stream_price_updates_async<F, Fut>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+        mut on_event: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(ParsedPriceUpdate) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (mut updates, handle) = self
+            .subscribe_with_options(ids, options)
+            .await?
+            .into_parts();
+        // the channel closes when the stream's task is aborted, ending this task
+        drop(rt::spawn(async move {
+            while let Some(update) = updates.recv().await {
+                on_event(update).await;
+            }
+        }));
+        Ok(handle)
+    }
+
+    /// Streams price updates for `ids` to a channel holding up to `capacity` updates, see
+    /// [`HermesClient::stream_to_channel_with_options`]
+    pub async fn stream_to_channel(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        capacity: usize,
+    ) -> Result<mpsc::Receiver<ParsedPriceUpdate>, HermesError> {
+        self.stream_to_channel_with_options(ids, capacity, StreamOptions::default())
+            .await
+    }
+
+    /// Streams price updates for `ids` with `options` to a channel holding up to `capacity` updates
+    ///
+    /// The stream runs in the background until the receiver is dropped, which then receives `None` once the stream
+    /// stops for any other reason. Updates which don't fit in the channel are handled as
+    /// [`StreamOptions::backpressure`] dictates, so a slow consumer can't make the stream buffer without bound.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, like [`mpsc::channel`].
+    pub async fn stream_to_channel_with_options(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        capacity: usize,
+        options: StreamOptions,
+    ) -> Result<mpsc::Receiver<ParsedPriceUpdate>, HermesError> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let backpressure = options.backpressure;
+        let permits = backpressure.permits(capacity);
+        let (queue, queued) = mpsc::unbounded_channel();
+        let handle = self
+            .spawn_stream(
+                ids,
+                options,
+                true,
+                permits.clone(),
+                Arc::new(StreamStats::new()),
+                move |batch| {
+                    let release = Release::of(&batch, &permits);
+                    let _ = queue.send(Queued {
+                        updates: batch.into_updates(),
+                        release,
+                    });
+                },
+            )
+            .await?;
+        drop(rt::spawn(async move {
+            forward(queued, sender, backpressure, || {}).await;
+            handle.stop();
+        }));
+        Ok(receiver)
+    }
+
+    /// Streams price updates for `ids` to a broadcast channel holding up to `capacity` updates, see
+    /// [`HermesClient::stream_to_broadcast_with_options`]
+    pub async fn stream_to_broadcast(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        capacity: usize,
+    ) -> Result<broadcast::Receiver<ParsedPriceUpdate>, HermesError> {
+        self.stream_to_broadcast_with_options(ids, capacity, StreamOptions::default())
+            .await
+    }
+
+    /// Streams price updates for `ids` with `options` to a broadcast channel holding up to `capacity` updates
+    ///
+    /// Every receiver, including those created with [`broadcast::Receiver::resubscribe`], receives each update
+    /// sent after it was created. A receiver falling more than `capacity` updates behind misses the oldest ones and
+    /// is told how many with [`broadcast::error::RecvError::Lagged`]. The stream runs in the background until an
+    /// update finds every receiver dropped, or its [`StreamOptions::cancellation`] is cancelled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, like [`broadcast::channel`].
+    pub async fn stream_to_broadcast_with_options(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        capacity: usize,
+        mut options: StreamOptions,
+    ) -> Result<broadcast::Receiver<ParsedPriceUpdate>, HermesError> {
+        let (sender, receiver) = broadcast::channel(capacity);
+        // stopping once nobody listens mustn't cancel a token shared with other streams
+        let cancellation = options
+            .cancellation
+            .map(|token| token.child_token())
+            .unwrap_or_default();
+        options.cancellation = Some(cancellation.clone());
+        let handle = self
+            .stream_price_updates_with_options(ids, options, move |update| {
+                if sender.send(update).is_err() {
+                    cancellation.cancel();
+                }
+            })
+            .await?;
+        handle.detach();
+        Ok(receiver)
+    }
+
+    /// Spawns a task which streams `ids` with `options`, invoking `on_event` with each message, only requesting the
+    /// binary data of messages unless `parsed`
+    ///
+    /// A permit is taken from `permits` for each message received, which the consumer returns once it's
+    /// delivered, see [`Backpressure::Block`].
+    pub(crate) async fn spawn_stream<F>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        options: StreamOptions,
+        parsed: bool,
+        permits: Option<Arc<Semaphore>>,
+        stats: Arc<StreamStats>,
+        mut on_event: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(StreamBatch) + Send + 'static,
+    {
+        self.misuse_policy.check_ids(ids)?;
+        let client = self.clone();
+        let ids = unique_ids(ids);
+        client.feed_policy.check(&ids)?;
+        let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+        stats.feeds.store(ids.len() as u64, Ordering::Relaxed);
+        client.streams.register(id, &stats);
+        let urls: Vec<String> = client
+            .endpoints()
+            .map(|endpoint| {
+                crate::endpoint_url(endpoint, "/v2/updates/price/stream").map(String::from)
+            })
+            .collect::<Result<_, _>>()?;
+        let max_feeds_per_connection = options.max_feeds_per_connection.max(1);
+        let mut last_event_id = options.last_event_id;
+        let snapshot = options.snapshot.then(|| ids.clone());
+        let feeds = Arc::new(StreamFeeds {
+            ids: Mutex::new(ids),
+            changed: Notify::new(),
+            misuse_policy: self.misuse_policy,
+            policy: self.feed_policy.clone(),
+        });
+        let cancellation = options.cancellation.unwrap_or_default();
+        let finished = CancellationToken::new();
+        let connection = Connection {
+            client,
+            urls,
+            stats: stats.clone(),
+            resume_from_last_event_id: options.resume_from_last_event_id,
+            backfill: options.backfill,
+            reconnect: options.reconnect,
+            idle_timeout: options.idle_timeout,
+            allow_unordered: options.allow_unordered,
+            benchmarks_only: options.benchmarks_only,
+            parsed,
+            permits,
+            on_connected: options.on_connected,
+            on_disconnected: options.on_disconnected,
+            on_reconnecting: options.on_reconnecting,
+            // giving up stops every connection of this stream, without cancelling a token shared with others
+            cancellation: cancellation.child_token(),
+            reporter: FailureReporter {
+                subscription_id: id,
+                stats: stats.clone(),
+                hook: options.on_failure,
+            },
+        };
+
+        let task = rt::spawn({
+            // dropped with the task, even when it's aborted before it first runs
+            let running = RunningGuard {
+                stats: stats.clone(),
+                finished: finished.clone(),
+            };
+            let feeds = feeds.clone();
+            let stats = stats.clone();
+            async move {
+                let _running = running;
+                let (sender, mut updates) = mpsc::unbounded_channel();
+                // delivery ends once every connection has stopped and dropped the sender
+                let connections = async {
+                    let sender = sender;
+                    while !connection.cancellation.is_cancelled() {
+                        let shards: Vec<Vec<FeedId>> = feeds
+                            .lock()
+                            .chunks(max_feeds_per_connection)
+                            .map(<[FeedId]>::to_vec)
+                            .collect();
+                        // event ids are specific to a connection, so only a stream with a single one can resume
+                        // from them, and only until its feeds change
+                        let last_event_id = last_event_id.take().filter(|_| shards.len() == 1);
+                        stats
+                            .connections
+                            .store(shards.len().max(1) as u64, Ordering::Relaxed);
+                        // closes this set of connections once the feeds change, without stopping the stream
+                        let resubscribe = connection.cancellation.child_token();
+                        let run = std::pin::pin!(async {
+                            if shards.is_empty() {
+                                resubscribe.cancelled().await;
+                            }
+                            future::join_all(shards.into_iter().map(|ids| {
+                                connection.run(
+                                    ids,
+                                    last_event_id.clone(),
+                                    sender.clone(),
+                                    &resubscribe,
+                                )
+                            }))
+                            .await;
+                        });
+                        let changed = std::pin::pin!(feeds.changed.notified());
+                        if let Either::Right((_, run)) = future::select(run, changed).await {
+                            resubscribe.cancel();
+                            run.await;
+                        }
+                    }
+                };
+                let deliver = async {
+                    if let Some(ids) = snapshot {
+                        for update in connection.snapshot(&ids).await {
+                            on_event(StreamBatch {
+                                update,
+                                snapshot: true,
+                            });
+                        }
+                    }
+                    while let Some(update) = updates.recv().await {
+                        on_event(StreamBatch {
+                            update,
+                            snapshot: false,
+                        });
+                    }
+                };
+                future::join(connections, deliver).await;
+            }
+        });
+
+        Ok(StreamHandle {
+            id,
+            task,
+            stats,
+            cancellation,
+            finished,
+            released: AtomicBool::new(false),
+            feeds,
+        })
+    }
+}
+
+/// Updates of a message queued for delivery to a bounded channel, see [`forward`]
+pub(crate) struct Queued<I> {
+    pub(crate) updates: I,
+    /// Returns the permit taken for the message once its updates are delivered
+    pub(crate) release: Option<Arc<Release>>,
+}
+
+/// Returns a permit taken for a message to the stream once dropped, see [`Backpressure::Block`]
+///
+/// Shared by every channel the message is delivered to, so the stream reads further once all of them took it.
+pub(crate) struct Release(Arc<Semaphore>);
+
+impl Release {
+    /// Release of the permit taken for `batch`, if any, snapshots being fetched over REST without taking one
+    pub(crate) fn of(batch: &StreamBatch, permits: &Option<Arc<Semaphore>>) -> Option<Arc<Self>> {
+        permits
+            .as_ref()
+            .filter(|_| !batch.snapshot)
+            .map(|permits| Arc::new(Self(permits.clone())))
+    }
+}
+
+impl Drop for Release {
+    fn drop(&mut self) {
+        self.0.add_permits(1);
+    }
+}
+
+/// Forwards the updates of `queued` messages to `sender`, handling those which don't fit as `backpressure`
+/// dictates, until either side closes, calling `sent` for each update sent
+///
+/// Updates held back once the queue closes are still delivered.
+pub(crate) async fn forward<T, I>(
+    mut queued: mpsc::UnboundedReceiver<Queued<I>>,
+    sender: mpsc::Sender<T>,
+    backpressure: Backpressure,
+    sent: impl Fn() + Send,
+) where
+    T: FeedUpdate + Send,
+    I: IntoIterator<Item = T> + Send,
+    I::IntoIter: Send,
+{
+    let capacity = sender.max_capacity();
+    // updates held back while the channel is full, sent before any later one
+    let mut held = VecDeque::new();
+    loop {
+        let next = std::pin::pin!(queued.recv());
+        let next = if held.is_empty() {
+            match future::select(std::pin::pin!(sender.closed()), next).await {
+                Either::Right((next, _)) => next,
+                Either::Left(_) => return,
+            }
+        } else {
+            match future::select(std::pin::pin!(sender.reserve()), next).await {
+                Either::Left((Ok(permit), _)) => {
+                    sent();
+                    permit.send(held.pop_front().expect("held updates"));
+                    continue;
+                }
+                Either::Left((Err(_), _)) => return,
+                Either::Right((next, _)) => next,
+            }
+        };
+        let Some(Queued { updates, release }) = next else {
+            break;
+        };
+        for update in updates {
+            if backpressure == Backpressure::Block {
+                let Ok(permit) = sender.reserve().await else {
+                    return;
+                };
+                sent();
+                permit.send(update);
+                continue;
+            }
+            if !held.is_empty() {
+                backpressure.hold(&mut held, update, capacity);
+                continue;
+            }
+            match sender.try_reserve() {
+                Ok(permit) => {
+                    sent();
+                    permit.send(update);
+                }
+                Err(mpsc::error::TrySendError::Full(())) => {
+                    backpressure.hold(&mut held, update, capacity)
+                }
+                Err(mpsc::error::TrySendError::Closed(())) => return,
+            }
+        }
+        drop(release);
+    }
+    for update in held {
+        let Ok(permit) = sender.reserve().await else {
+            return;
+        };
+        sent();
+        permit.send(update);
+    }
+}
+
+/// Outcome of waiting for a connection's next event
+enum Next {
+    Event(Result<Event, EventSourceError>),
+    /// Nothing was received within the idle timeout
+    Idle,
+    /// The stream was cancelled or the event source closed
+    Stopped,
+}
+
+/// Message of a stream without parsed prices, skipping over any hermes includes regardless
+#[derive(Deserialize)]
+struct BinaryMessage {
+    binary: BinaryUpdate,
+}
+
+/// One SSE connection of a stream, streaming a shard of its feeds
+struct Connection {
+    client: HermesClient,
+    /// Stream urls of each endpoint, in the order they are tried
+    urls: Vec<String>,
+    stats: Arc<StreamStats>,
+    resume_from_last_event_id: bool,
+    backfill: Option<Duration>,
+    reconnect: ReconnectPolicy,
+    idle_timeout: Option<Duration>,
+    allow_unordered: Option<bool>,
+    benchmarks_only: Option<bool>,
+    /// Whether messages include parsed prices, otherwise only their binary data is deserialized
+    parsed: bool,
+    /// Taken for each message received, and returned once it's consumed, see [`Backpressure::Block`]
+    permits: Option<Arc<Semaphore>>,
+    on_connected: Option<ConnectedHook>,
+    on_disconnected: Option<DisconnectedHook>,
+    on_reconnecting: Option<ReconnectingHook>,
+    cancellation: CancellationToken,
+    reporter: FailureReporter,
+}
+
+impl Connection {
+    /// Latest prices of `ids` fetched over REST, one update per request, reporting feeds without a price
+    async fn snapshot(&self, ids: &[FeedId]) -> Vec<PriceUpdate> {
+        let max_concurrency = self.client.chunking.max_concurrency;
+        let mut updates = Vec::new();
+        let mut failed = Vec::new();
+        for (chunk, update) in self.client.get_chunks(ids, max_concurrency).await {
+            match update {
+                Ok(update) => {
+                    let returned: Vec<FeedId> =
+                        update.parsed.iter().flatten().map(|f| f.id).collect();
+                    failed.extend(
+                        chunk
+                            .iter()
+                            .filter(|id| !returned.contains(id))
+                            .map(|id| format!("{id} ({})", FeedFailure::NotReturned)),
+                    );
+                    updates.push(update);
+                }
+                Err(err) => {
+                    let failure = FeedFailure::Request(Arc::new(err));
+                    failed.extend(chunk.iter().map(|id| format!("{id} ({failure})")));
+                }
+            }
+        }
+        if !failed.is_empty() {
+            self.reporter.report(
+                &self.client.base_url,
+                format_args!("snapshot is missing feeds: {}", failed.join(", ")),
+            );
+        }
+        updates
+    }
+
+    /// Updates missed while reconnecting of the feeds whose first update since is `update`, fetched over REST, see
+    /// [`StreamOptions::backfill`]
+    ///
+    /// `published` holds the publish time of each feed's latest update, and `reconnected` the feeds yet to update
+    /// since reconnecting.
+    async fn backfill(
+        &self,
+        update: &PriceUpdate,
+        window: Duration,
+        published: &mut HashMap<FeedId, i64>,
+        reconnected: &mut HashSet<FeedId>,
+    ) -> Vec<PriceUpdate> {
+        // publish times bounding each feed's missed updates, the last delivered or the start of the window, and the
+        // first since reconnecting
+        let mut gaps = HashMap::new();
+        for feed in update.parsed.iter().flatten() {
+            let publish_time = feed.price.publish_time;
+            if let (true, Some(&last)) = (reconnected.remove(&feed.id), published.get(&feed.id)) {
+                if publish_time > last + 1 {
+                    let from = last.max(publish_time - window.as_secs() as i64 - 1);
+                    gaps.insert(feed.id, (from, publish_time));
+                }
+            }
+            let latest = published.entry(feed.id).or_insert(publish_time);
+            *latest = publish_time.max(*latest);
+        }
+        let options = PriceUpdatesByTimeOptions {
+            benchmarks_only: self.benchmarks_only,
+            allow_unordered: self.allow_unordered,
+        };
+        let mut updates = Vec::new();
+        // the earliest publish time still missed, skipping past updates returned for later ones
+        while let Some(publish_time) = gaps
+            .values()
+            .filter(|&&(last, first)| last + 1 < first)
+            .map(|&(last, _)| last + 1)
+            .min()
+        {
+            let ids: Vec<FeedId> = gaps
+                .iter()
+                .filter(|(_, &(last, first))| last < publish_time && publish_time < first)
+                .map(|(id, _)| *id)
+                .collect();
+            let mut update = match self
+                .client
+                .get_price_updates_by_time(publish_time, &ids, &options)
+                .await
+            {
+                Ok(update) => update,
+                Err(err) => {
+                    self.reporter.report(
+                        &self.client.base_url,
+                        format_args!(
+                            "failed to backfill updates published at {publish_time}: {err}"
+                        ),
+                    );
+                    break;
+                }
+            };
+            // hermes returns the first update at or after the publish time, which may be one already delivered
+            if let Some(parsed) = &mut update.parsed {
+                parsed.retain_mut(|feed| match gaps.get_mut(&feed.id) {
+                    Some((last, first))
+                        if *last < feed.price.publish_time && feed.price.publish_time < *first =>
+                    {
+                        *last = feed.price.publish_time;
+                        true
+                    }
+                    _ => false,
+                });
+                if !parsed.is_empty() {
+                    updates.push(update);
+                }
+            }
+            // nothing was published between the requested publish time and the update returned
+            for id in &ids {
+                if let Some((last, _)) = gaps.get_mut(id) {
+                    *last = publish_time.max(*last);
+                }
+            }
+        }
+        updates
+    }
+
+    /// Next event of `es`, or why there is none, leaving the event source to be closed
+    async fn next_event(&self, es: &mut EventSource, cancellation: &CancellationToken) -> Next {
+        // reading no further until the consumer catches up pauses hermes
+        let permit = match &self.permits {
+            Some(permits) => {
+                let stopped = std::pin::pin!(cancellation.cancelled());
+                match future::select(stopped, std::pin::pin!(permits.acquire())).await {
+                    Either::Right((Ok(permit), _)) => Some(permit),
+                    _ => return Next::Stopped,
+                }
+            }
+            None => None,
+        };
+        let idle = std::pin::pin!(async {
+            match self.idle_timeout {
+                Some(timeout) => rt::sleep(timeout).await,
+                None => future::pending().await,
+            }
+        });
+        let stopped = std::pin::pin!(cancellation.cancelled());
+        match future::select(future::select(stopped, idle), es.next()).await {
+            Either::Left((Either::Left(_), _)) => Next::Stopped,
+            Either::Left((Either::Right(_), _)) => Next::Idle,
+            Either::Right((Some(event), _)) => {
+                if let (Ok(Event::Message(_)), Some(permit)) = (&event, permit) {
+                    permit.forget();
+                }
+                Next::Event(event)
+            }
+            Either::Right((None, _)) => Next::Stopped,
+        }
+    }
+
+    /// Waits for `duration` before reconnecting, returning early once `cancellation` is cancelled
+    async fn pause(&self, duration: Duration, cancellation: &CancellationToken) {
+        let _backing_off = StateGuard::enter(&self.stats.backing_off);
+        let sleep = std::pin::pin!(rt::sleep(duration));
+        future::select(std::pin::pin!(cancellation.cancelled()), sleep).await;
+    }
+
+    /// Counts a failed connection to `url` and waits before reconnecting, returning `false` once the stream gives up
+    async fn backoff(
+        &self,
+        url: &str,
+        failures: &mut u32,
+        cancellation: &CancellationToken,
+    ) -> bool {
+        *failures += 1;
+        if self.reconnect.gives_up(*failures) {
+            self.reporter.report(
+                url,
+                format_args!("giving up after {failures} failed connections"),
+            );
+            self.stats.gave_up.store(true, Ordering::Relaxed);
+            self.cancellation.cancel();
+            return false;
+        }
+        let delay = self.reconnect.delay_with(*failures, &self.client.rng);
+        if let Some(hook) = &self.on_reconnecting {
+            hook(*failures, delay);
+        }
+        self.pause(delay, cancellation).await;
+        true
+    }
+
+    /// Streams updates for `ids` to `sender`, reconnecting until the stream is aborted, gives up or `cancellation`,
+    /// a child of the stream's, is cancelled
+    async fn run(
+        &self,
+        ids: Vec<FeedId>,
+        mut last_event_id: Option<String>,
+        sender: mpsc::UnboundedSender<PriceUpdate>,
+        cancellation: &CancellationToken,
+    ) {
+        let Self {
+            client,
+            urls,
+            stats,
+            reporter,
+            ..
+        } = self;
+        let mut refresh_token = false;
+        let mut connected_before = false;
+        // publish time of each feed's latest update, and feeds yet to update since reconnecting
+        let mut published = HashMap::new();
+        let mut reconnected = HashSet::new();
+        // consecutive connections which failed without receiving a message
+        let mut failures = 0;
+        let mut endpoint = 0;
+        while !cancellation.is_cancelled() {
+            if connected_before {
+                stats.reconnects.fetch_add(1, Ordering::Relaxed);
+                reconnected.extend(ids.iter().copied());
+            }
+            connected_before = true;
+            let url = &urls[endpoint];
+            let mut req = client.http.get(url);
+            for id in &ids {
+                req = req.query(&[("ids[]", id)]);
+            }
+            if let Some(allow_unordered) = self.allow_unordered {
+                req = req.query(&[("allow_unordered", allow_unordered)]);
+            }
+            if let Some(benchmarks_only) = self.benchmarks_only {
+                req = req.query(&[("benchmarks_only", benchmarks_only)]);
+            }
+            if !self.parsed {
+                req = req.query(&[("parsed", false)]);
+            }
+            if let Some(last_event_id) = &last_event_id {
+                req = req.header("Last-Event-ID", last_event_id);
+            }
+            if let Some((header, correlation_id)) = client.next_correlation_id() {
+                req = req.header(&*header, correlation_id);
+            }
+            let req = match client.prepare(req, refresh_token).await {
+                Ok(req) => req,
+                Err(err) => {
+                    reporter.report(url, format_args!("failed to prepare SSE request: {err}"));
+                    if !self.backoff(url, &mut failures, cancellation).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            refresh_token = false;
+
+            let mut es = match EventSource::new(req) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    reporter.report(url, format_args!("failed to connect SSE: {err}"));
+                    if !self.backoff(url, &mut failures, cancellation).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            let mut received = false;
+            let mut connected = None;
+            // replaced when the connection closes for any reason other than the stream stopping
+            let mut reason = DisconnectReason::Stopped;
+            loop {
+                let event = match self.next_event(&mut es, cancellation).await {
+                    Next::Event(event) => event,
+                    Next::Idle => {
+                        let timeout = self.idle_timeout.unwrap_or_default();
+                        reporter
+                            .report(url, format_args!("no events for {timeout:?}, reconnecting"));
+                        reason = DisconnectReason::Idle;
+                        break;
+                    }
+                    Next::Stopped if !self.cancellation.is_cancelled() => {
+                        reason = DisconnectReason::Resubscribed;
+                        break;
+                    }
+                    Next::Stopped => break,
+                };
+                match event {
+                    Ok(Event::Message(msg)) => {
+                        received = true;
+                        stats.messages_received.fetch_add(1, Ordering::Relaxed);
+                        stats
+                            .bytes_received
+                            .fetch_add(msg.data.len() as u64, Ordering::Relaxed);
+                        stats
+                            .last_message_at_millis
+                            .store(rt::unix_timestamp_millis(), Ordering::Relaxed);
+                        if self.resume_from_last_event_id && !msg.id.is_empty() {
+                            last_event_id = Some(msg.id);
+                        }
+                        let update = if self.parsed {
+                            serde_json::from_str::<PriceUpdate>(&msg.data)
+                        } else {
+                            serde_json::from_str::<BinaryMessage>(&msg.data).map(|msg| {
+                                PriceUpdate {
+                                    binary: msg.binary,
+                                    parsed: None,
+                                }
+                            })
+                        };
+                        let update = match update {
+                            Ok(update) => update,
+                            Err(err) => {
+                                reporter.report(
+                                    url,
+                                    format_args!("failed to parse SSE message: {err}"),
+                                );
+                                if let Some(permits) = &self.permits {
+                                    permits.add_permits(1);
+                                }
+                                continue;
+                            }
+                        };
+                        if let Some(window) = self.backfill {
+                            let missed = self
+                                .backfill(&update, window, &mut published, &mut reconnected)
+                                .await;
+                            for missed in missed {
+                                // each delivered update returns a permit, like those of messages
+                                if let Some(permits) = &self.permits {
+                                    let stopped = std::pin::pin!(cancellation.cancelled());
+                                    match future::select(stopped, std::pin::pin!(permits.acquire()))
+                                        .await
+                                    {
+                                        Either::Right((Ok(permit), _)) => permit.forget(),
+                                        _ => break,
+                                    }
+                                }
+                                let _ = sender.send(missed);
+                            }
+                        }
+                        let _ = sender.send(update);
+                    }
+                    Ok(Event::Open) => {
+                        if connected.is_none() {
+                            connected = Some(StateGuard::enter(&stats.connected));
+                            if let Some(hook) = &self.on_connected {
+                                hook(url);
+                            }
+                        }
+                    }
+                    Err(EventSourceError::StreamEnded) => {
+                        reporter.report(url, "stream ended, reconnecting");
+                        reason = DisconnectReason::Ended;
+                        break;
+                    }
+                    Err(EventSourceError::InvalidStatusCode(StatusCode::UNAUTHORIZED, _)) => {
+                        reporter.report(url, "sse unauthorized, refreshing token");
+                        refresh_token = true;
+                        reason = DisconnectReason::Unauthorized;
+                        break;
+                    }
+                    Err(err) => {
+                        reporter.report(url, format_args!("sse error: {err}"));
+                        reason = DisconnectReason::Error(err.to_string());
+                        break;
+                    }
+                }
+            }
+            es.close();
+            let was_connected = connected.take().is_some();
+            if let (true, Some(hook)) = (was_connected, &self.on_disconnected) {
+                hook(url, &reason);
+            }
+            if cancellation.is_cancelled() {
+                break;
+            }
+            // stay on an endpoint which delivered messages, otherwise fail over to the next one
+            if received {
+                failures = 0;
+            } else {
+                endpoint = (endpoint + 1) % urls.len();
+            }
+            if !self.backoff(url, &mut failures, cancellation).await {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD},
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_stream_stats() {
+        let event = price_update_json(&[ETH_USD], 1, 1).to_string();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![event.clone()],
+        )])
+        .await
+        .unwrap();
+        let (mut rx, handle) = HermesClient::new(mock.url())
+            .subscribe(&[ETH_USD])
+            .await
+            .unwrap()
+            .into_parts();
+        // one event per connection
+        for _ in 0..3 {
+            rx.recv().await.unwrap();
+        }
+        handle.abort();
+        let stats = handle.stats();
+        assert!(stats.messages_received() >= 3);
+        assert_eq!(
+            stats.bytes_received(),
+            stats.messages_received() * event.len() as u64
+        );
+        assert!(stats.reconnects() >= 2);
+        assert!(stats.uptime() < Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_stream_failure_context() {
+        let event = price_update_json(&[ETH_USD], 1, 1).to_string();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![event],
+        )])
+        .await
+        .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let options = StreamOptions {
+            on_failure: Some(Arc::new(move |failure: &StreamFailure| {
+                let _ = tx.send(failure.clone());
+            })),
+            ..Default::default()
+        };
+        let handle = HermesClient::new(mock.url())
+            .stream_price_updates_with_options(&[ETH_USD], options, |_| {})
+            .await
+            .unwrap();
+        let failure = rx.recv().await.unwrap();
+        handle.abort();
+
+        assert_eq!(failure.subscription_id, handle.id());
+        assert_eq!(
+            failure.endpoint,
+            format!("{}/v2/updates/price/stream", mock.url())
+        );
+        assert_eq!(failure.feed_count, 1);
+        assert!(failure.last_activity.is_some());
+        let line = failure.to_string();
+        assert!(line.starts_with(&format!("stream {} failed: stream ended", handle.id())));
+        assert!(line.contains("feeds=1"));
+    }
+
+    /// The deprecated callback adapter keeps streaming once its handle is dropped, like it always did
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_stream_price_updates_compat() {
+        let event = price_update_json(&[ETH_USD], 1, 1).to_string();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![event],
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        drop(
+            client
+                .stream_price_updates(vec![ETH_USD.to_hex()], move |update| {
+                    let _ = tx.send(update);
+                })
+                .await
+                .unwrap(),
+        );
+        for _ in 0..2 {
+            assert_eq!(rx.recv().await.unwrap().id, ETH_USD);
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = client
+            .stream_price_updates(vec![format!("0x{ETH_USD}")], move |update| {
+                let _ = tx.send(update);
+            })
+            .await
+            .unwrap();
+        rx.recv().await.unwrap();
+        handle.abort();
+        // the callback is dropped along with the stopped stream, closing the channel
+        while rx.recv().await.is_some() {}
+
+        assert!(matches!(
+            client
+                .stream_price_updates(vec!["eth".to_string()], |_| {})
+                .await,
+            Err(HermesError::InvalidFeedId(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_sharding() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            max_feeds_per_connection: 1,
+            ..Default::default()
+        };
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD, BTC_USD], options)
+            .await
+            .unwrap();
+        // each connection delivers to the same subscription
+        for _ in 0..2 {
+            subscription.next().await.unwrap();
+        }
+
+        let mut queries: Vec<_> = mock
+            .requests()
+            .await
+            .into_iter()
+            .filter_map(|request| request.query)
+            .collect();
+        queries.dedup();
+        assert!(queries.len() >= 2);
+        for query in queries {
+            assert_eq!(query.matches("ids%5B%5D=").count(), 1);
+        }
+        assert!(mock.requests().await.iter().any(|r| r
+            .query
+            .as_ref()
+            .unwrap()
+            .contains(&BTC_USD.to_hex())));
+    }
+
+    #[tokio::test]
+    async fn test_stream_snapshot() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 7, 1),
+            ),
+            RecordedResponse::events(
+                "/v2/updates/price/stream",
+                vec![price_update_json(&[ETH_USD], 8, 2).to_string()],
+            ),
+        ])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            snapshot: true,
+            ..Default::default()
+        };
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+
+        let first = subscription.next().await.unwrap();
+        assert!(first.snapshot);
+        assert_eq!(first.price.publish_time, 1);
+        let live = subscription.next().await.unwrap();
+        assert!(!live.snapshot);
+        assert_eq!(live.price.publish_time, 2);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_batches() {
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json(
+                "/v2/updates/price/latest",
+                price_update_json(&[ETH_USD], 7, 1),
+            ),
+            RecordedResponse::events(
+                "/v2/updates/price/stream",
+                vec![price_update_json(&[ETH_USD, BTC_USD], 8, 2).to_string()],
+            ),
+        ])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            snapshot: true,
+            ..Default::default()
+        };
+        let mut batches = HermesClient::new(mock.url())
+            .subscribe_batches(&[ETH_USD, BTC_USD], options)
+            .await
+            .unwrap();
+
+        // hermes only returned ETH in the snapshot
+        let snapshot = batches.next().await.unwrap();
+        assert!(snapshot.snapshot);
+        assert_eq!(snapshot.update.parsed.as_ref().unwrap().len(), 1);
+        assert_eq!(snapshot.update.binary.data, ["504e4155"]);
+        // both feeds of a message arrive together, along with the binary data
+        let batch = batches.next().await.unwrap();
+        assert!(!batch.snapshot);
+        assert_eq!(batch.update.binary.data, ["504e4155"]);
+        let updates: Vec<_> = batch.into_updates().map(|u| u.id).collect();
+        assert_eq!(updates, [ETH_USD, BTC_USD]);
+        assert_eq!(batches.handle().stats().buffered(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_stream_binary_updates() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD, BTC_USD], 8, 2).to_string()],
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let mut subscription = client
+            .subscribe_with_binary(&[ETH_USD, BTC_USD], StreamOptions::default())
+            .await
+            .unwrap();
+        let eth = subscription.next().await.unwrap();
+        let btc = subscription.next().await.unwrap();
+        assert_eq!((eth.update.id, btc.update.id), (ETH_USD, BTC_USD));
+        assert_eq!(eth.binary.encoding, "hex");
+        assert_eq!(eth.binary.data, ["504e4155"]);
+        // updates from the same message share its binary data
+        assert!(Arc::ptr_eq(&eth.binary, &btc.binary));
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = client
+            .stream_price_updates_with_binary(&[ETH_USD], StreamOptions::default(), move |update| {
+                let _ = tx.send(update);
+            })
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().binary.data, ["504e4155"]);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_binary() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![
+                // parsed prices hermes includes anyway are skipped without being deserialized
+                serde_json::json!({
+                    "binary": {"encoding": "hex", "data": ["504e4155"]},
+                    "parsed": [{"unexpected": true}],
+                })
+                .to_string(),
+            ],
+        )])
+        .await
+        .unwrap();
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe_binary(&[ETH_USD], StreamOptions::default())
+            .await
+            .unwrap();
+        let binary = subscription.next().await.unwrap();
+        assert_eq!(binary.encoding, "hex");
+        assert_eq!(binary.data, ["504e4155"]);
+        let requests = mock.requests().await;
+        assert_eq!(
+            requests[0].query.as_deref(),
+            Some(format!("ids%5B%5D={ETH_USD}&parsed=false").as_str())
+        );
+        subscription.handle().abort();
+    }
+
+    #[tokio::test]
+    async fn test_stream_update_without_metadata() {
+        let mut event = price_update_json(&[ETH_USD], 1, 1);
+        event["parsed"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("metadata");
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![event.to_string()],
+        )])
+        .await
+        .unwrap();
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe(&[ETH_USD])
+            .await
+            .unwrap();
+        let update = subscription.next().await.unwrap();
+        assert_eq!(update.price.price, "1");
+        assert!(update.metadata.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let events: Vec<_> = (1..=3)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        let mut subscription = client.subscribe(&[ETH_USD, ETH_USD]).await.unwrap();
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(subscription.next().await.unwrap());
+        }
+        // duplicate ids are only sent once
+        let query = mock.requests().await[0].query.clone().unwrap();
+        assert_eq!(query.matches("ids%5B%5D=").count(), 1);
+        assert_eq!(
+            received
+                .iter()
+                .map(|u| u.price.publish_time)
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert!(subscription.handle().stats().messages_received() >= 3);
+
+        let (mut updates, handle) = subscription.into_parts();
+        handle.abort();
+        while updates.recv().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn test_price_update_stream() {
+        let events: Vec<_> = (1..=4)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(mock.url())
+            .feed_policy(crate::policy::FeedPolicy::allow([ETH_USD]))
+            .build()
+            .unwrap();
+
+        let even: Vec<_> = client
+            .price_update_stream(&[ETH_USD])
+            .map(Result::unwrap)
+            .filter(|update| future::ready(update.price.publish_time % 2 == 0))
+            .take(2)
+            .map(|update| update.price.publish_time)
+            .collect()
+            .await;
+        assert_eq!(even, [2, 4]);
+
+        let failed: Vec<_> = client.price_update_stream(&[BTC_USD]).collect().await;
+        assert!(matches!(
+            failed.as_slice(),
+            [Err(HermesError::FeedNotAllowed(_))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_try_price_update_stream() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![
+                "not json".to_string(),
+                price_update_json(&[ETH_USD], 1, 1).to_string(),
+            ],
+        )])
+        .await
+        .unwrap();
+        let (tx, mut hooked) = mpsc::unbounded_channel();
+        let options = StreamOptions {
+            on_failure: Some(Arc::new(move |failure: &StreamFailure| {
+                let _ = tx.send(failure.clone());
+            })),
+            ..Default::default()
+        };
+
+        let items: Vec<_> = HermesClient::new(mock.url())
+            .try_price_update_stream(&[ETH_USD], options)
+            .take(3)
+            .collect()
+            .await;
+        match items.as_slice() {
+            [Err(HermesError::Stream(parse)), Ok(update), Err(HermesError::Stream(ended))] => {
+                assert!(parse.error.starts_with("failed to parse SSE message"));
+                assert_eq!(update.id, ETH_USD);
+                assert_eq!(ended.error, "stream ended, reconnecting");
+            }
+            items => panic!("unexpected items {items:?}"),
+        }
+        // the caller's hook still sees every failure
+        assert!(hooked
+            .recv()
+            .await
+            .unwrap()
+            .error
+            .starts_with("failed to parse SSE message"));
+    }
+
+    #[tokio::test]
+    async fn test_async_callback() {
+        let events: Vec<_> = (1..=3)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+
+        // a full bounded channel makes the handler wait rather than drop updates
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let handle = HermesClient::new(mock.url())
+            .stream_price_updates_async(&[ETH_USD], StreamOptions::default(), move |update| {
+                let tx = tx.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    let _ = tx.send(update.price.publish_time).await;
+                }
+            })
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(rx.recv().await.unwrap());
+        }
+        assert_eq!(received, [1, 2, 3]);
+
+        handle.abort();
+        // the handler is dropped once its task ends
+        while rx.recv().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let events: Vec<_> = (1..=3)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let mut subscription = client.subscribe(&[ETH_USD]).await.unwrap();
+        subscription.next().await.unwrap();
+        subscription.handle().shutdown().await;
+        assert!(!subscription.handle().stats().is_running());
+        // updates received before the shutdown are still delivered, then the subscription ends
+        while subscription.next().await.is_some() {}
+
+        let token = CancellationToken::new();
+        let options = StreamOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut subscription = client
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+        subscription.next().await.unwrap();
+        token.cancel();
+        while subscription.next().await.is_some() {}
+        assert!(!subscription.handle().stats().is_running());
+        assert!(subscription.handle().cancellation_token().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_stream_state() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe(&[ETH_USD])
+            .await
+            .unwrap();
+        assert!(subscription.handle().last_event_at().is_none());
+        subscription.next().await.unwrap();
+        let handle = subscription.handle();
+        assert!(handle.last_event_at().is_some());
+        assert_ne!(handle.state(), StreamState::Stopped);
+
+        handle.stop();
+        while handle.state() != StreamState::Stopped {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!handle.stats().is_running());
+    }
+
+    /// The deprecated callback adapter delivers the same updates, in the same order, as a subscription
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_callback_adapter_parity() {
+        let events: Vec<_> = (1..=3)
+            .map(|i| price_update_json(&[ETH_USD], i * 100, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = client
+            .stream_price_updates(vec![ETH_USD.to_hex()], move |update| {
+                let _ = tx.send(update);
+            })
+            .await
+            .unwrap();
+        let mut from_callback = Vec::new();
+        for _ in 0..3 {
+            from_callback.push(rx.recv().await.unwrap());
+        }
+        handle.abort();
+        // the callback is dropped along with the stopped stream
+        while rx.recv().await.is_some() {}
+
+        let mut subscription = client.subscribe(&[ETH_USD]).await.unwrap();
+        let mut from_subscription = Vec::new();
+        for _ in 0..3 {
+            from_subscription.push(subscription.next().await.unwrap());
+        }
+        subscription.handle().abort();
+        let summary = |updates: &[ParsedPriceUpdate]| -> Vec<_> {
+            updates
+                .iter()
+                .map(|u| (u.id, u.price.price.clone(), u.price.publish_time))
+                .collect()
+        };
+        assert_eq!(summary(&from_callback), summary(&from_subscription));
+    }
+
+    #[tokio::test]
+    async fn test_stream_failover() {
+        let primary = MockHermes::start(vec![RecordedResponse::status(
+            "/v2/updates/price/stream",
+            503,
+        )])
+        .await
+        .unwrap();
+        let event = price_update_json(&[ETH_USD], 1, 1).to_string();
+        let fallback = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![event],
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::builder(primary.url())
+            .fallback_url(fallback.url())
+            .build()
+            .unwrap();
+
+        let (mut rx, handle) = client.subscribe(&[ETH_USD]).await.unwrap().into_parts();
+        for _ in 0..2 {
+            rx.recv().await.unwrap();
+        }
+        handle.abort();
+        assert_eq!(primary.requests().await.len(), 1);
+        assert!(fallback.requests().await.len() >= 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_reconnect_backoff() {
+        let mock = MockHermes::start(vec![RecordedResponse::status(
+            "/v2/updates/price/stream",
+            503,
+        )])
+        .await
+        .unwrap();
+        let subscription = HermesClient::new(mock.url())
+            .subscribe(&[ETH_USD])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        subscription.handle().abort();
+        // delays of at least 250ms then 500ms leave room for three attempts
+        let attempts = mock.requests().await.len();
+        assert!((2..=3).contains(&attempts), "{attempts} attempts");
+    }
+
+    /// Options recording each lifecycle event of a stream to the returned channel
+    fn lifecycle_options() -> (StreamOptions, mpsc::UnboundedReceiver<String>) {
+        let (tx, events) = mpsc::unbounded_channel();
+        let options = StreamOptions {
+            on_connected: Some(Arc::new({
+                let tx = tx.clone();
+                move |_: &str| {
+                    let _ = tx.send("connected".to_string());
+                }
+            })),
+            on_disconnected: Some(Arc::new({
+                let tx = tx.clone();
+                move |_: &str, reason: &DisconnectReason| {
+                    let _ = tx.send(format!("disconnected: {reason}"));
+                }
+            })),
+            on_reconnecting: Some(Arc::new(move |attempt, _| {
+                let _ = tx.send(format!("reconnecting: {attempt}"));
+            })),
+            ..Default::default()
+        };
+        (options, events)
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_hooks() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string(); 3],
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        let (options, mut events) = lifecycle_options();
+        let subscription = client
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        for _ in 0..4 {
+            received.push(events.recv().await.unwrap());
+        }
+        assert_eq!(
+            received,
+            [
+                "connected",
+                "disconnected: stream ended",
+                "reconnecting: 1",
+                "connected"
+            ]
+        );
+        subscription.handle().abort();
+
+        // slow events keep the connection open until the stream is stopped
+        mock.set_conditions(crate::mock::NetworkConditions {
+            latency: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .await;
+        let (options, mut events) = lifecycle_options();
+        let mut subscription = client
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+        subscription.next().await.unwrap();
+        subscription.handle().shutdown().await;
+        assert_eq!(events.recv().await.unwrap(), "connected");
+        assert_eq!(events.recv().await.unwrap(), "disconnected: stream stopped");
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        // responses stall for longer than the idle timeout
+        mock.set_conditions(crate::mock::NetworkConditions {
+            latency: Duration::from_secs(5),
+            ..Default::default()
+        })
+        .await;
+        let options = StreamOptions {
+            idle_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let subscription = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+        while mock.requests().await.len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let stats = subscription.handle().stats();
+        assert_eq!(stats.reconnects(), 1);
+        assert_eq!(
+            stats.last_error().as_deref(),
+            Some("no events for 100ms, reconnecting")
+        );
+        subscription.handle().abort();
+    }
+
+    #[tokio::test]
+    async fn test_change_feeds() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        // connections are pending until the feeds change
+        mock.set_conditions(crate::mock::NetworkConditions {
+            latency: Duration::from_secs(5),
+            ..Default::default()
+        })
+        .await;
+        let subscription = HermesClient::new(mock.url())
+            .subscribe(&[ETH_USD])
+            .await
+            .unwrap();
+        let handle = subscription.handle();
+        let queries = || async {
+            mock.requests()
+                .await
+                .into_iter()
+                .map(|request| request.query.unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+        while queries().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        handle.add_feeds(&[ETH_USD, BTC_USD]).unwrap();
+        assert_eq!(handle.feeds(), [ETH_USD, BTC_USD]);
+        assert_eq!(handle.stats().feed_count(), 2);
+        while queries().await.len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            queries().await[1],
+            format!("ids%5B%5D={ETH_USD}&ids%5B%5D={BTC_USD}")
+        );
+
+        handle.remove_feeds(&[ETH_USD]);
+        assert_eq!(handle.feeds(), [BTC_USD]);
+        while queries().await.len() < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(queries().await[2], format!("ids%5B%5D={BTC_USD}"));
+        // resubscribing isn't counted as reconnecting after a failure
+        assert_eq!(handle.stats().reconnects(), 0);
+        handle.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_channel() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+        let mut updates = client.stream_to_channel(&[ETH_USD], 1).await.unwrap();
+        assert_eq!(updates.recv().await.unwrap().id, ETH_USD);
+
+        // the stream stops once the receiver is dropped, and is then forgotten
+        drop(updates);
+        while !client.diagnostics().streams.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// How [`backpressured`] consumes a stream
+    #[derive(Clone, Copy)]
+    enum Consumer {
+        Channel,
+        Subscription,
+        Shared,
+    }
+
+    /// Prices received by `consumer` with `backpressure` and `capacity`, taken once the stream has delivered
+    /// `events` from a single connection
+    async fn backpressured(
+        consumer: Consumer,
+        backpressure: Backpressure,
+        capacity: usize,
+        events: Vec<serde_json::Value>,
+    ) -> Vec<(FeedId, String)> {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events.iter().map(ToString::to_string).collect(),
+        )])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            capacity,
+            backpressure,
+            reconnect: ReconnectPolicy::forever().base_delay(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let client = HermesClient::new(mock.url());
+        let ids = [ETH_USD, BTC_USD];
+        let mut updates: Pin<Box<dyn Stream<Item = ParsedPriceUpdate> + Send>> = match consumer {
+            Consumer::Channel => {
+                let receiver = client
+                    .stream_to_channel_with_options(&ids, capacity, options)
+                    .await
+                    .unwrap();
+                Box::pin(stream::unfold(receiver, |mut receiver| async move {
+                    Some((receiver.recv().await?, receiver))
+                }))
+            }
+            Consumer::Subscription => {
+                Box::pin(client.subscribe_with_options(&ids, options).await.unwrap())
+            }
+            Consumer::Shared => {
+                Box::pin(client.shared_stream(options).subscribe(&ids).await.unwrap())
+            }
+        };
+        // the consumer falls behind
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut received = Vec::new();
+        while let Ok(Some(update)) =
+            tokio::time::timeout(Duration::from_millis(100), updates.next()).await
+        {
+            received.push((update.id, update.price.price));
+        }
+        received
+    }
+
+    /// Asserts a slow `consumer` receives the updates each [`Backpressure`] lets through
+    async fn assert_backpressure(consumer: Consumer) {
+        let eth = |prices: &[&str]| {
+            prices
+                .iter()
+                .map(|price| (ETH_USD, price.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let events: Vec<_> = (1..=5)
+            .map(|price| price_update_json(&[ETH_USD], price, price))
+            .collect();
+        assert_eq!(
+            backpressured(consumer, Backpressure::Block, 1, events.clone()).await,
+            eth(&["1", "2", "3", "4", "5"])
+        );
+        assert_eq!(
+            backpressured(consumer, Backpressure::DropNewest, 2, events.clone()).await,
+            eth(&["1", "2"])
+        );
+        assert_eq!(
+            backpressured(consumer, Backpressure::DropOldest, 2, events).await,
+            eth(&["1", "2", "4", "5"])
+        );
+
+        let events = (1..=4)
+            .map(|price| price_update_json(&[ETH_USD, BTC_USD], price, price))
+            .collect();
+        assert_eq!(
+            backpressured(consumer, Backpressure::CoalescePerFeed, 1, events).await,
+            [
+                (ETH_USD, "1".to_string()),
+                (BTC_USD, "4".to_string()),
+                (ETH_USD, "4".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_channel_backpressure() {
+        assert_backpressure(Consumer::Channel).await;
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_backpressure() {
+        assert_backpressure(Consumer::Subscription).await;
+    }
+
+    #[tokio::test]
+    async fn test_shared_subscription_backpressure() {
+        assert_backpressure(Consumer::Shared).await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_broadcast() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            (1..=3)
+                .map(|price| price_update_json(&[ETH_USD], price, price).to_string())
+                .collect(),
+        )])
+        .await
+        .unwrap();
+        let mut first = HermesClient::new(mock.url())
+            .stream_to_broadcast(&[ETH_USD], 1)
+            .await
+            .unwrap();
+        let mut second = first.resubscribe();
+        // receivers falling behind are told how many updates they missed
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(matches!(
+            first.recv().await,
+            Err(broadcast::error::RecvError::Lagged(2))
+        ));
+        assert_eq!(first.recv().await.unwrap().price.price, "3");
+        assert!(matches!(
+            second.recv().await,
+            Err(broadcast::error::RecvError::Lagged(2))
+        ));
+        assert_eq!(second.recv().await.unwrap().price.price, "3");
+    }
+
+    #[tokio::test]
+    async fn test_stream_ordering_params() {
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            vec![price_update_json(&[ETH_USD], 1, 1).to_string()],
+        )])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            allow_unordered: Some(true),
+            benchmarks_only: Some(false),
+            ..Default::default()
+        };
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+        subscription.next().await.unwrap();
+        let requests = mock.requests().await;
+        assert_eq!(
+            requests[0].query.as_deref(),
+            Some(
+                format!("ids%5B%5D={ETH_USD}&allow_unordered=true&benchmarks_only=false").as_str()
+            )
+        );
+        subscription.handle().abort();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_policy_gives_up() {
+        let mock = MockHermes::start(vec![RecordedResponse::status(
+            "/v2/updates/price/stream",
+            503,
+        )])
+        .await
+        .unwrap();
+        let (tx, mut failures) = tokio::sync::mpsc::unbounded_channel();
+        let token = CancellationToken::new();
+        let options = StreamOptions {
+            reconnect: ReconnectPolicy::new(2).base_delay(Duration::from_millis(1)),
+            on_failure: Some(Arc::new(move |failure: &StreamFailure| {
+                let _ = tx.send(failure.error.clone());
+            })),
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut subscription = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap();
+        assert!(subscription.next().await.is_none());
+
+        let handle = subscription.handle();
+        assert!(handle.stats().gave_up());
+        assert_eq!(handle.state(), StreamState::Stopped);
+        assert_eq!(mock.requests().await.len(), 3);
+        let mut last = String::new();
+        while let Ok(error) = failures.try_recv() {
+            last = error;
+        }
+        assert_eq!(last, "giving up after 3 failed connections");
+        // a token shared with other streams isn't cancelled
+        assert!(!token.is_cancelled());
+    }
+
+    async fn stream_requests(options: StreamOptions) -> Vec<crate::mock::RecordedRequest> {
+        let event = price_update_json(&[ETH_USD], 1, 1).to_string();
+        let mut response =
+            RecordedResponse::events("/v2/updates/price/stream", vec![event.clone(), event]);
+        response.event_ids = vec!["1".to_string(), "2".to_string()];
+        let mock = MockHermes::start(vec![response]).await.unwrap();
+        let (mut rx, handle) = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap()
+            .into_parts();
+        // two events per connection, wait for the second connection
+        for _ in 0..3 {
+            rx.recv().await.unwrap();
+        }
+        handle.abort();
+        mock.requests().await
+    }
+
+    #[tokio::test]
+    async fn test_stream_last_event_id() {
+        let requests = stream_requests(StreamOptions {
+            last_event_id: Some("0".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(requests[0].headers["last-event-id"], "0");
+        assert_eq!(requests[1].headers["last-event-id"], "2");
+
+        let requests = stream_requests(StreamOptions {
+            resume_from_last_event_id: false,
+            ..Default::default()
+        })
+        .await;
+        assert!(!requests[0].headers.contains_key("last-event-id"));
+        assert!(!requests[1].headers.contains_key("last-event-id"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_backfill() {
+        let event = |publish_time| price_update_json(&[ETH_USD], 1, publish_time).to_string();
+        let mock = MockHermes::start(vec![
+            RecordedResponse::events("/v2/updates/price/stream", vec![event(10)]),
+            RecordedResponse::events("/v2/updates/price/stream", vec![event(14)]),
+            // hermes returns the first update at or after the requested publish time
+            RecordedResponse::json("/v2/updates/price/11", price_update_json(&[ETH_USD], 1, 12)),
+            RecordedResponse::json("/v2/updates/price/12", price_update_json(&[ETH_USD], 1, 12)),
+            RecordedResponse::json("/v2/updates/price/13", price_update_json(&[ETH_USD], 1, 13)),
+        ])
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            backfill: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let (mut rx, handle) = HermesClient::new(mock.url())
+            .subscribe_with_options(&[ETH_USD], options)
+            .await
+            .unwrap()
+            .into_parts();
+        let mut published = Vec::new();
+        for _ in 0..4 {
+            published.push(rx.recv().await.unwrap().price.publish_time);
+        }
+        handle.abort();
+        assert_eq!(published, [10, 12, 13, 14]);
+        let backfilled: Vec<String> = mock
+            .requests()
+            .await
+            .into_iter()
+            .map(|req| req.path)
+            .filter(|path| path != "/v2/updates/price/stream")
+            .collect();
+        // an update received at 11 covers 12
+        assert_eq!(backfilled, ["/v2/updates/price/11", "/v2/updates/price/13"]);
+    }
+}
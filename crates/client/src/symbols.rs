@@ -0,0 +1,139 @@
+//! Resolution of human readable symbols, such as `Crypto.BTC/USD`, to feed ids
+
+use {
+    crate::{error::HermesError, types::FeedId, HermesClient},
+    std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    },
+};
+
+/// Feed ids of every symbol seen in the metadata, keyed by lowercased symbol and shared by clones of a client
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SymbolCache(Arc<RwLock<HashMap<String, FeedId>>>);
+
+impl SymbolCache {
+    fn lookup(&self, symbols: &[impl AsRef<str>]) -> Vec<Option<FeedId>> {
+        let cache = self.0.read().unwrap_or_else(|e| e.into_inner());
+        symbols
+            .iter()
+            .map(|symbol| cache.get(&symbol.as_ref().to_lowercase()).copied())
+            .collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    fn replace(&self, symbols: HashMap<String, FeedId>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = symbols;
+    }
+}
+
+impl HermesClient {
+    /// Resolves a symbol such as `Crypto.BTC/USD` to its feed id, ignoring case
+    ///
+    /// See [`HermesClient::resolve_feed_symbols`].
+    pub async fn resolve_feed(&self, symbol: &str) -> Result<FeedId, HermesError> {
+        Ok(self.resolve_feed_symbols(&[symbol]).await?[0])
+    }
+
+    /// Resolves symbols to feed ids, in the same order, ignoring case
+    ///
+    /// Aliases registered with [`HermesClient::feed_aliases`] are resolved first. Other symbols are looked up in the
+    /// metadata of every feed, which is fetched once and cached by the client and its clones. It is only fetched
+    /// again, revalidating with its ETag, when a symbol isn't found.
+    pub async fn resolve_feed_symbols(
+        &self,
+        symbols: &[impl AsRef<str>],
+    ) -> Result<Vec<FeedId>, HermesError> {
+        let aliased = self.feed_aliases.lookup(symbols);
+        let lookup = |cached: Vec<Option<FeedId>>| -> Vec<Option<FeedId>> {
+            aliased
+                .iter()
+                .zip(cached)
+                .map(|(alias, id)| alias.or(id))
+                .collect()
+        };
+        if let Some(ids) = lookup(self.symbol_cache.lookup(symbols))
+            .into_iter()
+            .collect()
+        {
+            return Ok(ids);
+        }
+        self.refresh_symbols().await?;
+        symbols
+            .iter()
+            .zip(lookup(self.symbol_cache.lookup(symbols)))
+            .map(|(symbol, id)| {
+                id.ok_or_else(|| HermesError::UnknownFeed(symbol.as_ref().to_string()))
+            })
+            .collect()
+    }
+
+    /// Feed ids of pyth's own `symbols`, ignoring aliases, fetching the metadata if it hasn't been yet
+    pub(crate) async fn pyth_symbol_ids(
+        &self,
+        symbols: &[impl AsRef<str>],
+    ) -> Result<Vec<Option<FeedId>>, HermesError> {
+        if self.symbol_cache.len() == 0 {
+            self.refresh_symbols().await?;
+        }
+        Ok(self.symbol_cache.lookup(symbols))
+    }
+
+    /// Replaces the cached symbols with those of the latest metadata, revalidated with its ETag
+    async fn refresh_symbols(&self) -> Result<(), HermesError> {
+        let metadata = self.get_price_feeds_metadata_cached(None, None).await?;
+        self.symbol_cache.replace(
+            metadata
+                .iter()
+                .filter_map(|feed| Some((feed.attributes.get("symbol")?.to_lowercase(), feed.id)))
+                .collect(),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::{BTC_USD, ETH_USD},
+        crate::mock::{MockHermes, RecordedResponse},
+    };
+
+    #[tokio::test]
+    async fn test_resolve_feed_symbols() {
+        let mock = MockHermes::start(vec![RecordedResponse::json(
+            "/v2/price_feeds",
+            serde_json::json!([
+                {"id": ETH_USD, "attributes": {"symbol": "Crypto.ETH/USD"}},
+                {"id": BTC_USD, "attributes": {"symbol": "Crypto.BTC/USD"}},
+            ]),
+        )])
+        .await
+        .unwrap();
+        let client = HermesClient::new(mock.url());
+
+        assert_eq!(
+            client
+                .resolve_feed_symbols(&["Crypto.BTC/USD", "crypto.eth/usd"])
+                .await
+                .unwrap(),
+            [BTC_USD, ETH_USD]
+        );
+        // served from the cache shared with clones
+        assert_eq!(
+            client.clone().resolve_feed("Crypto.ETH/USD").await.unwrap(),
+            ETH_USD
+        );
+        assert_eq!(mock.requests().await.len(), 1);
+
+        assert!(matches!(
+            client.resolve_feed("Crypto.SOL/USD").await,
+            Err(HermesError::UnknownFeed(symbol)) if symbol == "Crypto.SOL/USD"
+        ));
+        assert_eq!(mock.requests().await.len(), 2);
+    }
+}
@@ -0,0 +1,179 @@
+//! Timers driving retry backoff, so the same retry logic runs on native, in browsers and under test control
+
+use {
+    crate::rt,
+    futures_util::future::FutureExt,
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::sync::oneshot,
+};
+
+/// Future returned by [`Timer::sleep`]
+#[cfg(not(target_arch = "wasm32"))]
+pub type Sleep = futures_util::future::BoxFuture<'static, ()>;
+
+/// Future returned by [`Timer::sleep`]
+#[cfg(target_arch = "wasm32")]
+pub type Sleep = futures_util::future::LocalBoxFuture<'static, ()>;
+
+/// Source of the delays between retries, see [`HermesClientBuilder::timer`](crate::builder::HermesClientBuilder::timer)
+pub trait Timer: Send + Sync {
+    /// Completes once `duration` has passed
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// Timer of the runtime, tokio on native targets and the browser's timers on wasm32
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RuntimeTimer;
+
+impl Timer for RuntimeTimer {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        rt::sleep(duration).boxed_local_or_send()
+    }
+}
+
+/// Boxes a future as [`Sleep`] for the current target
+trait BoxSleep {
+    fn boxed_local_or_send(self) -> Sleep;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<F: std::future::Future<Output = ()> + Send + 'static> BoxSleep for F {
+    fn boxed_local_or_send(self) -> Sleep {
+        self.boxed()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<F: std::future::Future<Output = ()> + 'static> BoxSleep for F {
+    fn boxed_local_or_send(self) -> Sleep {
+        self.boxed_local()
+    }
+}
+
+/// Timer which only advances when told to, for deterministic tests of retry behavior
+///
+/// Clones share the same time, so a test can keep a clone to advance the timer given to a client.
+#[derive(Debug, Clone, Default)]
+pub struct ManualTimer(Arc<Mutex<ManualState>>);
+
+#[derive(Debug, Default)]
+struct ManualState {
+    elapsed: Duration,
+    requested: Vec<Duration>,
+    /// Deadlines of pending sleeps, relative to the timer's creation
+    pending: Vec<(Duration, oneshot::Sender<()>)>,
+}
+
+impl ManualTimer {
+    /// Advances the timer by `duration`, completing every sleep which is due
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state();
+        state.elapsed += duration;
+        let elapsed = state.elapsed;
+        let (due, pending) = std::mem::take(&mut state.pending)
+            .into_iter()
+            .partition(|(deadline, _)| *deadline <= elapsed);
+        state.pending = pending;
+        for (_, wake) in due {
+            let _ = wake.send(());
+        }
+    }
+
+    /// Total time the timer has been advanced by
+    pub fn elapsed(&self) -> Duration {
+        self.state().elapsed
+    }
+
+    /// Durations of every sleep requested so far, in order
+    pub fn requested(&self) -> Vec<Duration> {
+        self.state().requested.clone()
+    }
+
+    /// Number of sleeps waiting for the timer to advance
+    pub fn pending(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ManualState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Timer for ManualTimer {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let mut state = self.state();
+        state.requested.push(duration);
+        let (wake, woken) = oneshot::channel();
+        if duration.is_zero() {
+            let _ = wake.send(());
+        } else {
+            let deadline = state.elapsed + duration;
+            state.pending.push((deadline, wake));
+        }
+        async move {
+            // a dropped timer completes its sleeps
+            let _ = woken.await;
+        }
+        .boxed_local_or_send()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+            retry::RetryPolicy,
+            HermesClient,
+        },
+    };
+
+    async fn wait_for_sleep(timer: &ManualTimer) {
+        while timer.pending() == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manual_timer_retries() {
+        let path = "/v2/updates/price/latest";
+        let mock = MockHermes::start(vec![
+            RecordedResponse::status(path, 503),
+            RecordedResponse::status(path, 503),
+            RecordedResponse::json(path, price_update_json(&[ETH_USD], 1, 1)),
+        ])
+        .await
+        .unwrap();
+        let timer = ManualTimer::default();
+        let client = HermesClient::builder(mock.url())
+            .retry_policy(
+                RetryPolicy::new(3)
+                    .base_delay(Duration::from_secs(60))
+                    .max_delay(Duration::from_secs(300))
+                    .jitter(false),
+            )
+            .timer(timer.clone())
+            .build()
+            .unwrap();
+        let request = tokio::spawn(async move { client.get_latest_price_feeds(&[ETH_USD]).await });
+
+        wait_for_sleep(&timer).await;
+        timer.advance(Duration::from_secs(59));
+        assert_eq!(timer.pending(), 1);
+        timer.advance(Duration::from_secs(1));
+        wait_for_sleep(&timer).await;
+        timer.advance(Duration::from_secs(120));
+
+        assert_eq!(request.await.unwrap().unwrap()[0].id, ETH_USD);
+        assert_eq!(
+            timer.requested(),
+            [Duration::from_secs(60), Duration::from_secs(120)]
+        );
+        assert_eq!(timer.elapsed(), Duration::from_secs(180));
+    }
+}
@@ -0,0 +1,188 @@
+//! Verification of the TWAPs calculated by hermes against prices sampled by the client
+//!
+//! Hermes calculates TWAPs from every slot's price on Pythnet. The client can only sample historical price updates,
+//! so small differences are expected, while large ones are evidence that the server's aggregation should be checked
+//! before it's used for settlement.
+//!
+//! ```no_run
+//! # async fn example(client: pyth_hermes_rs::HermesClient) -> Result<(), pyth_hermes_rs::error::HermesError> {
+//! use pyth_hermes_rs::feed_ids;
+//!
+//! let comparison = client.compare_twap(feed_ids::BTC_USD, 300, 30).await?;
+//! println!("{:.2} bps apart", comparison.difference_bps);
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{error::HermesError, types::FeedId, HermesClient},
+    futures_util::{stream, StreamExt, TryStreamExt},
+};
+
+/// TWAP reported by hermes along with one recomputed from sampled prices, see [`HermesClient::compare_twap`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwapComparison {
+    pub id: FeedId,
+    /// Unix timestamps in seconds of the window the server's TWAP covers
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub server_twap: f64,
+    pub client_twap: f64,
+    /// `client_twap - server_twap`
+    pub difference: f64,
+    /// Difference relative to the server's TWAP in basis points
+    pub difference_bps: f64,
+    /// Number of distinct prices the client's TWAP was computed from
+    pub samples: usize,
+}
+
+impl HermesClient {
+    /// Fetches the latest TWAP of `id` over `window_seconds` and recomputes it from `samples` historical prices
+    /// spread evenly over the same window
+    ///
+    /// Fails with [`HermesError::MissingFeeds`] if hermes has no TWAP or no historical prices for the feed.
+    pub async fn compare_twap(
+        &self,
+        id: FeedId,
+        window_seconds: u64,
+        samples: usize,
+    ) -> Result<TwapComparison, HermesError> {
+        let missing = || HermesError::MissingFeeds(vec![id]);
+        let twap = self
+            .get_latest_twaps(window_seconds, &[id])
+            .await?
+            .parsed
+            .and_then(|twaps| twaps.into_iter().find(|twap| twap.id == id))
+            .ok_or_else(missing)?;
+        let server_twap = twap.twap.to_f64().ok_or_else(missing)?;
+        let (start, end) = (twap.start_timestamp, twap.end_timestamp);
+
+        let samples = samples.max(1);
+        let step = (end - start) as f64 / samples as f64;
+        let requests: Vec<_> = (0..samples)
+            .map(|i| {
+                let at = start + (step * i as f64) as i64;
+                async move {
+                    let update = self.price_updates_by_time(at).ids([id]).send().await?;
+                    Ok::<_, HermesError>(update.parsed.unwrap_or_default())
+                }
+            })
+            .collect();
+        let feeds: Vec<_> = stream::iter(requests)
+            .buffered(self.chunking.max_concurrency)
+            .try_collect()
+            .await?;
+        let prices: Vec<_> = feeds
+            .into_iter()
+            .flatten()
+            .filter(|feed| feed.id == id)
+            .filter_map(|feed| Some((feed.price.publish_time, feed.price.to_f64()?)))
+            .collect();
+        let (client_twap, samples) =
+            time_weighted_average(prices, start, end).ok_or_else(missing)?;
+
+        let difference = client_twap - server_twap;
+        Ok(TwapComparison {
+            id,
+            start_timestamp: start,
+            end_timestamp: end,
+            server_twap,
+            client_twap,
+            difference,
+            difference_bps: difference / server_twap * 10_000.0,
+            samples,
+        })
+    }
+}
+
+/// Average of `(publish_time, price)` samples over `[start, end]`, each weighted by how long it was the latest
+/// price, along with the number of distinct samples
+///
+/// A sample published before `start` is the price at `start`.
+pub(crate) fn time_weighted_average(
+    mut prices: Vec<(i64, f64)>,
+    start: i64,
+    end: i64,
+) -> Option<(f64, usize)> {
+    prices.sort_by_key(|(publish_time, _)| *publish_time);
+    prices.dedup_by_key(|(publish_time, _)| *publish_time);
+    if prices.is_empty() {
+        return None;
+    }
+    if end <= start {
+        return Some((prices[prices.len() - 1].1, prices.len()));
+    }
+    let mut weighted = 0.0;
+    for (i, (publish_time, price)) in prices.iter().enumerate() {
+        let from = (*publish_time).clamp(start, end);
+        let until = prices
+            .get(i + 1)
+            .map_or(end, |(next, _)| (*next).clamp(start, end));
+        weighted += price * (until - from) as f64;
+    }
+    let covered = end - prices[0].0.clamp(start, end);
+    (covered > 0).then(|| (weighted / covered as f64, prices.len()))
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::ETH_USD,
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_time_weighted_average() {
+        assert_eq!(
+            time_weighted_average(vec![(30, 200.0), (0, 100.0), (30, 200.0)], 0, 60),
+            Some((150.0, 2))
+        );
+        // the price before the window applies from its start
+        assert_eq!(
+            time_weighted_average(vec![(-10, 100.0), (45, 200.0)], 0, 60),
+            Some((125.0, 2))
+        );
+        assert_eq!(time_weighted_average(Vec::new(), 0, 60), None);
+    }
+
+    #[tokio::test]
+    async fn test_compare_twap() {
+        let twap = json!({
+            "binary": {"encoding": "hex", "data": []},
+            "parsed": [{
+                "id": ETH_USD,
+                "start_timestamp": 1000,
+                "end_timestamp": 1060,
+                "twap": {"price": "14000000000", "conf": "0", "expo": -8, "publish_time": 1060},
+                "down_slots_ratio": "0",
+            }],
+        });
+        let mock = MockHermes::start(vec![
+            RecordedResponse::json("/v2/updates/twap/60/latest", twap),
+            RecordedResponse::json(
+                "/v2/updates/price/1000",
+                price_update_json(&[ETH_USD], 10_000_000_000, 1000),
+            ),
+            RecordedResponse::json(
+                "/v2/updates/price/1030",
+                price_update_json(&[ETH_USD], 20_000_000_000, 1030),
+            ),
+        ])
+        .await
+        .unwrap();
+
+        let comparison = HermesClient::new(mock.url())
+            .compare_twap(ETH_USD, 60, 2)
+            .await
+            .unwrap();
+        assert_eq!(comparison.server_twap, 140.0);
+        assert_eq!(comparison.client_twap, 150.0);
+        assert_eq!(comparison.difference, 10.0);
+        assert!((comparison.difference_bps - 714.29).abs() < 0.01);
+        assert_eq!(comparison.samples, 2);
+    }
+}
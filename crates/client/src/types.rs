@@ -1,19 +1,62 @@
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 /// URL of the public hermes api
 pub const PUBLIC_BASE_URL: &str = "https://hermes.pyth.network";
 
-#[derive(Debug, Deserialize)]
+/// A validated 32 byte price feed id
+///
+/// Parsing accepts hex with or without a `0x` prefix in any case, and displays as lowercase hex without a prefix
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FeedId([u8; 32]);
+
+/// Asset type used to filter price feed metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetType {
+    Crypto,
+    Equity,
+    Fx,
+    Metal,
+    Rates,
+    CryptoRedemptionRate,
+    Commodities,
+    CryptoIndex,
+    CryptoNav,
+}
+
+/// Encoding of binary price update data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncodingType {
+    #[default]
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedIdError {
+    /// The id did not contain 64 hex characters
+    InvalidLength(usize),
+    /// The id contained a non hex character
+    InvalidCharacter(char),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RpcPriceFeed {
-    pub id: String,
+    pub id: FeedId,
     pub price: RpcPrice,
     pub ema_price: RpcPrice,
     pub metadata: Option<RpcPriceFeedMetadata>,
     pub vaa: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RpcPrice {
     pub price: String,
     pub conf: String,
@@ -21,7 +64,7 @@ pub struct RpcPrice {
     pub publish_time: i64,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct RpcPriceFeedMetadata {
     pub emitter_chain: Option<i32>,
     pub prev_publish_time: Option<i64>,
@@ -29,19 +72,27 @@ pub struct RpcPriceFeedMetadata {
     pub slot: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PriceFeedMetadata {
-    pub id: String,
+    pub id: FeedId,
     pub attributes: HashMap<String, String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Metadata of a feed joined with its latest price, see [`crate::HermesClient::get_feeds_with_prices`]
+#[derive(Debug)]
+pub struct FeedWithPrice {
+    pub metadata: PriceFeedMetadata,
+    /// `None` when hermes did not return a price for the feed
+    pub price: Option<RpcPriceFeed>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct PriceUpdate {
     pub binary: BinaryUpdate,
     pub parsed: Option<Vec<RpcPriceFeed>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct BinaryUpdate {
     pub encoding: String,
     pub data: Vec<String>,
@@ -55,7 +106,7 @@ pub struct TwapsResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct ParsedPriceFeedTwap {
-    pub id: String,
+    pub id: FeedId,
     pub start_timestamp: i64,
     pub end_timestamp: i64,
     pub twap: RpcPrice,
@@ -79,25 +130,365 @@ pub struct ParsedPublisherStakeCap {
     pub cap: i64,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ParsedPriceUpdate {
-    pub id: String,
+    pub id: FeedId,
     pub price: RpcPrice,
     pub ema_price: RpcPrice,
-    pub metadata: RpcPriceFeedMetadata,
+    /// `None` when hermes didn't include the feed's metadata, which the update is delivered without
+    pub metadata: Option<RpcPriceFeedMetadata>,
+    /// Set on prices fetched over REST when a stream starts, see
+    /// [`StreamOptions::snapshot`](crate::stream::StreamOptions::snapshot)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub snapshot: bool,
+}
+
+impl From<RpcPriceFeed> for ParsedPriceUpdate {
+    fn from(feed: RpcPriceFeed) -> Self {
+        Self {
+            id: feed.id,
+            price: feed.price,
+            ema_price: feed.ema_price,
+            metadata: feed.metadata,
+            snapshot: false,
+        }
+    }
+}
+
+/// Optional query parameters for [`crate::HermesClient::get_price_updates_by_time`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct PriceUpdatesByTimeOptions {
+    /// If true, only include benchmark prices that are the initial price updates at a given timestamp
+    pub benchmarks_only: Option<bool>,
+    /// If true, allows unordered price updates to be included
+    pub allow_unordered: Option<bool>,
+}
+
+impl FeedId {
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parses hex encoded feed ids, failing with [`HermesError::InvalidFeedId`] listing every invalid id
+    ///
+    /// [`HermesError::InvalidFeedId`]: crate::error::HermesError::InvalidFeedId
+    pub fn parse_all(ids: &[impl AsRef<str>]) -> Result<Vec<Self>, crate::error::HermesError> {
+        let mut parsed = Vec::with_capacity(ids.len());
+        let mut invalid = Vec::new();
+        for id in ids {
+            match Self::parse(id.as_ref()) {
+                Ok(id) => parsed.push(id),
+                Err(err) => invalid.push((id.as_ref().to_string(), err)),
+            }
+        }
+        if !invalid.is_empty() {
+            return Err(crate::error::HermesError::InvalidFeedId(invalid));
+        }
+        Ok(parsed)
+    }
+
+    /// Parses a hex encoded feed id, optionally prefixed with `0x`
+    pub const fn parse(id: &str) -> Result<Self, FeedIdError> {
+        let mut hex = id.as_bytes();
+        if let [b'0', b'x' | b'X', rest @ ..] = hex {
+            hex = rest;
+        }
+        if hex.len() != 64 {
+            return Err(FeedIdError::InvalidLength(hex.len()));
+        }
+        let mut bytes = [0_u8; 32];
+        let mut i = 0;
+        while i < 32 {
+            let hi = match hex_value(hex[i * 2]) {
+                Ok(v) => v,
+                Err(e) => return Err(e),
+            };
+            let lo = match hex_value(hex[i * 2 + 1]) {
+                Ok(v) => v,
+                Err(e) => return Err(e),
+            };
+            bytes[i] = (hi << 4) | lo;
+            i += 1;
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Parses a hex encoded feed id, intended for declaring constants
+    ///
+    /// # Panics
+    ///
+    /// If `id` is not a valid feed id, use [`FeedId::parse`] for fallible parsing
+    pub const fn from_hex(id: &str) -> Self {
+        match Self::parse(id) {
+            Ok(id) => id,
+            Err(_) => panic!("invalid feed id"),
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Returns the lowercase hex encoding without a `0x` prefix
+    pub fn to_hex(&self) -> String {
+        self.to_string()
+    }
+}
+
+const fn hex_value(c: u8) -> Result<u8, FeedIdError> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(FeedIdError::InvalidCharacter(c as char)),
+    }
+}
+
+impl AsRef<FeedId> for FeedId {
+    fn as_ref(&self) -> &FeedId {
+        self
+    }
+}
+
+impl FromStr for FeedId {
+    type Err = FeedIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<&str> for FeedId {
+    type Error = FeedIdError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::parse(value)
+    }
+}
+
+impl PartialEq<str> for FeedId {
+    fn eq(&self, other: &str) -> bool {
+        Self::parse(other).is_ok_and(|other| other == *self)
+    }
+}
+
+impl PartialEq<&str> for FeedId {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl fmt::Display for FeedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FeedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FeedId({self})")
+    }
+}
+
+impl Serialize for FeedId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FeedId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Self::parse(&id).map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for FeedIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(f, "expected 64 hex characters, found {len}"),
+            Self::InvalidCharacter(c) => write!(f, "invalid hex character {c:?}"),
+        }
+    }
+}
+
+impl std::error::Error for FeedIdError {}
+
+impl AssetType {
+    /// Returns the value hermes expects for the `asset_type` query parameter
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Crypto => "crypto",
+            Self::Equity => "equity",
+            Self::Fx => "fx",
+            Self::Metal => "metal",
+            Self::Rates => "rates",
+            Self::CryptoRedemptionRate => "crypto_redemption_rate",
+            Self::Commodities => "commodities",
+            Self::CryptoIndex => "crypto_index",
+            Self::CryptoNav => "crypto_nav",
+        }
+    }
+}
+
+impl fmt::Display for AssetType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl RpcPrice {
     /// Converts the pyth reported price from an integer into a floating point
+    ///
+    /// Rounds to the nearest float, see [`RpcPrice::checked_to_f64`] for a conversion which fails instead.
     pub fn to_f64(&self) -> Option<f64> {
-        let price = self.price.parse::<u64>().ok()?;
+        let price = self.price.parse::<i64>().ok()?;
         Some(price as f64 / ((10_u64.pow(self.expo.unsigned_abs())) as f64))
     }
+
+    /// Time the price was published
+    pub fn published_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.publish_time.max(0) as u64)
+    }
+
+    /// Time elapsed between publishing and `now`, zero if the price was published after `now`
+    pub fn age_at(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.published_at()).unwrap_or_default()
+    }
+}
+
+/// Renders as `1606.44665033 ± 0.74 @ 2024-06-05T10:00:00Z`, with the exponent applied exactly
+impl fmt::Display for RpcPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ± {} @ {}",
+            apply_expo(&self.price, self.expo),
+            apply_expo(&self.conf, self.expo),
+            format_timestamp(self.publish_time)
+        )
+    }
+}
+
+/// Renders as `<feed id>: <price>`
+impl fmt::Display for RpcPriceFeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.price)
+    }
+}
+
+/// Renders as `<feed id>: <price>`
+impl fmt::Display for ParsedPriceUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.price)
+    }
+}
+
+/// Shifts the decimal point of an integer string by `expo`, trimming trailing zeros of the fraction
+pub(crate) fn apply_expo(value: &str, expo: i32) -> String {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value),
+    };
+    if expo >= 0 {
+        return format!("{sign}{digits}{}", "0".repeat(expo as usize));
+    }
+    let places = expo.unsigned_abs() as usize;
+    let digits = format!("{digits:0>width$}", width = places + 1);
+    let (int, frac) = digits.split_at(digits.len() - places);
+    match frac.trim_end_matches('0') {
+        "" => format!("{sign}{int}"),
+        frac => format!("{sign}{int}.{frac}"),
+    }
+}
+
+/// Formats seconds since the unix epoch as an RFC 3339 UTC timestamp
+pub(crate) fn format_timestamp(unix_seconds: i64) -> String {
+    let (days, secs) = (
+        unix_seconds.div_euclid(86400),
+        unix_seconds.rem_euclid(86400),
+    );
+    // Howard Hinnant's civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
 }
 
 #[cfg(test)]
 mod test {
-    use super::RpcPrice;
+    use {
+        super::{format_timestamp, AssetType, FeedId, FeedIdError, RpcPrice},
+        crate::feed_ids::ETH_USD,
+    };
+
+    #[test]
+    fn test_feed_id_parse() {
+        let hex = ETH_USD.to_hex();
+        let id = FeedId::from_hex(&hex);
+        assert_eq!(id, ETH_USD);
+        assert_eq!(id.to_string(), hex);
+        assert_eq!(
+            format!("0x{}", hex.to_uppercase())
+                .parse::<FeedId>()
+                .unwrap(),
+            id
+        );
+        assert_eq!(id, hex.as_str());
+        assert_eq!("ff61".parse::<FeedId>(), Err(FeedIdError::InvalidLength(4)));
+        assert_eq!(
+            hex.replace('f', "g").parse::<FeedId>(),
+            Err(FeedIdError::InvalidCharacter('g'))
+        );
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{hex}\""));
+        assert_eq!(
+            serde_json::from_str::<FeedId>(&format!("\"0x{hex}\"")).unwrap(),
+            id
+        );
+    }
+
+    #[test]
+    fn test_rpc_price_display() {
+        let price = RpcPrice {
+            price: "160644670000".to_string(),
+            conf: "74000000".to_string(),
+            expo: -8,
+            publish_time: 1717581600,
+        };
+        assert_eq!(price.to_string(), "1606.4467 ± 0.74 @ 2024-06-05T10:00:00Z");
+
+        let price = RpcPrice {
+            price: "-5".to_string(),
+            conf: "0".to_string(),
+            expo: -3,
+            publish_time: 0,
+        };
+        assert_eq!(price.to_string(), "-0.005 ± 0 @ 1970-01-01T00:00:00Z");
+
+        let price = RpcPrice {
+            price: "12".to_string(),
+            conf: "1".to_string(),
+            expo: 2,
+            publish_time: 951782400,
+        };
+        assert_eq!(price.to_string(), "1200 ± 100 @ 2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(-1), "1969-12-31T23:59:59Z");
+    }
 
     #[test]
     fn test_rpc_price_to_f64() {
@@ -114,6 +505,39 @@ mod test {
             expo: -8,
             publish_time: 1744523627,
         };
-        assert_eq!(price.to_f64().unwrap(), 1606.44665033)
+        assert_eq!(price.to_f64().unwrap(), 1606.44665033);
+        let price = RpcPrice {
+            price: "-2500000".to_string(),
+            conf: "1000".to_string(),
+            expo: -6,
+            publish_time: 1744523627,
+        };
+        assert_eq!(price.to_f64().unwrap(), -2.5)
+    }
+
+    #[test]
+    fn test_asset_type_serialization() {
+        for asset_type in [
+            AssetType::Crypto,
+            AssetType::Equity,
+            AssetType::Fx,
+            AssetType::Metal,
+            AssetType::Rates,
+            AssetType::CryptoRedemptionRate,
+            AssetType::Commodities,
+            AssetType::CryptoIndex,
+            AssetType::CryptoNav,
+        ] {
+            assert_eq!(
+                serde_json::to_string(&asset_type).unwrap(),
+                format!("\"{asset_type}\"")
+            );
+        }
+        let req = reqwest::Client::new()
+            .get("http://localhost")
+            .query(&[("asset_type", Some(AssetType::CryptoRedemptionRate))])
+            .build()
+            .unwrap();
+        assert_eq!(req.url().query(), Some("asset_type=crypto_redemption_rate"));
     }
 }
@@ -0,0 +1,365 @@
+//! Integration with [axum](https://docs.rs/axum) web backends
+//!
+//! [`HermesState`] holds a client and a [`PriceCache`] kept up to date by a subscription. Handlers take the cache as
+//! an extractor from any state it can be derived from with [`FromRef`], and [`router`] serves the cached prices,
+//! including an SSE route re-broadcasting updates to browsers, along with the client's [`Diagnostics`].
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use pyth_hermes_rs::{types::FeedId, web::{self, HermesState}, HermesClient};
+//!
+//! let eth = FeedId::from_hex("ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace");
+//! let state = HermesState::new(HermesClient::new("https://hermes.pyth.network"));
+//! state.follow(&[eth]).await?.detach();
+//!
+//! let app = axum::Router::new()
+//!     .nest("/hermes", web::router())
+//!     .with_state(state);
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+//! axum::serve(listener, app).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{
+        diagnostics::Diagnostics,
+        error::HermesError,
+        rt,
+        stream::{StreamHandle, Subscription},
+        types::{FeedId, ParsedPriceUpdate},
+        HermesClient,
+    },
+    axum::{
+        extract::{FromRef, FromRequestParts, Path, State},
+        http::{request::Parts, StatusCode},
+        response::sse::{Event, KeepAlive, Sse},
+        routing::get,
+        Json, Router,
+    },
+    futures_util::stream::{self, Stream},
+    std::{
+        collections::HashMap,
+        convert::Infallible,
+        fmt,
+        sync::{Arc, RwLock},
+    },
+    tokio::sync::broadcast,
+};
+
+/// Number of updates buffered for each SSE client before it starts missing updates
+const BROADCAST_CAPACITY: usize = 1024;
+
+/// Latest price of each feed, shared between request handlers
+///
+/// Clones share the same prices. Usable as an axum extractor when it can be derived from the router's state.
+#[derive(Clone)]
+pub struct PriceCache {
+    prices: Arc<RwLock<HashMap<FeedId, ParsedPriceUpdate>>>,
+    updates: broadcast::Sender<ParsedPriceUpdate>,
+}
+
+/// Shared state for routers embedding the client
+#[derive(Clone)]
+pub struct HermesState {
+    pub client: HermesClient,
+    pub cache: PriceCache,
+}
+
+impl Default for PriceCache {
+    fn default() -> Self {
+        Self {
+            prices: Default::default(),
+            updates: broadcast::channel(BROADCAST_CAPACITY).0,
+        }
+    }
+}
+
+impl fmt::Debug for PriceCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriceCache")
+            .field("feeds", &self.read().len())
+            .field("listeners", &self.updates.receiver_count())
+            .finish()
+    }
+}
+
+impl fmt::Debug for HermesState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HermesState")
+            .field("base_url", &self.client.base_url)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl PriceCache {
+    /// Stores `update` and broadcasts it to listeners, returning whether it was stored
+    ///
+    /// Updates older than the cached price of their feed are dropped, as are repeats of it with the same price,
+    /// confidence and publish time, so listeners aren't woken by feeds which haven't moved.
+    pub fn insert(&self, update: ParsedPriceUpdate) -> bool {
+        {
+            let mut prices = self.prices.write().unwrap_or_else(|e| e.into_inner());
+            if let Some(cached) = prices.get(&update.id) {
+                let (cached, new) = (&cached.price, &update.price);
+                if cached.publish_time > new.publish_time
+                    || (cached.publish_time == new.publish_time
+                        && cached.price == new.price
+                        && cached.conf == new.conf)
+                {
+                    return false;
+                }
+            }
+            prices.insert(update.id, update.clone());
+        }
+        // only fails when nobody is listening
+        let _ = self.updates.send(update);
+        true
+    }
+
+    /// Latest cached price of `id`
+    pub fn get(&self, id: &FeedId) -> Option<ParsedPriceUpdate> {
+        self.read().get(id).cloned()
+    }
+
+    /// Latest cached price of every feed, sorted by feed id
+    pub fn snapshot(&self) -> Vec<ParsedPriceUpdate> {
+        let mut prices: Vec<_> = self.read().values().cloned().collect();
+        prices.sort_by_key(|update| update.id);
+        prices
+    }
+
+    /// Receives updates stored from now on
+    pub fn updates(&self) -> broadcast::Receiver<ParsedPriceUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Stores every update received by `subscription`, returning the handle controlling the stream
+    pub fn follow(&self, subscription: Subscription) -> StreamHandle {
+        let (mut updates, handle) = subscription.into_parts();
+        let cache = self.clone();
+        // ends once the stream is stopped
+        drop(rt::spawn(async move {
+            while let Some(update) = updates.recv().await {
+                cache.insert(update);
+            }
+        }));
+        handle
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<FeedId, ParsedPriceUpdate>> {
+        self.prices.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HermesState {
+    pub fn new(client: HermesClient) -> Self {
+        Self {
+            client,
+            cache: PriceCache::default(),
+        }
+    }
+
+    /// Subscribes to `ids`, storing their updates in the cache
+    pub async fn follow(&self, ids: &[impl AsRef<FeedId>]) -> Result<StreamHandle, HermesError> {
+        Ok(self.cache.follow(self.client.subscribe(ids).await?))
+    }
+}
+
+impl FromRef<HermesState> for HermesClient {
+    fn from_ref(state: &HermesState) -> Self {
+        state.client.clone()
+    }
+}
+
+impl FromRef<HermesState> for PriceCache {
+    fn from_ref(state: &HermesState) -> Self {
+        state.cache.clone()
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for PriceCache
+where
+    PriceCache: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_ref(state))
+    }
+}
+
+/// Routes serving the cached prices and the client's state
+///
+/// - `GET /prices` returns every cached price
+/// - `GET /prices/{id}` returns the price of a feed, or 404 if it isn't cached
+/// - `GET /prices/stream` streams updates as `price_update` SSE events
+/// - `GET /status` returns the client's [`Diagnostics`]
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    PriceCache: FromRef<S>,
+    HermesClient: FromRef<S>,
+{
+    Router::new()
+        .route("/prices", get(latest_prices))
+        .route("/prices/stream", get(price_stream))
+        .route("/prices/{id}", get(latest_price))
+        .route("/status", get(diagnostics))
+}
+
+/// Handler returning every cached price
+pub async fn latest_prices(cache: PriceCache) -> Json<Vec<ParsedPriceUpdate>> {
+    Json(cache.snapshot())
+}
+
+/// Handler returning the cached price of the feed in the path
+pub async fn latest_price(
+    cache: PriceCache,
+    Path(id): Path<FeedId>,
+) -> Result<Json<ParsedPriceUpdate>, StatusCode> {
+    cache.get(&id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Handler re-broadcasting updates stored in the cache as SSE events
+pub async fn price_stream(cache: PriceCache) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream::unfold(cache.updates(), |mut updates| async move {
+        loop {
+            match updates.recv().await {
+                Ok(update) => {
+                    let event = Event::default()
+                        .event("price_update")
+                        .json_data(&update)
+                        .unwrap_or_default();
+                    return Some((Ok(event), updates));
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    log::warn!("sse client fell behind, skipped {missed} price updates");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Handler returning the client's [`Diagnostics`]
+pub async fn diagnostics(State(client): State<HermesClient>) -> Json<Diagnostics> {
+    Json(client.diagnostics())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            feed_ids::{BTC_USD, ETH_USD},
+            mock::{price_update_json, MockHermes, RecordedResponse},
+        },
+        futures_util::StreamExt,
+        reqwest_eventsource::{Event as SseEvent, EventSource},
+        std::time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_router() {
+        let events: Vec<_> = (1..=3)
+            .map(|i| price_update_json(&[ETH_USD], i, i).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let state = HermesState::new(HermesClient::new(mock.url()));
+        let handle = state.follow(&[ETH_USD]).await.unwrap();
+        while state
+            .cache
+            .get(&ETH_USD)
+            .is_none_or(|update| update.price.publish_time < 3)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        handle.abort();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hermes", listener.local_addr().unwrap());
+        let app = Router::new()
+            .nest("/hermes", router())
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let price: serde_json::Value = reqwest::get(format!("{url}/prices/{ETH_USD}"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(price["price"]["publish_time"], 3);
+        let missing = reqwest::get(format!("{url}/prices/{BTC_USD}"))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        let _shared = state.client.shared_stream(Default::default());
+        let status: serde_json::Value = reqwest::get(format!("{url}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status["streams"][0]["id"], handle.id());
+        assert_eq!(status["streams"][0]["feed_count"], 1);
+        assert_eq!(status["shared_streams"][0]["subscribers"], 0);
+        assert_eq!(status["catalog_entries"], 0);
+
+        let mut events = EventSource::get(format!("{url}/prices/stream"));
+        assert!(matches!(events.next().await, Some(Ok(SseEvent::Open))));
+        let mut update = state.cache.get(&ETH_USD).unwrap();
+        update.price.publish_time = 4;
+        state.cache.insert(update);
+        match events.next().await {
+            Some(Ok(SseEvent::Message(message))) => {
+                assert_eq!(message.event, "price_update");
+                let update: ParsedPriceUpdate = serde_json::from_str(&message.data).unwrap();
+                assert_eq!(update.price.publish_time, 4);
+            }
+            other => panic!("expected a price update, got {other:?}"),
+        }
+        events.close();
+        assert_eq!(latest_prices(state.cache).await.0.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_skips_unchanged_updates() {
+        let cache = PriceCache::default();
+        let mut updates = cache.updates();
+        let update = |price: i64, publish_time: i64| -> ParsedPriceUpdate {
+            serde_json::from_value(
+                price_update_json(&[ETH_USD], price, publish_time)["parsed"][0].clone(),
+            )
+            .unwrap()
+        };
+
+        assert!(cache.insert(update(10, 5)));
+        // repeated, e.g. replayed after a reconnect
+        assert!(!cache.insert(update(10, 5)));
+        // older than the cached price
+        assert!(!cache.insert(update(11, 4)));
+        // corrected price for the same publish time
+        assert!(cache.insert(update(12, 5)));
+        assert!(cache.insert(update(12, 6)));
+
+        let received: Vec<_> = std::iter::from_fn(|| updates.try_recv().ok())
+            .map(|update| (update.price.price, update.price.publish_time))
+            .collect();
+        assert_eq!(
+            received,
+            [("10".into(), 5), ("12".into(), 5), ("12".to_string(), 6)]
+        );
+        assert_eq!(cache.get(&ETH_USD).unwrap().price.publish_time, 6);
+    }
+}
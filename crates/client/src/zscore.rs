@@ -0,0 +1,200 @@
+//! Price updates annotated with how unusual their move is, for alerting on statistically normalized signals
+//!
+//! Each feed's returns, the fractional change from one update to the next, are kept over a rolling window. An
+//! update's z-score is the number of standard deviations its return is from the mean of the window's preceding
+//! returns, so a threshold such as `|z| > 4` means the same across volatile and quiet feeds.
+
+use {
+    crate::{
+        error::HermesError,
+        stream::{StreamHandle, StreamOptions},
+        types::*,
+        HermesClient,
+    },
+    std::collections::{HashMap, VecDeque},
+};
+
+/// Fewest preceding returns a z-score is computed from
+const MIN_SAMPLES: usize = 2;
+
+/// Price update along with its return and z-score
+#[derive(Debug, Clone)]
+pub struct ScoredUpdate {
+    pub update: ParsedPriceUpdate,
+    /// Fractional change from the feed's previous price, `None` for its first update
+    pub return_: Option<f64>,
+    /// Standard deviations of `return_` from the window's mean, `None` until the window has enough returns or when
+    /// they don't vary
+    pub z_score: Option<f64>,
+}
+
+/// Rolling window of returns of each feed, producing a [`ScoredUpdate`] for each update
+#[derive(Debug)]
+pub struct ZScoreTracker {
+    window: usize,
+    feeds: HashMap<FeedId, FeedReturns>,
+}
+
+#[derive(Debug, Default)]
+struct FeedReturns {
+    last_price: Option<f64>,
+    last_publish_time: i64,
+    returns: VecDeque<f64>,
+}
+
+impl ZScoreTracker {
+    /// Scores updates against the `window` most recent returns of their feed
+    ///
+    /// # Panics
+    ///
+    /// If `window` is less than 2
+    pub fn new(window: usize) -> Self {
+        assert!(
+            window >= MIN_SAMPLES,
+            "window must hold at least two returns"
+        );
+        Self {
+            window,
+            feeds: HashMap::new(),
+        }
+    }
+
+    /// Scores `update` and adds its return to the window
+    ///
+    /// Updates which aren't newer than the feed's last one, such as repeats after a reconnect, are scored without
+    /// changing the window.
+    pub fn update(&mut self, update: ParsedPriceUpdate) -> ScoredUpdate {
+        let feed = self.feeds.entry(update.id).or_default();
+        let price = update.price.to_f64();
+        let newer = update.price.publish_time > feed.last_publish_time;
+        let return_ = feed
+            .last_price
+            .zip(price)
+            .filter(|(last, _)| *last != 0.0 && newer)
+            .map(|(last, price)| (price - last) / last);
+        let z_score = return_.and_then(|r| z_score(&feed.returns, r));
+        if newer && price.is_some() {
+            feed.last_price = price;
+            feed.last_publish_time = update.price.publish_time;
+            if let Some(r) = return_ {
+                if feed.returns.len() == self.window {
+                    feed.returns.pop_front();
+                }
+                feed.returns.push_back(r);
+            }
+        }
+        ScoredUpdate {
+            update,
+            return_,
+            z_score,
+        }
+    }
+}
+
+/// Z-score of `value` against the sample mean and standard deviation of `samples`
+fn z_score(samples: &VecDeque<f64>, value: f64) -> Option<f64> {
+    if samples.len() < MIN_SAMPLES {
+        return None;
+    }
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let std_dev = variance.sqrt();
+    (std_dev > 0.0).then(|| (value - mean) / std_dev)
+}
+
+impl HermesClient {
+    /// Streams price updates for `ids`, invoking `on_update` with each one scored against the `window` most recent
+    /// returns of its feed, see [`ZScoreTracker`]
+    pub async fn stream_price_z_scores<F>(
+        &self,
+        ids: &[impl AsRef<FeedId>],
+        window: usize,
+        mut on_update: F,
+    ) -> Result<StreamHandle, HermesError>
+    where
+        F: FnMut(ScoredUpdate) + Send + 'static,
+    {
+        let mut tracker = ZScoreTracker::new(window);
+        self.stream_price_updates_with_options(ids, StreamOptions::default(), move |update| {
+            on_update(tracker.update(update));
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::feed_ids::ETH_USD,
+        crate::mock::{price_update_json, MockHermes, RecordedResponse},
+    };
+
+    fn update(price: i64, publish_time: i64) -> ParsedPriceUpdate {
+        serde_json::from_value(
+            price_update_json(&[ETH_USD], price, publish_time)["parsed"][0].clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_z_scores() {
+        let mut tracker = ZScoreTracker::new(3);
+        // returns of +1%, -1%, +1%, then a 10% jump
+        let prices = [
+            100_000_000,
+            101_000_000,
+            99_990_000,
+            100_989_900,
+            111_088_890,
+        ];
+        let scored: Vec<_> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, price)| tracker.update(update(*price, i as i64 + 1)))
+            .collect();
+
+        assert_eq!(scored[0].return_, None);
+        assert!((scored[1].return_.unwrap() - 0.01).abs() < 1e-9);
+        // too few returns in the window
+        assert!(scored[2].z_score.is_none());
+        assert!(scored[3].z_score.unwrap().abs() < 1.0);
+        assert!(scored[4].z_score.unwrap() > 4.0);
+
+        // a repeat doesn't count as a return of zero
+        let repeat = tracker.update(update(111_088_890, 5));
+        assert_eq!(repeat.return_, None);
+        assert_eq!(tracker.feeds[&ETH_USD].returns.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stream_price_z_scores() {
+        let events = [100, 101, 100, 101, 120]
+            .into_iter()
+            .enumerate()
+            .map(|(i, price)| price_update_json(&[ETH_USD], price, i as i64 + 1).to_string())
+            .collect();
+        let mock = MockHermes::start(vec![RecordedResponse::events(
+            "/v2/updates/price/stream",
+            events,
+        )])
+        .await
+        .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = HermesClient::new(mock.url())
+            .stream_price_z_scores(&[ETH_USD], 10, move |scored| {
+                let _ = tx.send(scored);
+            })
+            .await
+            .unwrap();
+        let mut scored = Vec::new();
+        for _ in 0..5 {
+            scored.push(rx.recv().await.unwrap());
+        }
+        handle.abort();
+
+        assert_eq!(scored[4].update.price.publish_time, 5);
+        assert!(scored[4].z_score.unwrap() > 2.0);
+    }
+}